@@ -0,0 +1,161 @@
+//! Units shared by the channels: the length counter and the volume
+//! envelope, both clocked by the frame sequencer.
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct LengthCounter {
+    enabled: bool,
+    counter: u16,
+    /// 64, or 256 for the wave channel.
+    max: u16,
+}
+
+impl LengthCounter {
+    pub fn new(max: u16) -> Self {
+        Self {
+            enabled: false,
+            counter: 0,
+            max,
+        }
+    }
+
+    /// Loads the length from the NRx1 length bits, which count up to the
+    /// maximum.
+    pub fn load(&mut self, length: u16) {
+        self.counter = self.max - length;
+    }
+
+    pub fn set_enabled(&mut self, to: bool) {
+        self.enabled = to;
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// A trigger with an expired length restarts it at the maximum.
+    pub fn trigger(&mut self) {
+        if self.counter == 0 {
+            self.counter = self.max;
+        }
+    }
+
+    /// Returns whether the length just ran out, turning the channel off.
+    pub fn clock(&mut self) -> bool {
+        if !self.enabled || self.counter == 0 {
+            return false;
+        }
+
+        self.counter -= 1;
+        self.counter == 0
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Envelope {
+    initial_volume: u8,
+    increase: bool,
+    /// Frame sequencer envelope clocks between steps, 0 freezes it.
+    pace: u8,
+    volume: u8,
+    timer: u8,
+}
+
+impl Envelope {
+    /// The DAC is on as long as NRx2 asks for anything but silence going
+    /// down.
+    pub fn dac_enabled(nrx2: u8) -> bool {
+        nrx2 & 0b1111_1000 != 0
+    }
+
+    pub fn write(&mut self, nrx2: u8) {
+        self.initial_volume = nrx2 >> 4;
+        self.increase = nrx2 & 0b0000_1000 != 0;
+        self.pace = nrx2 & 0b0000_0111;
+    }
+
+    pub fn trigger(&mut self) {
+        self.volume = self.initial_volume;
+        self.timer = self.pace;
+    }
+
+    pub fn volume(&self) -> u8 {
+        self.volume
+    }
+
+    pub fn clock(&mut self) {
+        if self.pace == 0 {
+            return;
+        }
+
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer > 0 {
+            return;
+        }
+        self.timer = self.pace;
+
+        if self.increase && self.volume < 15 {
+            self.volume += 1;
+        } else if !self.increase && self.volume > 0 {
+            self.volume -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn length_runs_out() {
+        let mut length = LengthCounter::new(64);
+        length.load(62);
+        length.set_enabled(true);
+
+        assert!(!length.clock());
+        assert!(length.clock());
+        assert!(!length.clock());
+
+        length.trigger();
+        for _ in 0..63 {
+            assert!(!length.clock());
+        }
+        assert!(length.clock());
+    }
+
+    #[test]
+    fn disabled_length_holds() {
+        let mut length = LengthCounter::new(256);
+        length.load(255);
+
+        assert!(!length.clock());
+        length.set_enabled(true);
+        assert!(length.clock());
+    }
+
+    #[test]
+    fn envelope_steps_every_pace_clocks() {
+        let mut envelope = Envelope::default();
+        envelope.write(0b1110_0010);
+        envelope.trigger();
+        assert_eq!(envelope.volume(), 14);
+
+        envelope.clock();
+        assert_eq!(envelope.volume(), 14);
+        envelope.clock();
+        assert_eq!(envelope.volume(), 13);
+
+        envelope.write(0b0000_1001);
+        envelope.trigger();
+        for _ in 0..20 {
+            envelope.clock();
+        }
+        assert_eq!(envelope.volume(), 15);
+    }
+
+    #[test]
+    fn dac_follows_nrx2() {
+        assert!(!Envelope::dac_enabled(0b0000_0111));
+        assert!(Envelope::dac_enabled(0b0000_1000));
+        assert!(Envelope::dac_enabled(0b0001_0000));
+    }
+}
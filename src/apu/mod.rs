@@ -0,0 +1,515 @@
+//! Audio processing unit: two square channels, the wave channel and the
+//! noise channel, mixed to stereo by NR50/NR51.
+
+mod channel;
+mod noise;
+mod output;
+mod record;
+mod resample;
+mod square;
+mod wave;
+
+pub use self::output::{
+    to_i16, HighPass, Quality, SampleBuffer, CPU_CLOCK_HZ, CYCLES_PER_FRAME, DEFAULT_SAMPLE_RATE,
+};
+pub use self::record::{PcmFormat, PcmWriter, Recorder, Recording, SampleSink, SharedSink};
+
+use std::io;
+
+use self::noise::Noise;
+use self::square::Square;
+use self::wave::{Wave, WAVE_RAM_SIZE};
+use crate::mmu::Addr;
+
+pub const NR10_ADDR: Addr = 0xFF10;
+pub const NR11_ADDR: Addr = 0xFF11;
+pub const NR12_ADDR: Addr = 0xFF12;
+pub const NR13_ADDR: Addr = 0xFF13;
+pub const NR14_ADDR: Addr = 0xFF14;
+pub const NR21_ADDR: Addr = 0xFF16;
+pub const NR22_ADDR: Addr = 0xFF17;
+pub const NR23_ADDR: Addr = 0xFF18;
+pub const NR24_ADDR: Addr = 0xFF19;
+pub const NR30_ADDR: Addr = 0xFF1A;
+pub const NR31_ADDR: Addr = 0xFF1B;
+pub const NR32_ADDR: Addr = 0xFF1C;
+pub const NR33_ADDR: Addr = 0xFF1D;
+pub const NR34_ADDR: Addr = 0xFF1E;
+pub const NR41_ADDR: Addr = 0xFF20;
+pub const NR42_ADDR: Addr = 0xFF21;
+pub const NR43_ADDR: Addr = 0xFF22;
+pub const NR44_ADDR: Addr = 0xFF23;
+pub const NR50_ADDR: Addr = 0xFF24;
+pub const NR51_ADDR: Addr = 0xFF25;
+pub const NR52_ADDR: Addr = 0xFF26;
+pub const WAVE_RAM_START: Addr = 0xFF30;
+pub const WAVE_RAM_END: Addr = 0xFF3F;
+
+pub const NR52_POWER: u8 = 0b1000_0000;
+
+/// T-cycles between frame sequencer steps, 512 Hz.
+pub const FRAME_SEQUENCER_PERIOD: u32 = 8192;
+
+const REGISTER_COUNT: usize = (NR51_ADDR - NR10_ADDR + 1) as usize;
+
+/// Bits of NR10-NR51 which read as 1, write-only ones included.
+fn read_mask(addr: Addr) -> u8 {
+    match addr {
+        NR10_ADDR => 0x80,
+        NR11_ADDR | NR21_ADDR => 0x3F,
+        NR12_ADDR | NR22_ADDR | NR42_ADDR | NR43_ADDR => 0x00,
+        NR14_ADDR | NR24_ADDR | NR34_ADDR | NR44_ADDR => 0xBF,
+        NR30_ADDR => 0x7F,
+        NR32_ADDR => 0x9F,
+        NR50_ADDR | NR51_ADDR => 0x00,
+        // Periods, NR31 and NR41 lengths, and the two unmapped holes.
+        _ => 0xFF,
+    }
+}
+
+/// Analog output of a channel DAC, None being a DAC turned off.
+fn dac(output: Option<u8>) -> f32 {
+    match output {
+        Some(value) => f32::from(value) / 7.5 - 1.0,
+        None => 0.0,
+    }
+}
+
+/// Left and right output, from -1.0 to 1.0, of the channels together.
+fn mix(channels: &[[f32; 2]; 4]) -> [f32; 2] {
+    channels.iter().fold([0.0; 2], |[left, right], channel| {
+        [left + channel[0], right + channel[1]]
+    })
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Apu {
+    powered: bool,
+    /// NR10-NR51 as last written, for reading back.
+    registers: [u8; REGISTER_COUNT],
+
+    square1: Square,
+    square2: Square,
+    wave: Wave,
+    noise: Noise,
+
+    frame_step: u8,
+    frame_timer: u32,
+
+    output: SampleBuffer,
+    recorder: Option<Recorder>,
+}
+
+impl Default for Apu {
+    fn default() -> Self {
+        Self::new(DEFAULT_SAMPLE_RATE)
+    }
+}
+
+impl Apu {
+    /// An APU producing `sample_rate` stereo samples per second.
+    pub fn new(sample_rate: u32) -> Self {
+        Self::with_output(SampleBuffer::new(sample_rate, Quality::default()))
+    }
+
+    /// An APU feeding its output into `output`.
+    pub fn with_output(output: SampleBuffer) -> Self {
+        Self {
+            powered: false,
+            registers: [0; REGISTER_COUNT],
+            square1: Square::new(true),
+            square2: Square::new(false),
+            wave: Wave::default(),
+            noise: Noise::default(),
+            frame_step: 0,
+            frame_timer: 0,
+            output,
+            recorder: None,
+        }
+    }
+
+    pub fn is_register(addr: Addr) -> bool {
+        matches!(addr, NR10_ADDR..=NR52_ADDR | WAVE_RAM_START..=WAVE_RAM_END)
+    }
+
+    pub fn read_register(&self, addr: Addr) -> u8 {
+        match addr {
+            NR52_ADDR => self.read_nr52(),
+            WAVE_RAM_START..=WAVE_RAM_END => self.wave.read_ram(usize::from(addr - WAVE_RAM_START)),
+            _ => self.registers[usize::from(addr - NR10_ADDR)] | read_mask(addr),
+        }
+    }
+
+    pub fn write_register(&mut self, addr: Addr, value: u8) {
+        match addr {
+            NR52_ADDR => return self.write_nr52(value),
+            WAVE_RAM_START..=WAVE_RAM_END => {
+                let offset = usize::from(addr - WAVE_RAM_START);
+                return self.wave.write_ram(offset, value);
+            }
+            // Everything else is frozen while powered off.
+            _ if !self.powered => return,
+            _ => self.registers[usize::from(addr - NR10_ADDR)] = value,
+        }
+
+        match addr {
+            NR10_ADDR..=NR14_ADDR => self.square1.write(addr - NR10_ADDR, value),
+            NR21_ADDR..=NR24_ADDR => self.square2.write(addr - NR21_ADDR + 1, value),
+            NR30_ADDR..=NR34_ADDR => self.wave.write(addr - NR30_ADDR, value),
+            NR41_ADDR..=NR44_ADDR => self.noise.write(addr - NR41_ADDR + 1, value),
+            _ => {}
+        }
+    }
+
+    fn read_nr52(&self) -> u8 {
+        let power = if self.powered { NR52_POWER } else { 0 };
+        let channels = [
+            self.square1.enabled(),
+            self.square2.enabled(),
+            self.wave.enabled(),
+            self.noise.enabled(),
+        ]
+        .iter()
+        .enumerate()
+        .fold(0, |bits, (index, on)| bits | (u8::from(*on) << index));
+
+        0b0111_0000 | power | channels
+    }
+
+    fn write_nr52(&mut self, value: u8) {
+        let powered = value & NR52_POWER != 0;
+
+        if self.powered && !powered {
+            // Powering off clears every register but wave RAM.
+            let mut wave = Wave::default();
+            for offset in 0..WAVE_RAM_SIZE {
+                wave.write_ram(offset, self.wave.read_ram(offset));
+            }
+
+            self.registers = [0; REGISTER_COUNT];
+            self.square1 = Square::new(true);
+            self.square2 = Square::new(false);
+            self.wave = wave;
+            self.noise = Noise::default();
+        } else if !self.powered && powered {
+            self.frame_step = 0;
+            self.frame_timer = 0;
+        }
+
+        self.powered = powered;
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.output.sample_rate()
+    }
+
+    pub fn output(&self) -> &SampleBuffer {
+        &self.output
+    }
+
+    pub fn output_mut(&mut self) -> &mut SampleBuffer {
+        &mut self.output
+    }
+
+    /// Takes the interleaved left/right samples produced so far.
+    pub fn take_samples(&mut self) -> Vec<f32> {
+        self.output.take_f32()
+    }
+
+    /// Takes the interleaved left/right samples produced so far as signed
+    /// 16-bit PCM.
+    pub fn take_samples_i16(&mut self) -> Vec<i16> {
+        self.output.take_i16()
+    }
+
+    /// Starts streaming everything the APU outputs from now on to `mix`,
+    /// resampled like the output, and each channel to its own sink in
+    /// `stems`. Ends a recording already running first.
+    pub fn start_recording(
+        &mut self,
+        mix: SharedSink,
+        stems: Option<[SharedSink; 4]>,
+    ) -> io::Result<()> {
+        self.stop_recording()?;
+        self.recorder = Some(Recorder::new(&self.output, mix, stems));
+
+        Ok(())
+    }
+
+    pub fn recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    /// Ends the recording, handing the last samples to the sinks and
+    /// finishing them.
+    pub fn stop_recording(&mut self) -> io::Result<()> {
+        match self.recorder.take() {
+            Some(recorder) => recorder.finish(),
+            None => Ok(()),
+        }
+    }
+
+    /// Advances by `cycles` T-cycles of the normal speed clock, which the
+    /// APU keeps in double speed.
+    pub fn tick(&mut self, cycles: u32) {
+        for _ in 0..cycles {
+            if self.powered {
+                self.tick_frame_sequencer();
+                self.square1.tick();
+                self.square2.tick();
+                self.wave.tick();
+                self.noise.tick();
+            }
+
+            let channels = self.channel_mixes();
+            let mix = mix(&channels);
+
+            self.output.push(mix[0], mix[1]);
+            if let Some(recorder) = &mut self.recorder {
+                recorder.push(mix, &channels);
+            }
+        }
+    }
+
+    fn tick_frame_sequencer(&mut self) {
+        self.frame_timer += 1;
+        if self.frame_timer < FRAME_SEQUENCER_PERIOD {
+            return;
+        }
+        self.frame_timer = 0;
+
+        match self.frame_step {
+            0 | 4 => self.clock_lengths(),
+            2 | 6 => {
+                self.clock_lengths();
+                self.square1.clock_sweep();
+            }
+            7 => {
+                self.square1.clock_envelope();
+                self.square2.clock_envelope();
+                self.noise.clock_envelope();
+            }
+            _ => {}
+        }
+
+        self.frame_step = (self.frame_step + 1) % 8;
+    }
+
+    fn clock_lengths(&mut self) {
+        self.square1.clock_length();
+        self.square2.clock_length();
+        self.wave.clock_length();
+        self.noise.clock_length();
+    }
+
+    /// Analog output of each channel, from -1.0 to 1.0, before panning.
+    pub fn channel_outputs(&self) -> [f32; 4] {
+        [
+            dac(self.square1.output()),
+            dac(self.square2.output()),
+            dac(self.wave.output()),
+            dac(self.noise.output()),
+        ]
+    }
+
+    /// Left and right output of each channel, panned and scaled by the
+    /// master volume, adding up to the mix from -1.0 to 1.0.
+    fn channel_mixes(&self) -> [[f32; 2]; 4] {
+        let mut mixes = [[0.0; 2]; 4];
+        if !self.powered {
+            return mixes;
+        }
+
+        let nr50 = self.registers[usize::from(NR50_ADDR - NR10_ADDR)];
+        let nr51 = self.registers[usize::from(NR51_ADDR - NR10_ADDR)];
+        let channels = self.channel_outputs();
+
+        for (channel, (mix, output)) in mixes.iter_mut().zip(channels.iter()).enumerate() {
+            for (side, mixed) in mix.iter_mut().enumerate() {
+                // NR51 has the left enables in its upper nibble, and NR50 the
+                // left volume in bits 4-6.
+                let shift = if side == 0 { 4 } else { 0 };
+                if (nr51 >> shift) & (1 << channel) == 0 {
+                    continue;
+                }
+                let volume = f32::from((nr50 >> shift) & 0b111) + 1.0;
+
+                *mixed = output / 4.0 * volume / 8.0;
+            }
+        }
+
+        mixes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::apu::record::test::recording_sinks;
+
+    fn powered_apu() -> Apu {
+        let mut output = SampleBuffer::new(CPU_CLOCK_HZ / 4, Quality::Fast);
+        output.set_high_pass(HighPass::Off);
+        let mut apu = Apu::with_output(output);
+        apu.write_register(NR52_ADDR, NR52_POWER);
+        apu.write_register(NR50_ADDR, 0x77);
+
+        apu
+    }
+
+    #[test]
+    fn register_read_masks() {
+        let mut apu = powered_apu();
+
+        apu.write_register(NR11_ADDR, 0b1000_0001);
+        apu.write_register(NR13_ADDR, 0x42);
+        apu.write_register(NR30_ADDR, 0x80);
+
+        assert_eq!(apu.read_register(NR10_ADDR), 0x80);
+        assert_eq!(apu.read_register(NR11_ADDR), 0b1011_1111);
+        assert_eq!(apu.read_register(NR13_ADDR), 0xFF);
+        assert_eq!(apu.read_register(NR30_ADDR), 0xFF);
+        assert_eq!(apu.read_register(0xFF15), 0xFF);
+        assert_eq!(apu.read_register(NR50_ADDR), 0x77);
+    }
+
+    #[test]
+    fn nr52_reports_channels() {
+        let mut apu = powered_apu();
+        assert_eq!(apu.read_register(NR52_ADDR), 0xF0);
+
+        apu.write_register(NR22_ADDR, 0xF0);
+        apu.write_register(NR24_ADDR, 0x80);
+        apu.write_register(NR42_ADDR, 0xF0);
+        apu.write_register(NR44_ADDR, 0x80);
+
+        assert_eq!(apu.read_register(NR52_ADDR), 0xFA);
+    }
+
+    #[test]
+    fn power_off_clears_registers_but_not_wave_ram() {
+        let mut apu = powered_apu();
+        apu.write_register(NR12_ADDR, 0xF0);
+        apu.write_register(NR14_ADDR, 0x80);
+        apu.write_register(WAVE_RAM_START, 0x5A);
+
+        apu.write_register(NR52_ADDR, 0);
+
+        assert_eq!(apu.read_register(NR52_ADDR), 0x70);
+        assert_eq!(apu.read_register(NR12_ADDR), 0x00);
+        assert_eq!(apu.read_register(NR50_ADDR), 0x00);
+        assert_eq!(apu.read_register(WAVE_RAM_START), 0x5A);
+
+        // Writes are ignored until powered on again.
+        apu.write_register(NR12_ADDR, 0xF0);
+        assert_eq!(apu.read_register(NR12_ADDR), 0x00);
+    }
+
+    #[test]
+    fn length_counts_at_256_hz() {
+        let mut apu = powered_apu();
+        apu.write_register(NR21_ADDR, 62);
+        apu.write_register(NR22_ADDR, 0xF0);
+        apu.write_register(NR24_ADDR, 0b1100_0000);
+
+        // Steps 0 and 2 clock the length.
+        apu.tick(FRAME_SEQUENCER_PERIOD * 2);
+        assert_eq!(apu.read_register(NR52_ADDR) & 0b10, 0b10);
+
+        apu.tick(FRAME_SEQUENCER_PERIOD);
+        assert_eq!(apu.read_register(NR52_ADDR) & 0b10, 0);
+    }
+
+    #[test]
+    fn panning_and_volume() {
+        let mut apu = powered_apu();
+        // Square 2 right after a trigger, low on the first step of its duty.
+        apu.write_register(NR22_ADDR, 0xF0);
+        apu.write_register(NR24_ADDR, 0x80);
+        apu.write_register(NR51_ADDR, 0b0000_0010);
+        apu.write_register(NR50_ADDR, 0b0000_0011);
+
+        let [left, right] = mix(&apu.channel_mixes());
+
+        assert_eq!(left, 0.0);
+        assert_eq!(right, dac(Some(0)) / 4.0 * 4.0 / 8.0);
+    }
+
+    #[test]
+    fn produces_samples_at_the_chosen_rate() {
+        let mut apu = Apu::new(32_768);
+        apu.tick(CPU_CLOCK_HZ / 128);
+
+        let samples = apu.take_samples();
+        assert_eq!(samples.len(), 2 * 256);
+        assert!(samples.iter().all(|sample| *sample == 0.0));
+
+        apu.tick(CPU_CLOCK_HZ / 128);
+        assert_eq!(apu.take_samples_i16().len(), 2 * 256);
+    }
+
+    #[test]
+    fn square_wave_reaches_the_output() {
+        let mut apu = powered_apu();
+        apu.write_register(NR51_ADDR, 0b0001_0001);
+        apu.write_register(NR11_ADDR, 0b1000_0000);
+        apu.write_register(NR12_ADDR, 0xF0);
+        apu.write_register(NR13_ADDR, 0xFF);
+        apu.write_register(NR14_ADDR, 0x87);
+
+        apu.tick(4 * 64);
+        let samples = apu.take_samples();
+
+        let max = samples.iter().cloned().fold(f32::MIN, f32::max);
+        let min = samples.iter().cloned().fold(f32::MAX, f32::min);
+        assert!(max > min);
+        assert!(max <= dac(Some(15)) / 4.0);
+        assert!(min >= dac(Some(0)) / 4.0);
+    }
+
+    #[test]
+    fn recording_keeps_the_whole_run_with_stems() {
+        let mut apu = powered_apu();
+        apu.write_register(NR51_ADDR, 0b1000_0011);
+        apu.write_register(NR12_ADDR, 0xF0);
+        apu.write_register(NR14_ADDR, 0x80);
+        apu.write_register(NR22_ADDR, 0xA0);
+        apu.write_register(NR24_ADDR, 0x85);
+        apu.write_register(NR42_ADDR, 0xF0);
+        apu.write_register(NR44_ADDR, 0x80);
+
+        let (mix, stems, stem_sinks) = recording_sinks(apu.sample_rate());
+
+        apu.start_recording(mix.clone(), Some(stem_sinks)).unwrap();
+        apu.tick(4096);
+        // Draining the live output leaves the recording alone.
+        let live = apu.take_samples_i16();
+        apu.tick(4096);
+
+        apu.stop_recording().unwrap();
+        assert!(!apu.recording());
+
+        let mix = mix.borrow();
+        let stems: Vec<Recording> = stems.iter().map(|stem| stem.borrow().clone()).collect();
+        assert_eq!(mix.len(), 2 * 1024);
+        assert_eq!(&mix.samples()[..live.len()], &live[..]);
+        assert_eq!(stems.len(), 4);
+
+        // Wave is muted, noise only panned left.
+        assert!(stems[2].samples().iter().all(|sample| *sample == 0));
+        assert!(stems[3]
+            .samples()
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .all(|sample| *sample == 0));
+        for (index, sample) in mix.samples().iter().enumerate() {
+            let sum: i32 = stems
+                .iter()
+                .map(|stem| i32::from(stem.samples()[index]))
+                .sum();
+            assert!((i32::from(*sample) - sum).abs() <= 4);
+        }
+    }
+}
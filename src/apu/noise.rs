@@ -0,0 +1,188 @@
+//! Noise channel 4, the output of a 15-bit LFSR which can be cut down to
+//! 7 bits for a more tonal sound.
+
+use super::channel::{Envelope, LengthCounter};
+
+/// T-cycles per LFSR clock for each NR43 divider code, before the shift.
+const DIVISORS: [u32; 8] = [8, 16, 32, 48, 64, 80, 96, 112];
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Noise {
+    enabled: bool,
+    dac_enabled: bool,
+    shift: u8,
+    /// The LFSR also feeds bit 6, repeating every 127 clocks.
+    narrow: bool,
+    divider: u8,
+    lfsr: u16,
+    /// T-cycles until the next LFSR clock.
+    timer: u32,
+    length: LengthCounter,
+    envelope: Envelope,
+}
+
+impl Default for Noise {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dac_enabled: false,
+            shift: 0,
+            narrow: false,
+            divider: 0,
+            lfsr: 0,
+            timer: 0,
+            length: LengthCounter::new(64),
+            envelope: Envelope::default(),
+        }
+    }
+}
+
+impl Noise {
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Takes a write to NR41 to NR44, `register` being the 1 to 4 index.
+    pub fn write(&mut self, register: u16, value: u8) {
+        match register {
+            1 => self.length.load(u16::from(value & 0b0011_1111)),
+            2 => {
+                self.envelope.write(value);
+                self.dac_enabled = Envelope::dac_enabled(value);
+                if !self.dac_enabled {
+                    self.enabled = false;
+                }
+            }
+            3 => {
+                self.shift = value >> 4;
+                self.narrow = value & 0b0000_1000 != 0;
+                self.divider = value & 0b0000_0111;
+            }
+            4 => {
+                self.length.set_enabled(value & 0b0100_0000 != 0);
+                if value & 0b1000_0000 != 0 {
+                    self.trigger();
+                }
+            }
+            _ => unreachable!("the noise channel has 4 registers"),
+        }
+    }
+
+    fn trigger(&mut self) {
+        self.enabled = self.dac_enabled;
+        self.lfsr = 0x7FFF;
+        self.timer = self.timer_period();
+        self.length.trigger();
+        self.envelope.trigger();
+    }
+
+    fn timer_period(&self) -> u32 {
+        DIVISORS[usize::from(self.divider)] << self.shift
+    }
+
+    /// Advances the LFSR by one T-cycle.
+    pub fn tick(&mut self) {
+        if self.timer > 1 {
+            self.timer -= 1;
+            return;
+        }
+
+        self.timer = self.timer_period();
+
+        let feedback = (self.lfsr ^ (self.lfsr >> 1)) & 1;
+        self.lfsr = (self.lfsr >> 1) | (feedback << 14);
+        if self.narrow {
+            self.lfsr = (self.lfsr & !(1 << 6)) | (feedback << 6);
+        }
+    }
+
+    pub fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    pub fn clock_envelope(&mut self) {
+        self.envelope.clock();
+    }
+
+    /// Digital output, 0 to 15, or None while the DAC is off.
+    pub fn output(&self) -> Option<u8> {
+        if !self.dac_enabled {
+            return None;
+        }
+        if !self.enabled {
+            return Some(0);
+        }
+
+        // High while bit 0 is clear.
+        let high = !self.lfsr as u8 & 1;
+        Some(high * self.envelope.volume())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn triggered(nr43: u8) -> Noise {
+        let mut noise = Noise::default();
+        noise.write(2, 0xF0);
+        noise.write(3, nr43);
+        noise.write(4, 0b1000_0000);
+
+        noise
+    }
+
+    /// LFSR clocks before the sequence repeats.
+    fn sequence_length(mut noise: Noise) -> usize {
+        let start = noise.lfsr;
+        let period = noise.timer_period();
+
+        for clocks in 1..=0x8000 {
+            for _ in 0..period {
+                noise.tick();
+            }
+            if noise.lfsr == start {
+                return clocks;
+            }
+        }
+
+        panic!("the LFSR never repeated");
+    }
+
+    #[test]
+    fn wide_lfsr_repeats_every_32767_clocks() {
+        assert_eq!(sequence_length(triggered(0x00)), 0x7FFF);
+    }
+
+    #[test]
+    fn narrow_lfsr_repeats_every_127_clocks() {
+        let mut noise = triggered(0b0000_1000);
+        // Get into the 7-bit loop first.
+        for _ in 0..16 * 8 {
+            noise.tick();
+        }
+
+        assert_eq!(sequence_length(noise), 127);
+    }
+
+    #[test]
+    fn first_output_after_trigger() {
+        let mut noise = triggered(0x00);
+        assert_eq!(noise.output(), Some(0));
+
+        // 0x7FFF shifts in a 0 on top, bit 0 stays set until it gets there.
+        for _ in 0..8 {
+            noise.tick();
+        }
+        assert_eq!(noise.output(), Some(0));
+    }
+
+    #[test]
+    fn divider_and_shift() {
+        let noise = triggered(0b0011_0101);
+
+        assert_eq!(noise.timer_period(), 80 << 3);
+    }
+}
@@ -0,0 +1,359 @@
+//! Turns the APU's per-cycle output into samples at the caller's rate.
+
+use super::resample::BlepSynth;
+
+/// T-cycles per second, the rate the APU produces output at.
+pub const CPU_CLOCK_HZ: u32 = 4_194_304;
+pub const DEFAULT_SAMPLE_RATE: u32 = 48_000;
+/// T-cycles per video frame, at single speed.
+pub const CYCLES_PER_FRAME: u32 = 70_224;
+
+/// How samples are made from the per-cycle output.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Quality {
+    /// Averages the cycles each sample covers. Cheap, but aliases.
+    Fast,
+    /// Band-limited steps over a 16-sample windowed sinc.
+    #[default]
+    Balanced,
+    /// Band-limited steps over a 32-sample windowed sinc, for recordings.
+    Best,
+}
+
+/// The capacitor in front of the headphone amplifier, removing the DC
+/// offset the DACs leave on the output.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum HighPass {
+    Off,
+    #[default]
+    Dmg,
+    Cgb,
+}
+
+impl HighPass {
+    /// Charge kept per T-cycle.
+    fn charge_per_cycle(self) -> Option<f64> {
+        match self {
+            HighPass::Off => None,
+            HighPass::Dmg => Some(0.999_958),
+            HighPass::Cgb => Some(0.998_943),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Resampler {
+    Average { sum: [f32; 2], cycles: u32 },
+    Blep(BlepSynth),
+}
+
+impl Resampler {
+    fn new(quality: Quality) -> Self {
+        match quality {
+            Quality::Fast => Resampler::Average {
+                sum: [0.0; 2],
+                cycles: 0,
+            },
+            Quality::Balanced => Resampler::Blep(BlepSynth::new(16)),
+            Quality::Best => Resampler::Blep(BlepSynth::new(32)),
+        }
+    }
+}
+
+/// Collects interleaved stereo samples from the per-cycle output.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SampleBuffer {
+    sample_rate: u32,
+    quality: Quality,
+    resampler: Resampler,
+    high_pass: HighPass,
+    /// Charge kept per sample, None while the filter is off.
+    charge: Option<f32>,
+    capacitor: [f32; 2],
+    /// Grows by `sample_rate` per cycle, a sample is due every
+    /// `CPU_CLOCK_HZ`, so the rate is exact over time.
+    phase: u32,
+    samples: Vec<f32>,
+    /// The last frame produced, repeated when a consumer asks for more
+    /// than there is.
+    last: [f32; 2],
+}
+
+impl SampleBuffer {
+    pub fn new(sample_rate: u32, quality: Quality) -> Self {
+        assert!(
+            sample_rate > 0 && sample_rate <= CPU_CLOCK_HZ,
+            "sample rate must be between 1 and {} Hz",
+            CPU_CLOCK_HZ
+        );
+
+        let mut buffer = Self {
+            sample_rate,
+            quality,
+            resampler: Resampler::new(quality),
+            high_pass: HighPass::Off,
+            charge: None,
+            capacitor: [0.0; 2],
+            phase: 0,
+            samples: Vec::new(),
+            last: [0.0; 2],
+        };
+        buffer.set_high_pass(HighPass::default());
+
+        buffer
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn quality(&self) -> Quality {
+        self.quality
+    }
+
+    pub fn high_pass(&self) -> HighPass {
+        self.high_pass
+    }
+
+    pub fn set_high_pass(&mut self, high_pass: HighPass) {
+        let cycles_per_sample = f64::from(CPU_CLOCK_HZ) / f64::from(self.sample_rate);
+
+        self.high_pass = high_pass;
+        self.charge = high_pass
+            .charge_per_cycle()
+            .map(|charge| charge.powf(cycles_per_sample) as f32);
+    }
+
+    /// Stereo frames produced per video frame, on average.
+    pub fn frames_per_video_frame(&self) -> f64 {
+        f64::from(self.sample_rate) * f64::from(CYCLES_PER_FRAME) / f64::from(CPU_CLOCK_HZ)
+    }
+
+    /// Adds the output of one T-cycle.
+    pub fn push(&mut self, left: f32, right: f32) {
+        let due = self.phase + self.sample_rate >= CPU_CLOCK_HZ;
+
+        let sample = match &mut self.resampler {
+            Resampler::Average { sum, cycles } => {
+                sum[0] += left;
+                sum[1] += right;
+                *cycles += 1;
+                if due {
+                    let count = *cycles as f32;
+                    let sample = [sum[0] / count, sum[1] / count];
+                    *sum = [0.0; 2];
+                    *cycles = 0;
+                    Some(sample)
+                } else {
+                    None
+                }
+            }
+            Resampler::Blep(synth) => {
+                let position = (CPU_CLOCK_HZ - self.phase) as f32 / CPU_CLOCK_HZ as f32;
+                synth.set_level([left, right], position);
+                if due {
+                    Some(synth.next_sample())
+                } else {
+                    None
+                }
+            }
+        };
+
+        self.phase += self.sample_rate;
+        if let Some(sample) = sample {
+            self.phase -= CPU_CLOCK_HZ;
+            self.emit(sample);
+        }
+    }
+
+    fn emit(&mut self, sample: [f32; 2]) {
+        let mut sample = sample;
+
+        if let Some(charge) = self.charge {
+            for (value, capacitor) in sample.iter_mut().zip(self.capacitor.iter_mut()) {
+                let input = *value;
+                *value = input - *capacitor;
+                *capacitor = input - *value * charge;
+            }
+        }
+
+        self.samples.extend_from_slice(&sample);
+        self.last = sample;
+    }
+
+    /// Stereo frames waiting to be taken.
+    pub fn len(&self) -> usize {
+        self.samples.len() / 2
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Takes the interleaved left/right samples, from -1.0 to 1.0.
+    pub fn take_f32(&mut self) -> Vec<f32> {
+        std::mem::take(&mut self.samples)
+    }
+
+    /// Takes the interleaved left/right samples as signed 16-bit PCM.
+    pub fn take_i16(&mut self) -> Vec<i16> {
+        self.take_f32().into_iter().map(to_i16).collect()
+    }
+
+    /// Takes exactly `frames` stereo frames, keeping any extra for the next
+    /// call and repeating the last frame if there are not enough. Taking
+    /// `frames_per_video_frame` rounded each frame keeps a fixed-size
+    /// consumer in step with the emulation.
+    pub fn take_exact(&mut self, frames: usize) -> Vec<f32> {
+        let available = self.samples.len().min(frames * 2);
+        let mut samples: Vec<f32> = self.samples.drain(..available).collect();
+
+        while samples.len() < frames * 2 {
+            samples.extend_from_slice(&self.last);
+        }
+
+        samples
+    }
+
+    /// `take_exact` as signed 16-bit PCM.
+    pub fn take_exact_i16(&mut self, frames: usize) -> Vec<i16> {
+        self.take_exact(frames).into_iter().map(to_i16).collect()
+    }
+}
+
+pub fn to_i16(sample: f32) -> i16 {
+    (sample.clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i16
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn unfiltered(sample_rate: u32, quality: Quality) -> SampleBuffer {
+        let mut buffer = SampleBuffer::new(sample_rate, quality);
+        buffer.set_high_pass(HighPass::Off);
+
+        buffer
+    }
+
+    #[test]
+    fn exact_rate_over_a_second() {
+        for quality in [Quality::Fast, Quality::Balanced, Quality::Best] {
+            let mut buffer = SampleBuffer::new(44_100, quality);
+
+            for _ in 0..CPU_CLOCK_HZ {
+                buffer.push(0.0, 0.0);
+            }
+
+            assert_eq!(buffer.len(), 44_100);
+        }
+    }
+
+    #[test]
+    fn samples_average_their_cycles() {
+        let mut buffer = unfiltered(CPU_CLOCK_HZ / 4, Quality::Fast);
+
+        buffer.push(1.0, -1.0);
+        buffer.push(1.0, -1.0);
+        buffer.push(0.0, 0.0);
+        buffer.push(0.0, 0.0);
+
+        assert_eq!(buffer.take_f32(), vec![0.5, -0.5]);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn i16_conversion_clamps() {
+        assert_eq!(to_i16(1.0), i16::MAX);
+        assert_eq!(to_i16(-2.0), -i16::MAX);
+        assert_eq!(to_i16(0.0), 0);
+    }
+
+    #[test]
+    fn band_limited_output_settles_on_the_input() {
+        let mut buffer = unfiltered(48_000, Quality::Balanced);
+
+        for _ in 0..CPU_CLOCK_HZ / 100 {
+            buffer.push(0.5, -0.5);
+        }
+
+        let samples = buffer.take_f32();
+        let tail = &samples[samples.len() - 2..];
+        assert!((tail[0] - 0.5).abs() < 1e-4);
+        assert!((tail[1] + 0.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn band_limiting_removes_what_averaging_aliases() {
+        // A 72 kHz square wave, nothing of which should be left at 48 kHz.
+        let alias = |quality| {
+            let mut buffer = unfiltered(48_000, quality);
+            for cycle in 0..CPU_CLOCK_HZ / 10 {
+                let level = if cycle % 58 < 29 { 1.0 } else { 0.0 };
+                buffer.push(level, level);
+            }
+            let samples = buffer.take_f32();
+            let settled = &samples[samples.len() / 2..];
+            let mean = settled.iter().sum::<f32>() / settled.len() as f32;
+            settled
+                .iter()
+                .map(|sample| (sample - mean).abs())
+                .fold(0.0, f32::max)
+        };
+
+        let averaged = alias(Quality::Fast);
+        assert!(averaged > 0.1);
+        assert!(alias(Quality::Balanced) < averaged / 10.0);
+        assert!(alias(Quality::Best) < averaged / 10.0);
+    }
+
+    #[test]
+    fn high_pass_removes_dc_offset() {
+        for high_pass in [HighPass::Dmg, HighPass::Cgb] {
+            let mut buffer = SampleBuffer::new(48_000, Quality::Fast);
+            buffer.set_high_pass(high_pass);
+
+            for _ in 0..CPU_CLOCK_HZ {
+                buffer.push(0.8, -0.8);
+            }
+
+            let samples = buffer.take_f32();
+            assert!(samples[0] > 0.7);
+            let tail = &samples[samples.len() - 2..];
+            assert!(tail[0].abs() < 0.01 && tail[1].abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn cgb_capacitor_charges_faster() {
+        let dmg = SampleBuffer::new(48_000, Quality::Fast);
+        let mut cgb = dmg.clone();
+        cgb.set_high_pass(HighPass::Cgb);
+
+        assert_eq!(dmg.high_pass(), HighPass::Dmg);
+        assert!(cgb.charge.unwrap() < dmg.charge.unwrap());
+    }
+
+    #[test]
+    fn take_exact_pads_and_keeps_the_rest() {
+        let mut buffer = unfiltered(CPU_CLOCK_HZ, Quality::Fast);
+        for _ in 0..5 {
+            buffer.push(0.25, -0.25);
+        }
+
+        assert_eq!(buffer.take_exact(3).len(), 6);
+        assert_eq!(buffer.len(), 2);
+        assert_eq!(
+            buffer.take_exact(4),
+            vec![0.25, -0.25, 0.25, -0.25, 0.25, -0.25, 0.25, -0.25]
+        );
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn frames_per_video_frame() {
+        let buffer = SampleBuffer::new(CPU_CLOCK_HZ / 64, Quality::Fast);
+
+        assert_eq!(buffer.frames_per_video_frame(), 70_224.0 / 64.0);
+    }
+}
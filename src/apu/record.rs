@@ -0,0 +1,477 @@
+//! Recording the APU output without an audio device, streamed as it is
+//! produced to a WAV file, raw PCM, or memory.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::rc::Rc;
+
+use super::output::SampleBuffer;
+
+/// Channels in every recording, always stereo.
+const CHANNELS: u16 = 2;
+const BITS_PER_SAMPLE: u16 = 16;
+const WAV_HEADER_SIZE: u32 = 44;
+/// Offsets of the sizes patched in once the length is known.
+const RIFF_SIZE_OFFSET: u64 = 4;
+const DATA_SIZE_OFFSET: u64 = 40;
+
+/// Stereo frames resampled before they are handed to a sink.
+const FLUSH_FRAMES: usize = 1024;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PcmFormat {
+    /// RIFF WAVE with a 44-byte header.
+    Wav,
+    /// Headerless little-endian samples.
+    Raw,
+}
+
+/// Size of the WAV data chunk holding `samples` 16-bit samples, or an error
+/// when the RIFF sizes can not represent it.
+fn wav_data_size(samples: u64) -> io::Result<u32> {
+    samples
+        .checked_mul(u64::from(BITS_PER_SAMPLE / 8))
+        .filter(|size| *size <= u64::from(u32::MAX - (WAV_HEADER_SIZE - 8)))
+        .map(|size| size as u32)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "recording is too long for a WAV file",
+            )
+        })
+}
+
+fn write_wav_header<W: Write>(writer: &mut W, sample_rate: u32, data_size: u32) -> io::Result<()> {
+    let block_align = CHANNELS * BITS_PER_SAMPLE / 8;
+
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&(WAV_HEADER_SIZE - 8 + data_size).to_le_bytes())?;
+    writer.write_all(b"WAVE")?;
+
+    writer.write_all(b"fmt ")?;
+    writer.write_all(&16u32.to_le_bytes())?;
+    // Integer PCM.
+    writer.write_all(&1u16.to_le_bytes())?;
+    writer.write_all(&CHANNELS.to_le_bytes())?;
+    writer.write_all(&sample_rate.to_le_bytes())?;
+    writer.write_all(&(sample_rate * u32::from(block_align)).to_le_bytes())?;
+    writer.write_all(&block_align.to_le_bytes())?;
+    writer.write_all(&BITS_PER_SAMPLE.to_le_bytes())?;
+
+    writer.write_all(b"data")?;
+    writer.write_all(&data_size.to_le_bytes())
+}
+
+fn write_samples<W: Write>(writer: &mut W, samples: &[i16]) -> io::Result<()> {
+    let bytes: Vec<u8> = samples
+        .iter()
+        .flat_map(|sample| sample.to_le_bytes())
+        .collect();
+
+    writer.write_all(&bytes)
+}
+
+/// Where a recording goes, fed interleaved left/right 16-bit samples as
+/// the APU produces them.
+pub trait SampleSink {
+    fn write_samples(&mut self, samples: &[i16]);
+
+    /// Called once the recording ends, e.g. to complete a header. Reports
+    /// any error met while writing.
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A sink shared with the caller, who keeps it to look at or finish what
+/// was recorded.
+pub type SharedSink = Rc<RefCell<dyn SampleSink>>;
+
+/// Streams a recording to `writer`. WAV sizes are left at 0 until `finish`
+/// seeks back to fill them in.
+#[derive(Debug)]
+pub struct PcmWriter<W: Write + Seek> {
+    writer: W,
+    format: PcmFormat,
+    samples: u64,
+    /// The first write error, writing stops there and `finish` reports it.
+    error: Option<io::Error>,
+}
+
+impl PcmWriter<BufWriter<File>> {
+    /// Creates the file at `path`, replacing any file there.
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+        format: PcmFormat,
+        sample_rate: u32,
+    ) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?), format, sample_rate)
+    }
+}
+
+impl<W: Write + Seek> PcmWriter<W> {
+    pub fn new(mut writer: W, format: PcmFormat, sample_rate: u32) -> io::Result<Self> {
+        if format == PcmFormat::Wav {
+            write_wav_header(&mut writer, sample_rate, 0)?;
+        }
+
+        Ok(Self {
+            writer,
+            format,
+            samples: 0,
+            error: None,
+        })
+    }
+
+    /// Samples written so far, left and right counted apart.
+    pub fn samples(&self) -> u64 {
+        self.samples
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn append(&mut self, samples: &[i16]) -> io::Result<()> {
+        let total = self.samples + samples.len() as u64;
+        if self.format == PcmFormat::Wav {
+            wav_data_size(total)?;
+        }
+
+        write_samples(&mut self.writer, samples)?;
+        self.samples = total;
+
+        Ok(())
+    }
+
+    fn patch_sizes(&mut self) -> io::Result<()> {
+        let data_size = wav_data_size(self.samples)?;
+
+        self.writer.seek(SeekFrom::Start(RIFF_SIZE_OFFSET))?;
+        self.writer
+            .write_all(&(WAV_HEADER_SIZE - 8 + data_size).to_le_bytes())?;
+        self.writer.seek(SeekFrom::Start(DATA_SIZE_OFFSET))?;
+        self.writer.write_all(&data_size.to_le_bytes())?;
+        self.writer.seek(SeekFrom::End(0))?;
+
+        Ok(())
+    }
+}
+
+impl<W: Write + Seek> SampleSink for PcmWriter<W> {
+    fn write_samples(&mut self, samples: &[i16]) {
+        if self.error.is_some() {
+            return;
+        }
+
+        if let Err(err) = self.append(samples) {
+            self.error = Some(err);
+        }
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        if self.format == PcmFormat::Wav {
+            self.patch_sizes()?;
+        }
+
+        self.writer.flush()
+    }
+}
+
+/// Interleaved left/right 16-bit samples kept in memory, for short captures
+/// compared in tests.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Recording {
+    sample_rate: u32,
+    samples: Vec<i16>,
+}
+
+impl Recording {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            samples: Vec::new(),
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn samples(&self) -> &[i16] {
+        &self.samples
+    }
+
+    /// Stereo frames recorded.
+    pub fn len(&self) -> usize {
+        self.samples.len() / 2
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W, format: PcmFormat) -> io::Result<()> {
+        if format == PcmFormat::Wav {
+            let data_size = wav_data_size(self.samples.len() as u64)?;
+            write_wav_header(writer, self.sample_rate, data_size)?;
+        }
+
+        write_samples(writer, &self.samples)
+    }
+
+    pub fn write_to_path<P: AsRef<Path>>(&self, path: P, format: PcmFormat) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(&mut writer, format)?;
+
+        writer.flush()
+    }
+}
+
+impl SampleSink for Recording {
+    fn write_samples(&mut self, samples: &[i16]) {
+        self.samples.extend_from_slice(samples);
+    }
+}
+
+/// One output resampled on its own and handed to its sink in batches.
+#[derive(Clone)]
+struct Track {
+    buffer: SampleBuffer,
+    sink: SharedSink,
+}
+
+impl Track {
+    fn new(output: &SampleBuffer, sink: SharedSink) -> Self {
+        Self {
+            buffer: resampler_like(output),
+            sink,
+        }
+    }
+
+    fn push(&mut self, [left, right]: [f32; 2]) {
+        self.buffer.push(left, right);
+        if self.buffer.len() >= FLUSH_FRAMES {
+            self.flush();
+        }
+    }
+
+    fn flush(&mut self) {
+        if !self.buffer.is_empty() {
+            let samples = self.buffer.take_i16();
+            self.sink.borrow_mut().write_samples(&samples);
+        }
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.flush();
+        self.sink.borrow_mut().finish()
+    }
+}
+
+impl fmt::Debug for Track {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Track")
+            .field("buffer", &self.buffer)
+            .field("sink", &"..")
+            .finish()
+    }
+}
+
+impl PartialEq for Track {
+    fn eq(&self, rhs: &Self) -> bool {
+        self.buffer == rhs.buffer && Rc::ptr_eq(&self.sink, &rhs.sink)
+    }
+}
+
+/// An empty buffer resampling and filtering the way `output` does.
+fn resampler_like(output: &SampleBuffer) -> SampleBuffer {
+    let mut buffer = SampleBuffer::new(output.sample_rate(), output.quality());
+    buffer.set_high_pass(output.high_pass());
+
+    buffer
+}
+
+/// Feeds the mix and, if asked for, each channel on its own, panned and
+/// scaled like in the mix so the stems add up to it.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Recorder {
+    mix: Track,
+    stems: Vec<Track>,
+}
+
+impl Recorder {
+    /// A recorder resampling like `output` does. `stems` take square 1,
+    /// square 2, wave and noise.
+    pub fn new(output: &SampleBuffer, mix: SharedSink, stems: Option<[SharedSink; 4]>) -> Self {
+        let stems = stems
+            .map(|stems| {
+                stems
+                    .iter()
+                    .map(|sink| Track::new(output, sink.clone()))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self {
+            mix: Track::new(output, mix),
+            stems,
+        }
+    }
+
+    /// Adds the output of one T-cycle.
+    pub fn push(&mut self, mix: [f32; 2], channels: &[[f32; 2]; 4]) {
+        self.mix.push(mix);
+        for (stem, channel) in self.stems.iter_mut().zip(channels.iter()) {
+            stem.push(*channel);
+        }
+    }
+
+    /// Hands the samples still buffered to the sinks and finishes them all,
+    /// returning the first error.
+    pub fn finish(mut self) -> io::Result<()> {
+        let mut result = self.mix.finish();
+        for stem in &mut self.stems {
+            let finished = stem.finish();
+            result = result.and(finished);
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use std::env;
+    use std::fs;
+    use std::io::Cursor;
+    use std::process;
+
+    use super::*;
+
+    use crate::apu::Quality;
+
+    pub(crate) type SharedRecording = Rc<RefCell<Recording>>;
+
+    /// A mix recording and four stem recordings, along with the stems as
+    /// the sinks a `Recorder` takes.
+    pub(crate) fn recording_sinks(
+        sample_rate: u32,
+    ) -> (SharedRecording, Vec<SharedRecording>, [SharedSink; 4]) {
+        let mix = Rc::new(RefCell::new(Recording::new(sample_rate)));
+        let stems: Vec<SharedRecording> = (0..4)
+            .map(|_| Rc::new(RefCell::new(Recording::new(sample_rate))))
+            .collect();
+        let stem_sinks: [SharedSink; 4] = [
+            stems[0].clone(),
+            stems[1].clone(),
+            stems[2].clone(),
+            stems[3].clone(),
+        ];
+
+        (mix, stems, stem_sinks)
+    }
+
+    fn recording() -> Recording {
+        Recording {
+            sample_rate: 48_000,
+            samples: vec![1, -1, 0x1234, -0x1234],
+        }
+    }
+
+    #[test]
+    fn wav_header() {
+        let mut wav = Vec::new();
+        recording().write(&mut wav, PcmFormat::Wav).unwrap();
+
+        assert_eq!(wav.len(), 44 + 8);
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(&wav[4..8], &44u32.to_le_bytes());
+        assert_eq!(&wav[8..16], b"WAVEfmt ");
+        assert_eq!(&wav[22..24], &2u16.to_le_bytes());
+        assert_eq!(&wav[24..28], &48_000u32.to_le_bytes());
+        assert_eq!(&wav[28..32], &192_000u32.to_le_bytes());
+        assert_eq!(&wav[32..36], &[4, 0, 16, 0]);
+        assert_eq!(&wav[36..40], b"data");
+        assert_eq!(&wav[40..44], &8u32.to_le_bytes());
+        assert_eq!(&wav[44..48], &[1, 0, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn raw_is_just_the_samples() {
+        let mut raw = Vec::new();
+        recording().write(&mut raw, PcmFormat::Raw).unwrap();
+
+        assert_eq!(raw, vec![1, 0, 0xFF, 0xFF, 0x34, 0x12, 0xCC, 0xED]);
+    }
+
+    #[test]
+    fn wav_sizes_are_checked() {
+        assert_eq!(wav_data_size(4).unwrap(), 8);
+        assert!(wav_data_size(u64::from(u32::MAX) / 2).is_err());
+        assert!(wav_data_size(u64::MAX).is_err());
+    }
+
+    #[test]
+    fn streamed_wav_matches_in_memory() {
+        let mut writer = PcmWriter::new(Cursor::new(Vec::new()), PcmFormat::Wav, 48_000).unwrap();
+
+        writer.write_samples(&[1, -1]);
+        writer.write_samples(&[0x1234, -0x1234]);
+        writer.finish().unwrap();
+        assert_eq!(writer.samples(), 4);
+
+        let mut expected = Vec::new();
+        recording().write(&mut expected, PcmFormat::Wav).unwrap();
+        assert_eq!(writer.into_inner().into_inner(), expected);
+    }
+
+    #[test]
+    fn streamed_raw_has_no_header() {
+        let mut writer = PcmWriter::new(Cursor::new(Vec::new()), PcmFormat::Raw, 48_000).unwrap();
+
+        writer.write_samples(&[1, -1]);
+        writer.finish().unwrap();
+
+        assert_eq!(writer.into_inner().into_inner(), vec![1, 0, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn streams_to_a_file() {
+        let path = env::temp_dir().join(format!("gemuboi-{}-record.wav", process::id()));
+
+        let mut writer = PcmWriter::create(&path, PcmFormat::Wav, 48_000).unwrap();
+        writer.write_samples(&[1, -1, 0x1234, -0x1234]);
+        writer.finish().unwrap();
+        drop(writer);
+
+        let wav = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(wav.len(), 52);
+        assert_eq!(&wav[40..44], &8u32.to_le_bytes());
+    }
+
+    #[test]
+    fn recorder_flushes_on_finish() {
+        let output = SampleBuffer::new(48_000, Quality::Fast);
+        let (mix, stems, stem_sinks) = recording_sinks(48_000);
+
+        let mut recorder = Recorder::new(&output, mix.clone(), Some(stem_sinks));
+        for _ in 0..10 * 88 {
+            recorder.push([0.5; 2], &[[0.125; 2]; 4]);
+        }
+        // Still buffered.
+        assert!(mix.borrow().is_empty());
+
+        recorder.finish().unwrap();
+        assert_eq!(mix.borrow().len(), 10);
+        assert!(stems.iter().all(|stem| stem.borrow().len() == 10));
+    }
+}
@@ -0,0 +1,171 @@
+//! Band-limited step synthesis. The APU output only ever jumps between
+//! levels, so each jump is added to the output as a windowed-sinc step
+//! instead of sampling the signal, which keeps the harmonics above the
+//! output Nyquist frequency from aliasing back.
+
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+
+/// Sub-sample positions a step can be placed at.
+const PHASES: usize = 64;
+/// Kernel cutoff in cycles per output sample, a bit under Nyquist.
+const CUTOFF: f64 = 0.45;
+
+/// Impulse responses of the kernel for every phase, each one normalised so
+/// a step settles on exactly its height.
+#[derive(Debug, PartialEq, Clone)]
+struct Kernel {
+    taps: usize,
+    table: Vec<f32>,
+}
+
+impl Kernel {
+    fn new(taps: usize) -> Self {
+        let half = taps as f64 / 2.0;
+        let mut table = Vec::with_capacity(PHASES * taps);
+
+        for phase in 0..PHASES {
+            let offset = phase as f64 / PHASES as f64;
+            let impulse: Vec<f64> = (0..taps)
+                .map(|tap| {
+                    let x = tap as f64 + offset - half;
+                    sinc(2.0 * CUTOFF * x) * blackman(x / half)
+                })
+                .collect();
+            let sum: f64 = impulse.iter().sum();
+
+            table.extend(impulse.iter().map(|value| (value / sum) as f32));
+        }
+
+        Self { taps, table }
+    }
+
+    fn impulse(&self, phase: usize) -> &[f32] {
+        &self.table[phase * self.taps..(phase + 1) * self.taps]
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x == 0.0 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+/// Blackman window over -1.0 to 1.0.
+fn blackman(x: f64) -> f64 {
+    if x.abs() >= 1.0 {
+        return 0.0;
+    }
+
+    0.42 + 0.5 * (PI * x).cos() + 0.08 * (2.0 * PI * x).cos()
+}
+
+/// Turns level changes of a stereo signal into band-limited samples,
+/// delayed by half the kernel.
+#[derive(Debug, PartialEq, Clone)]
+pub struct BlepSynth {
+    kernel: Kernel,
+    /// Level of the input on each side.
+    level: [f32; 2],
+    /// Differences still to be added to the coming samples, front first.
+    deltas: [VecDeque<f32>; 2],
+    /// Running sum of the deltas, the band-limited signal.
+    output: [f32; 2],
+}
+
+impl BlepSynth {
+    pub fn new(taps: usize) -> Self {
+        let deltas = VecDeque::from(vec![0.0; taps + 1]);
+
+        Self {
+            kernel: Kernel::new(taps),
+            level: [0.0; 2],
+            deltas: [deltas.clone(), deltas],
+            output: [0.0; 2],
+        }
+    }
+
+    /// Moves the input to `level`, `position` output samples, 0.0 to 1.0,
+    /// before the next one is due.
+    pub fn set_level(&mut self, level: [f32; 2], position: f32) {
+        let phase = ((position * PHASES as f32) as usize).min(PHASES - 1);
+
+        let impulse = self.kernel.impulse(phase);
+        let sides = self.level.iter_mut().zip(self.deltas.iter_mut());
+
+        for ((current, deltas), target) in sides.zip(level.iter()) {
+            let delta = target - *current;
+            if delta == 0.0 {
+                continue;
+            }
+            *current = *target;
+
+            for (slot, weight) in deltas.iter_mut().zip(impulse) {
+                *slot += delta * weight;
+            }
+        }
+    }
+
+    /// Produces the next output sample.
+    pub fn next_sample(&mut self) -> [f32; 2] {
+        for side in 0..2 {
+            let deltas = &mut self.deltas[side];
+            self.output[side] += deltas.pop_front().unwrap_or(0.0);
+            deltas.push_back(0.0);
+        }
+
+        self.output
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn kernel_phases_sum_to_one() {
+        let kernel = Kernel::new(16);
+
+        for phase in 0..PHASES {
+            let sum: f32 = kernel.impulse(phase).iter().sum();
+            assert!((sum - 1.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn step_settles_on_its_height() {
+        let mut synth = BlepSynth::new(16);
+        synth.set_level([0.5, -0.25], 0.3);
+
+        let samples: Vec<[f32; 2]> = (0..32).map(|_| synth.next_sample()).collect();
+
+        assert!(samples[0][0].abs() < 0.01);
+        let [left, right] = samples[31];
+        assert!((left - 0.5).abs() < 1e-5);
+        assert!((right + 0.25).abs() < 1e-5);
+        // Half way up between the samples around the kernel centre.
+        assert!(samples[7][0] < 0.25 && samples[8][0] > 0.25);
+    }
+
+    #[test]
+    fn alternating_steps_above_nyquist_cancel_out() {
+        let mut synth = BlepSynth::new(32);
+        let mut peak: f32 = 0.0;
+
+        // Six transitions per output sample, far beyond what it can carry.
+        for index in 0..3000 {
+            let level = if index % 2 == 0 { 1.0 } else { 0.0 };
+            synth.set_level([level; 2], (6 - index % 6) as f32 / 6.0);
+            if index % 6 == 5 {
+                let [left, _] = synth.next_sample();
+                if index > 600 {
+                    peak = peak.max((left - 0.5).abs());
+                }
+            }
+        }
+
+        assert!(peak < 0.05, "aliased by {}", peak);
+    }
+}
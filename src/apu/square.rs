@@ -0,0 +1,292 @@
+//! Square channels 1 and 2, the first one with a frequency sweep.
+
+use super::channel::{Envelope, LengthCounter};
+
+/// Waveforms per NRx1 duty, one bit per step.
+const DUTY_PATTERNS: [u8; 4] = [0b0000_0001, 0b1000_0001, 0b1000_0111, 0b0111_1110];
+const MAX_PERIOD: u16 = 0x7FF;
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Sweep {
+    /// Sweep clocks between updates, 0 stops updating.
+    pace: u8,
+    decrease: bool,
+    step: u8,
+    enabled: bool,
+    timer: u8,
+    shadow: u16,
+    /// A decreasing calculation ran since the last trigger, switching to
+    /// increasing then turns the channel off.
+    decreased: bool,
+}
+
+impl Sweep {
+    fn reload_timer(&mut self) {
+        self.timer = if self.pace == 0 { 8 } else { self.pace };
+    }
+
+    /// The next period, or None when it overflows and turns the channel off.
+    fn next_period(&mut self) -> Option<u16> {
+        let delta = self.shadow >> self.step;
+        if self.decrease {
+            self.decreased = true;
+            Some(self.shadow - delta)
+        } else {
+            Some(self.shadow + delta).filter(|period| *period <= MAX_PERIOD)
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Square {
+    enabled: bool,
+    dac_enabled: bool,
+    duty: u8,
+    duty_step: u8,
+    period: u16,
+    /// T-cycles until the next duty step.
+    timer: u32,
+    length: LengthCounter,
+    envelope: Envelope,
+    sweep: Option<Sweep>,
+}
+
+impl Square {
+    pub fn new(with_sweep: bool) -> Self {
+        Self {
+            enabled: false,
+            dac_enabled: false,
+            duty: 0,
+            duty_step: 0,
+            period: 0,
+            timer: 0,
+            length: LengthCounter::new(64),
+            envelope: Envelope::default(),
+            sweep: if with_sweep {
+                Some(Sweep::default())
+            } else {
+                None
+            },
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Takes a write to NRx0 to NRx4, `register` being the x0 to x4 index.
+    pub fn write(&mut self, register: u16, value: u8) {
+        match register {
+            0 => {
+                if let Some(sweep) = &mut self.sweep {
+                    sweep.pace = (value >> 4) & 0b111;
+                    sweep.decrease = value & 0b0000_1000 != 0;
+                    sweep.step = value & 0b0000_0111;
+                    if !sweep.decrease && sweep.decreased {
+                        self.enabled = false;
+                    }
+                }
+            }
+            1 => {
+                self.duty = value >> 6;
+                self.length.load(u16::from(value & 0b0011_1111));
+            }
+            2 => {
+                self.envelope.write(value);
+                self.dac_enabled = Envelope::dac_enabled(value);
+                if !self.dac_enabled {
+                    self.enabled = false;
+                }
+            }
+            3 => self.period = (self.period & 0x700) | u16::from(value),
+            4 => {
+                self.period = (self.period & 0xFF) | (u16::from(value & 0b111) << 8);
+                self.length.set_enabled(value & 0b0100_0000 != 0);
+                if value & 0b1000_0000 != 0 {
+                    self.trigger();
+                }
+            }
+            _ => unreachable!("square channels have 5 registers"),
+        }
+    }
+
+    fn trigger(&mut self) {
+        self.enabled = self.dac_enabled;
+        self.timer = self.timer_period();
+        self.length.trigger();
+        self.envelope.trigger();
+
+        if let Some(sweep) = &mut self.sweep {
+            sweep.shadow = self.period;
+            sweep.reload_timer();
+            sweep.enabled = sweep.pace != 0 || sweep.step != 0;
+            sweep.decreased = false;
+            if sweep.step != 0 && sweep.next_period().is_none() {
+                self.enabled = false;
+            }
+        }
+    }
+
+    fn timer_period(&self) -> u32 {
+        (2048 - u32::from(self.period)) * 4
+    }
+
+    /// Advances the duty by one T-cycle.
+    pub fn tick(&mut self) {
+        if self.timer > 1 {
+            self.timer -= 1;
+            return;
+        }
+
+        self.timer = self.timer_period();
+        self.duty_step = (self.duty_step + 1) % 8;
+    }
+
+    pub fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    pub fn clock_envelope(&mut self) {
+        self.envelope.clock();
+    }
+
+    pub fn clock_sweep(&mut self) {
+        let sweep = match &mut self.sweep {
+            Some(sweep) => sweep,
+            None => return,
+        };
+
+        sweep.timer = sweep.timer.saturating_sub(1);
+        if sweep.timer > 0 {
+            return;
+        }
+        sweep.reload_timer();
+
+        if !sweep.enabled || sweep.pace == 0 {
+            return;
+        }
+
+        match sweep.next_period() {
+            Some(period) if sweep.step != 0 => {
+                sweep.shadow = period;
+                self.period = period;
+                // The new period is checked again straight away.
+                if sweep.next_period().is_none() {
+                    self.enabled = false;
+                }
+            }
+            Some(_) => {}
+            None => self.enabled = false,
+        }
+    }
+
+    /// Digital output, 0 to 15, or None while the DAC is off.
+    pub fn output(&self) -> Option<u8> {
+        if !self.dac_enabled {
+            return None;
+        }
+        if !self.enabled {
+            return Some(0);
+        }
+
+        let high = DUTY_PATTERNS[usize::from(self.duty)] >> (7 - self.duty_step) & 1;
+        Some(high * self.envelope.volume())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn triggered(with_sweep: bool, period: u16) -> Square {
+        let mut square = Square::new(with_sweep);
+        square.write(1, 0b1000_0000);
+        square.write(2, 0xF0);
+        square.write(3, period as u8);
+        square.write(4, 0b1000_0000 | (period >> 8) as u8);
+
+        square
+    }
+
+    #[test]
+    fn duty_cycle_waveform() {
+        let mut square = triggered(false, 0x7FF);
+        let mut waveform = Vec::new();
+
+        for _ in 0..8 {
+            waveform.push(square.output().unwrap());
+            for _ in 0..4 {
+                square.tick();
+            }
+        }
+
+        // 50% duty.
+        assert_eq!(waveform, vec![15, 0, 0, 0, 0, 15, 15, 15]);
+    }
+
+    #[test]
+    fn dac_off_disables_channel() {
+        let mut square = triggered(false, 0x100);
+        assert!(square.enabled());
+
+        square.write(2, 0x00);
+
+        assert!(!square.enabled());
+        assert_eq!(square.output(), None);
+    }
+
+    #[test]
+    fn length_turns_channel_off() {
+        let mut square = triggered(false, 0x100);
+        square.write(1, 63);
+        square.write(4, 0b0100_0000);
+
+        square.clock_length();
+
+        assert!(!square.enabled());
+        assert_eq!(square.output(), Some(0));
+    }
+
+    #[test]
+    fn sweep_raises_period() {
+        let mut square = Square::new(true);
+        square.write(0, 0b0001_0001);
+        square.write(2, 0xF0);
+        square.write(3, 0x00);
+        square.write(4, 0b1000_0001);
+
+        square.clock_sweep();
+
+        assert_eq!(square.period, 0x180);
+        assert!(square.enabled());
+    }
+
+    #[test]
+    fn sweep_overflow_disables() {
+        let mut square = Square::new(true);
+        square.write(0, 0b0001_0001);
+        square.write(2, 0xF0);
+        square.write(3, 0x00);
+        square.write(4, 0b1000_0101);
+
+        assert!(square.enabled());
+        square.clock_sweep();
+        assert!(!square.enabled());
+    }
+
+    #[test]
+    fn sweep_direction_change_after_decrease_disables() {
+        let mut square = Square::new(true);
+        square.write(0, 0b0001_1001);
+        square.write(2, 0xF0);
+        square.write(4, 0b1000_0100);
+        square.clock_sweep();
+        assert!(square.enabled());
+
+        square.write(0, 0b0001_0001);
+
+        assert!(!square.enabled());
+    }
+}
@@ -0,0 +1,179 @@
+//! Wave channel 3, playing 32 4-bit samples from wave RAM.
+
+use super::channel::LengthCounter;
+
+pub const WAVE_RAM_SIZE: usize = 16;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Wave {
+    enabled: bool,
+    dac_enabled: bool,
+    /// NR32 output level: mute, 100%, 50% or 25%.
+    level: u8,
+    period: u16,
+    /// T-cycles until the next sample.
+    timer: u32,
+    /// Sample index into wave RAM, 0 to 31.
+    position: u8,
+    /// The sample currently played.
+    sample: u8,
+    length: LengthCounter,
+    ram: [u8; WAVE_RAM_SIZE],
+}
+
+impl Default for Wave {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dac_enabled: false,
+            level: 0,
+            period: 0,
+            timer: 0,
+            position: 0,
+            sample: 0,
+            length: LengthCounter::new(256),
+            ram: [0; WAVE_RAM_SIZE],
+        }
+    }
+}
+
+impl Wave {
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn read_ram(&self, offset: usize) -> u8 {
+        self.ram[offset]
+    }
+
+    pub fn write_ram(&mut self, offset: usize, value: u8) {
+        self.ram[offset] = value;
+    }
+
+    /// Takes a write to NR30 to NR34, `register` being the 0 to 4 index.
+    pub fn write(&mut self, register: u16, value: u8) {
+        match register {
+            0 => {
+                self.dac_enabled = value & 0b1000_0000 != 0;
+                if !self.dac_enabled {
+                    self.enabled = false;
+                }
+            }
+            1 => self.length.load(u16::from(value)),
+            2 => self.level = (value >> 5) & 0b11,
+            3 => self.period = (self.period & 0x700) | u16::from(value),
+            4 => {
+                self.period = (self.period & 0xFF) | (u16::from(value & 0b111) << 8);
+                self.length.set_enabled(value & 0b0100_0000 != 0);
+                if value & 0b1000_0000 != 0 {
+                    self.trigger();
+                }
+            }
+            _ => unreachable!("the wave channel has 5 registers"),
+        }
+    }
+
+    fn trigger(&mut self) {
+        self.enabled = self.dac_enabled;
+        self.timer = self.timer_period();
+        self.position = 0;
+        self.length.trigger();
+    }
+
+    fn timer_period(&self) -> u32 {
+        (2048 - u32::from(self.period)) * 2
+    }
+
+    /// Advances the wave position by one T-cycle.
+    pub fn tick(&mut self) {
+        if !self.enabled {
+            return;
+        }
+        if self.timer > 1 {
+            self.timer -= 1;
+            return;
+        }
+
+        self.timer = self.timer_period();
+        self.position = (self.position + 1) % 32;
+
+        let byte = self.ram[usize::from(self.position / 2)];
+        self.sample = if self.position.is_multiple_of(2) {
+            byte >> 4
+        } else {
+            byte & 0x0F
+        };
+    }
+
+    pub fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    /// Digital output, 0 to 15, or None while the DAC is off.
+    pub fn output(&self) -> Option<u8> {
+        if !self.dac_enabled {
+            return None;
+        }
+        if !self.enabled || self.level == 0 {
+            return Some(0);
+        }
+
+        Some(self.sample >> (self.level - 1))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn playing(level: u8) -> Wave {
+        let mut wave = Wave::default();
+        for offset in 0..WAVE_RAM_SIZE {
+            wave.write_ram(offset, 0x0F + 0x10 * offset as u8);
+        }
+        wave.write(0, 0b1000_0000);
+        wave.write(2, level << 5);
+        wave.write(3, 0xFF);
+        wave.write(4, 0b1000_0111);
+
+        wave
+    }
+
+    #[test]
+    fn plays_nibbles_in_order() {
+        let mut wave = playing(1);
+        let mut samples = Vec::new();
+
+        for _ in 0..4 {
+            wave.tick();
+            wave.tick();
+            samples.push(wave.output().unwrap());
+        }
+
+        assert_eq!(samples, vec![0x0F, 0x1, 0x0F, 0x2]);
+    }
+
+    #[test]
+    fn output_level_shifts() {
+        let mut wave = playing(3);
+        wave.tick();
+        wave.tick();
+
+        assert_eq!(wave.output(), Some(0x03));
+
+        wave.write(2, 0);
+        assert_eq!(wave.output(), Some(0));
+    }
+
+    #[test]
+    fn dac_off_disables_channel() {
+        let mut wave = playing(1);
+
+        wave.write(0, 0);
+
+        assert!(!wave.enabled());
+        assert_eq!(wave.output(), None);
+    }
+}
@@ -0,0 +1,564 @@
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+pub const HEADER_START: usize = 0x0100;
+pub const HEADER_END: usize = 0x0150;
+
+pub const LOGO_START: usize = 0x0104;
+pub const LOGO_END: usize = 0x0134;
+
+/// The logo the boot ROM scrolls in and compares against the header's copy.
+pub const NINTENDO_LOGO: [u8; LOGO_END - LOGO_START] = [
+    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+];
+
+const TITLE_START: usize = 0x0134;
+const TITLE_END: usize = 0x0144;
+const NEW_LICENSEE_ADDR: usize = 0x0144;
+const CGB_FLAG_ADDR: usize = 0x0143;
+const SGB_FLAG_ADDR: usize = 0x0146;
+const CARTRIDGE_TYPE_ADDR: usize = 0x0147;
+const ROM_SIZE_ADDR: usize = 0x0148;
+const RAM_SIZE_ADDR: usize = 0x0149;
+const OLD_LICENSEE_ADDR: usize = 0x014B;
+const VERSION_ADDR: usize = 0x014C;
+const HEADER_CHECKSUM_ADDR: usize = 0x014D;
+const GLOBAL_CHECKSUM_ADDR: usize = 0x014E;
+
+/// Old licensee code telling the real one is in the new licensee field.
+const USE_NEW_LICENSEE: u8 = 0x33;
+
+pub const ROM_BANK_SIZE: usize = 0x4000;
+pub const RAM_BANK_SIZE: usize = 0x2000;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// The image ends before the header does.
+    TooSmall {
+        len: usize,
+    },
+    UnknownCartridgeType(u8),
+    UnknownRomSize(u8),
+    UnknownRamSize(u8),
+    /// The image is not as large as the ROM size code says.
+    RomSizeMismatch {
+        expected: usize,
+        actual: usize,
+    },
+    HeaderChecksumMismatch {
+        expected: u8,
+        actual: u8,
+    },
+    GlobalChecksumMismatch {
+        expected: u16,
+        actual: u16,
+    },
+    /// The header asks for a bank controller that is not emulated.
+    UnsupportedMbc(Mbc),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "failed to read cartridge: {}", err),
+            Error::TooSmall { len } => {
+                write!(f, "cartridge of {} bytes is too small for a header", len)
+            }
+            Error::UnknownCartridgeType(code) => {
+                write!(f, "unknown cartridge type {:#04X}", code)
+            }
+            Error::UnknownRomSize(code) => write!(f, "unknown ROM size code {:#04X}", code),
+            Error::UnknownRamSize(code) => write!(f, "unknown RAM size code {:#04X}", code),
+            Error::RomSizeMismatch { expected, actual } => write!(
+                f,
+                "header declares {} bytes of ROM but got {}",
+                expected, actual
+            ),
+            Error::HeaderChecksumMismatch { expected, actual } => write!(
+                f,
+                "header checksum is {:#04X} but computed {:#04X}",
+                expected, actual
+            ),
+            Error::GlobalChecksumMismatch { expected, actual } => write!(
+                f,
+                "global checksum is {:#06X} but computed {:#06X}",
+                expected, actual
+            ),
+            Error::UnsupportedMbc(mbc) => write!(f, "unsupported bank controller {:?}", mbc),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// CGB support declared at 0x0143.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CgbFlag {
+    /// Made for the DMG, the byte is part of the title.
+    Dmg,
+    /// 0x80, runs on both.
+    Supported,
+    /// 0xC0, refuses to run on a DMG.
+    Only,
+}
+
+/// The bank controller wired on the board.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Mbc {
+    None,
+    Mbc1,
+    Mbc2,
+    Mmm01,
+    Mbc3,
+    Mbc5,
+    Mbc6,
+    Mbc7,
+    PocketCamera,
+    Tama5,
+    HuC3,
+    HuC1,
+}
+
+/// The decoded cartridge type byte at 0x0147.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct CartridgeType {
+    pub code: u8,
+    pub mbc: Mbc,
+    pub ram: bool,
+    pub battery: bool,
+    pub timer: bool,
+    pub rumble: bool,
+}
+
+impl CartridgeType {
+    pub fn from_code(code: u8) -> Result<CartridgeType> {
+        let (mbc, ram, battery, timer, rumble) = match code {
+            0x00 => (Mbc::None, false, false, false, false),
+            0x01 => (Mbc::Mbc1, false, false, false, false),
+            0x02 => (Mbc::Mbc1, true, false, false, false),
+            0x03 => (Mbc::Mbc1, true, true, false, false),
+            0x05 => (Mbc::Mbc2, false, false, false, false),
+            0x06 => (Mbc::Mbc2, false, true, false, false),
+            0x08 => (Mbc::None, true, false, false, false),
+            0x09 => (Mbc::None, true, true, false, false),
+            0x0B => (Mbc::Mmm01, false, false, false, false),
+            0x0C => (Mbc::Mmm01, true, false, false, false),
+            0x0D => (Mbc::Mmm01, true, true, false, false),
+            0x0F => (Mbc::Mbc3, false, true, true, false),
+            0x10 => (Mbc::Mbc3, true, true, true, false),
+            0x11 => (Mbc::Mbc3, false, false, false, false),
+            0x12 => (Mbc::Mbc3, true, false, false, false),
+            0x13 => (Mbc::Mbc3, true, true, false, false),
+            0x19 => (Mbc::Mbc5, false, false, false, false),
+            0x1A => (Mbc::Mbc5, true, false, false, false),
+            0x1B => (Mbc::Mbc5, true, true, false, false),
+            0x1C => (Mbc::Mbc5, false, false, false, true),
+            0x1D => (Mbc::Mbc5, true, false, false, true),
+            0x1E => (Mbc::Mbc5, true, true, false, true),
+            0x20 => (Mbc::Mbc6, false, false, false, false),
+            0x22 => (Mbc::Mbc7, true, true, false, true),
+            0xFC => (Mbc::PocketCamera, false, false, false, false),
+            0xFD => (Mbc::Tama5, false, false, false, false),
+            0xFE => (Mbc::HuC3, false, false, false, false),
+            0xFF => (Mbc::HuC1, true, true, false, false),
+            _ => return Err(Error::UnknownCartridgeType(code)),
+        };
+
+        Ok(CartridgeType {
+            code,
+            mbc,
+            ram,
+            battery,
+            timer,
+            rumble,
+        })
+    }
+}
+
+/// Who published the game.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Licensee {
+    /// The code at 0x014B.
+    Old(u8),
+    /// The two ASCII characters at 0x0144, used when the old code is 0x33.
+    New([u8; 2]),
+}
+
+/// The cartridge header at 0x0100-0x014F.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Header {
+    pub title: String,
+    pub cgb_flag: CgbFlag,
+    pub sgb: bool,
+    pub cartridge_type: CartridgeType,
+    pub rom_size_code: u8,
+    pub ram_size_code: u8,
+    pub licensee: Licensee,
+    pub version: u8,
+    pub header_checksum: u8,
+    pub global_checksum: u16,
+}
+
+impl Header {
+    /// Parses and validates the header of a whole ROM image.
+    pub fn parse(rom: &[u8]) -> Result<Header> {
+        if rom.len() < HEADER_END {
+            return Err(Error::TooSmall { len: rom.len() });
+        }
+
+        let cgb_flag = match rom[CGB_FLAG_ADDR] {
+            0xC0 => CgbFlag::Only,
+            flag if flag & 0x80 != 0 => CgbFlag::Supported,
+            _ => CgbFlag::Dmg,
+        };
+
+        // CGB cartridges took the last byte of the title for their flag.
+        let title_end = match cgb_flag {
+            CgbFlag::Dmg => TITLE_END,
+            CgbFlag::Supported | CgbFlag::Only => CGB_FLAG_ADDR,
+        };
+        let title = rom[TITLE_START..title_end]
+            .iter()
+            .take_while(|byte| **byte != 0)
+            .map(|byte| char::from(*byte))
+            .collect();
+
+        let licensee = match rom[OLD_LICENSEE_ADDR] {
+            USE_NEW_LICENSEE => Licensee::New([rom[NEW_LICENSEE_ADDR], rom[NEW_LICENSEE_ADDR + 1]]),
+            code => Licensee::Old(code),
+        };
+
+        let header = Header {
+            title,
+            cgb_flag,
+            sgb: rom[SGB_FLAG_ADDR] == 0x03,
+            cartridge_type: CartridgeType::from_code(rom[CARTRIDGE_TYPE_ADDR])?,
+            rom_size_code: rom[ROM_SIZE_ADDR],
+            ram_size_code: rom[RAM_SIZE_ADDR],
+            licensee,
+            version: rom[VERSION_ADDR],
+            header_checksum: rom[HEADER_CHECKSUM_ADDR],
+            global_checksum: u16::from_be_bytes([
+                rom[GLOBAL_CHECKSUM_ADDR],
+                rom[GLOBAL_CHECKSUM_ADDR + 1],
+            ]),
+        };
+
+        header.rom_size()?;
+        header.ram_size()?;
+
+        let actual = header_checksum(rom);
+        if actual != header.header_checksum {
+            return Err(Error::HeaderChecksumMismatch {
+                expected: header.header_checksum,
+                actual,
+            });
+        }
+
+        Ok(header)
+    }
+
+    /// ROM size in bytes, 32 KiB shifted left by the size code.
+    pub fn rom_size(&self) -> Result<usize> {
+        match self.rom_size_code {
+            0x00..=0x08 => Ok((ROM_BANK_SIZE * 2) << self.rom_size_code),
+            code => Err(Error::UnknownRomSize(code)),
+        }
+    }
+
+    pub fn rom_banks(&self) -> Result<usize> {
+        Ok(self.rom_size()? / ROM_BANK_SIZE)
+    }
+
+    /// External RAM size in bytes, MBC2 has its RAM built in and declares
+    /// none here.
+    pub fn ram_size(&self) -> Result<usize> {
+        match self.ram_size_code {
+            0x00 => Ok(0),
+            0x01 => Ok(0x800),
+            0x02 => Ok(RAM_BANK_SIZE),
+            0x03 => Ok(RAM_BANK_SIZE * 4),
+            0x04 => Ok(RAM_BANK_SIZE * 16),
+            0x05 => Ok(RAM_BANK_SIZE * 8),
+            code => Err(Error::UnknownRamSize(code)),
+        }
+    }
+}
+
+/// The checksum over 0x0134-0x014C the boot ROM verifies.
+pub fn header_checksum(rom: &[u8]) -> u8 {
+    rom[TITLE_START..HEADER_CHECKSUM_ADDR]
+        .iter()
+        .fold(0_u8, |sum, byte| sum.wrapping_sub(*byte).wrapping_sub(1))
+}
+
+/// Sum of every byte of the image except the global checksum itself.
+pub fn global_checksum(rom: &[u8]) -> u16 {
+    rom.iter()
+        .enumerate()
+        .filter(|(addr, _)| *addr != GLOBAL_CHECKSUM_ADDR && *addr != GLOBAL_CHECKSUM_ADDR + 1)
+        .fold(0_u16, |sum, (_, byte)| sum.wrapping_add(u16::from(*byte)))
+}
+
+/// A ROM image along with its header.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Cartridge {
+    header: Header,
+    rom: Vec<u8>,
+}
+
+impl Cartridge {
+    /// Loads an image whose size and global checksum match its header.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Cartridge> {
+        let header = Header::parse(bytes)?;
+
+        let expected = header.rom_size()?;
+        if bytes.len() != expected {
+            return Err(Error::RomSizeMismatch {
+                expected,
+                actual: bytes.len(),
+            });
+        }
+
+        let actual = global_checksum(bytes);
+        if actual != header.global_checksum {
+            return Err(Error::GlobalChecksumMismatch {
+                expected: header.global_checksum,
+                actual,
+            });
+        }
+
+        Ok(Cartridge {
+            header,
+            rom: bytes.to_vec(),
+        })
+    }
+
+    /// Loads an image the way the hardware would, only needing a valid
+    /// header. The global checksum is ignored and short images are padded
+    /// with 0xFF, what undriven ROM lines read, to a whole number of banks.
+    pub fn from_bytes_unchecked(bytes: &[u8]) -> Result<Cartridge> {
+        let header = Header::parse(bytes)?;
+
+        let banks = bytes.len().div_ceil(ROM_BANK_SIZE).max(2);
+        let mut rom = bytes.to_vec();
+        rom.resize(banks * ROM_BANK_SIZE, 0xFF);
+
+        Ok(Cartridge { header, rom })
+    }
+
+    /// Loads a `.gb` or `.gbc` file.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Cartridge> {
+        let bytes = fs::read(path)?;
+
+        Cartridge::from_bytes(&bytes)
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    pub fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    pub fn into_rom(self) -> Vec<u8> {
+        self.rom
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+
+    /// A blank image of `rom_size_code` with valid checksums.
+    pub(crate) fn rom_image(cartridge_type: u8, rom_size_code: u8, ram_size_code: u8) -> Vec<u8> {
+        let mut rom = vec![0; (ROM_BANK_SIZE * 2) << rom_size_code];
+
+        rom[TITLE_START..TITLE_START + 7].copy_from_slice(b"GEMUBOI");
+        rom[CARTRIDGE_TYPE_ADDR] = cartridge_type;
+        rom[ROM_SIZE_ADDR] = rom_size_code;
+        rom[RAM_SIZE_ADDR] = ram_size_code;
+
+        fix_checksums(&mut rom);
+
+        rom
+    }
+
+    pub(crate) fn fix_checksums(rom: &mut [u8]) {
+        rom[HEADER_CHECKSUM_ADDR] = header_checksum(rom);
+
+        let [h, l] = global_checksum(rom).to_be_bytes();
+        rom[GLOBAL_CHECKSUM_ADDR] = h;
+        rom[GLOBAL_CHECKSUM_ADDR + 1] = l;
+    }
+
+    #[test]
+    fn parse_header() {
+        let mut rom = rom_image(0x13, 0x02, 0x03);
+        rom[VERSION_ADDR] = 0x01;
+        rom[SGB_FLAG_ADDR] = 0x03;
+        rom[OLD_LICENSEE_ADDR] = 0x01;
+        fix_checksums(&mut rom);
+
+        let cartridge = Cartridge::from_bytes(&rom).unwrap();
+        let header = cartridge.header();
+
+        assert_eq!(header.title, "GEMUBOI");
+        assert_eq!(header.cgb_flag, CgbFlag::Dmg);
+        assert!(header.sgb);
+        assert_eq!(header.cartridge_type.mbc, Mbc::Mbc3);
+        assert!(header.cartridge_type.ram);
+        assert!(header.cartridge_type.battery);
+        assert!(!header.cartridge_type.timer);
+        assert_eq!(header.rom_size().unwrap(), 128 * 1024);
+        assert_eq!(header.rom_banks().unwrap(), 8);
+        assert_eq!(header.ram_size().unwrap(), 32 * 1024);
+        assert_eq!(header.licensee, Licensee::Old(0x01));
+        assert_eq!(header.version, 0x01);
+        assert_eq!(cartridge.rom(), &rom[..]);
+    }
+
+    #[test]
+    fn parse_cgb_title_and_new_licensee() {
+        let mut rom = rom_image(0x00, 0x00, 0x00);
+        rom[TITLE_START..TITLE_END].copy_from_slice(b"ABCDEFGHIJKLMNOP");
+        rom[CGB_FLAG_ADDR] = 0xC0;
+        rom[NEW_LICENSEE_ADDR] = b'0';
+        rom[NEW_LICENSEE_ADDR + 1] = b'1';
+        rom[OLD_LICENSEE_ADDR] = USE_NEW_LICENSEE;
+        fix_checksums(&mut rom);
+
+        let header = Header::parse(&rom).unwrap();
+
+        assert_eq!(header.title, "ABCDEFGHIJKLMNO");
+        assert_eq!(header.cgb_flag, CgbFlag::Only);
+        assert_eq!(header.licensee, Licensee::New(*b"01"));
+    }
+
+    #[test]
+    fn reject_too_small_image() {
+        let result = Cartridge::from_bytes(&[0; 0x100]);
+
+        assert!(matches!(result, Err(Error::TooSmall { len: 0x100 })));
+    }
+
+    #[test]
+    fn reject_bad_header_checksum() {
+        let mut rom = rom_image(0x00, 0x00, 0x00);
+        rom[HEADER_CHECKSUM_ADDR] ^= 0xFF;
+
+        assert!(matches!(
+            Header::parse(&rom),
+            Err(Error::HeaderChecksumMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn reject_bad_global_checksum() {
+        let mut rom = rom_image(0x00, 0x00, 0x00);
+        rom[0x2000] = 0x42;
+
+        assert!(matches!(
+            Cartridge::from_bytes(&rom),
+            Err(Error::GlobalChecksumMismatch { .. })
+        ));
+        // The header alone is still fine.
+        assert!(Header::parse(&rom).is_ok());
+    }
+
+    #[test]
+    fn reject_unknown_codes() {
+        assert!(matches!(
+            Header::parse(&rom_image(0x04, 0x00, 0x00)),
+            Err(Error::UnknownCartridgeType(0x04))
+        ));
+
+        let mut rom = rom_image(0x00, 0x00, 0x00);
+        rom[ROM_SIZE_ADDR] = 0x09;
+        fix_checksums(&mut rom);
+        assert!(matches!(
+            Header::parse(&rom),
+            Err(Error::UnknownRomSize(0x09))
+        ));
+
+        let mut rom = rom_image(0x00, 0x00, 0x00);
+        rom[RAM_SIZE_ADDR] = 0x06;
+        fix_checksums(&mut rom);
+        assert!(matches!(
+            Header::parse(&rom),
+            Err(Error::UnknownRamSize(0x06))
+        ));
+    }
+
+    #[test]
+    fn reject_truncated_rom() {
+        let rom = rom_image(0x01, 0x01, 0x00);
+
+        assert!(matches!(
+            Cartridge::from_bytes(&rom[..ROM_BANK_SIZE * 2]),
+            Err(Error::RomSizeMismatch {
+                expected: 0x10000,
+                actual: 0x8000
+            })
+        ));
+    }
+
+    #[test]
+    fn unchecked_accepts_bad_global_checksum() {
+        let mut rom = rom_image(0x01, 0x01, 0x00);
+        rom[0x2000] = 0x42;
+
+        let cartridge = Cartridge::from_bytes_unchecked(&rom).unwrap();
+
+        assert_eq!(cartridge.rom(), &rom[..]);
+    }
+
+    #[test]
+    fn unchecked_pads_to_whole_banks() {
+        let rom = rom_image(0x01, 0x01, 0x00);
+
+        let cartridge = Cartridge::from_bytes_unchecked(&rom[..ROM_BANK_SIZE + 1]).unwrap();
+
+        assert_eq!(cartridge.rom().len(), ROM_BANK_SIZE * 2);
+        assert_eq!(
+            cartridge.rom()[..ROM_BANK_SIZE + 1],
+            rom[..ROM_BANK_SIZE + 1]
+        );
+        assert_eq!(cartridge.rom()[ROM_BANK_SIZE + 1], 0xFF);
+        // The header still has to be valid.
+        assert!(matches!(
+            Cartridge::from_bytes_unchecked(&[0; 0x100]),
+            Err(Error::TooSmall { len: 0x100 })
+        ));
+    }
+
+    #[test]
+    fn load_from_missing_path() {
+        assert!(matches!(
+            Cartridge::from_path("/nonexistent/gemuboi.gb"),
+            Err(Error::Io(_))
+        ));
+    }
+}
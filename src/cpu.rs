@@ -1,22 +1,272 @@
+use std::error;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::interrupt::{Interrupt, DISPATCH_CYCLES};
+use crate::joypad::ButtonState;
 use crate::mmu::Mmu;
-use crate::opcode::table::{op_table, Cycle, OpLength};
+use crate::opcode::function::push;
+use crate::opcode::table::{is_illegal_op_code, op_table, Cycle, OpLength};
 use crate::registers::Registers;
 
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum StepError {
+    IllegalOpcode { pc: u16, opcode: u8 },
+}
+
+impl fmt::Display for StepError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StepError::IllegalOpcode { pc, opcode } => {
+                write!(f, "illegal opcode {:#04X} at {:#06X}", opcode, pc)
+            }
+        }
+    }
+}
+
+impl error::Error for StepError {}
+
+/// Handler for illegal opcodes, called with the PC and the opcode.
+pub type IllegalOpcodeCallback = Rc<dyn Fn(&mut Cpu, u16, u8)>;
+
+/// What `Cpu::step` does when it meets one of the eleven undefined opcodes.
+#[derive(Default, Clone)]
+pub enum IllegalOpcodePolicy {
+    /// Stop executing forever, the way hardware does.
+    #[default]
+    LockUp,
+    /// Leave the CPU untouched and return `StepError::IllegalOpcode`.
+    Error,
+    /// Skip the opcode as a 4 cycles, 1 byte instruction and call the handler
+    /// afterwards, which is free to move PC elsewhere.
+    Callback(IllegalOpcodeCallback),
+}
+
+impl fmt::Debug for IllegalOpcodePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IllegalOpcodePolicy::LockUp => write!(f, "LockUp"),
+            IllegalOpcodePolicy::Error => write!(f, "Error"),
+            IllegalOpcodePolicy::Callback(_) => write!(f, "Callback(..)"),
+        }
+    }
+}
+
+impl PartialEq for IllegalOpcodePolicy {
+    fn eq(&self, rhs: &Self) -> bool {
+        match (self, rhs) {
+            (IllegalOpcodePolicy::LockUp, IllegalOpcodePolicy::LockUp) => true,
+            (IllegalOpcodePolicy::Error, IllegalOpcodePolicy::Error) => true,
+            (IllegalOpcodePolicy::Callback(lhs), IllegalOpcodePolicy::Callback(rhs)) => {
+                Rc::ptr_eq(lhs, rhs)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// CGB speed switch register.
+pub const KEY1_ADDR: u16 = 0xFF4D;
+
 #[derive(Default, PartialEq, Debug, Clone)]
 pub struct Cpu {
     pub(crate) registers: Registers,
     pub(crate) mmu: Mmu,
 
     ime: bool,
+    // EI only takes effect after the instruction following it.
+    ime_scheduled: bool,
+    // IME was set by an EI right before the instruction being executed.
+    ime_just_enabled: bool,
+    halted: bool,
+    // HALT executed with IME unset and an interrupt pending, the next fetch
+    // does not move PC.
+    halt_bug: bool,
+    stopped: bool,
+    locked_up: bool,
+
+    cgb_mode: bool,
+    double_speed: bool,
+
+    illegal_opcode_policy: IllegalOpcodePolicy,
 }
 
 impl Cpu {
+    /// A CPU running from `mmu`, starting at PC 0x0000 with nothing set up.
+    pub fn with_mmu(mmu: Mmu) -> Self {
+        Self {
+            mmu,
+            ..Self::default()
+        }
+    }
+
+    /// Fetches the instruction at PC, executes it and moves PC to the next
+    /// instruction, then clocks the rest of the hardware by the same amount.
+    /// Returns the T-cycles the instruction took.
+    pub fn step(&mut self) -> Result<u8, StepError> {
+        let cycles = self.step_cpu()?;
+        // STOP stops the system clock along with the CPU.
+        if !self.stopped {
+            self.mmu.tick(u32::from(cycles), self.double_speed);
+        }
+
+        Ok(cycles)
+    }
+
+    fn step_cpu(&mut self) -> Result<u8, StepError> {
+        if self.locked_up {
+            return Ok(4);
+        }
+
+        if self.stopped {
+            // Only a joypad line going low while stopped brings the CPU
+            // back, IF keeps the joypad bit of any earlier press.
+            if !self.mmu.joypad().line_fell() {
+                return Ok(4);
+            }
+
+            self.stopped = false;
+        }
+
+        if self.halted {
+            // HALT ends on any pending interrupt, whether IME is set or not.
+            if self.mmu.pending_interrupt().is_none() {
+                return Ok(4);
+            }
+
+            self.halted = false;
+        }
+
+        if self.ime {
+            if let Some(interrupt) = self.mmu.pending_interrupt() {
+                return Ok(self.dispatch_interrupt(interrupt));
+            }
+        }
+
+        self.ime_just_enabled = self.ime_scheduled;
+        if self.ime_scheduled {
+            self.ime_scheduled = false;
+            self.ime = true;
+        }
+
+        let pc = self.registers.pc();
+        let op_code = self.mmu.read_byte(pc);
+
+        if is_illegal_op_code(op_code) {
+            return self.execute_illegal_instruction(pc, op_code);
+        }
+
+        Ok(self.execute_instruction(op_code))
+    }
+
+    /// Steps until at least `cycles` T-cycles have elapsed. Returns the
+    /// T-cycles actually spent, which may overshoot by the last instruction.
+    pub fn run_for_cycles(&mut self, cycles: u32) -> Result<u32, StepError> {
+        let mut elapsed = 0;
+
+        while elapsed < cycles {
+            elapsed += u32::from(self.step()?);
+        }
+
+        Ok(elapsed)
+    }
+
+    /// Steps until `predicate` holds, checking it before every instruction.
+    /// Returns the T-cycles spent.
+    pub fn run_until<P>(&mut self, mut predicate: P) -> Result<u32, StepError>
+    where
+        P: FnMut(&Cpu) -> bool,
+    {
+        let mut elapsed = 0;
+
+        while !predicate(self) {
+            elapsed += u32::from(self.step()?);
+        }
+
+        Ok(elapsed)
+    }
+
     fn execute_instruction(&mut self, op_code: u8) -> u8 {
-        let (Cycle(cycle), OpLength(_len)) = op_table(op_code)(self);
+        if self.halt_bug {
+            // Pretend PC never moved past the op code, so the op code byte is
+            // read again as the first argument or as the next instruction.
+            self.halt_bug = false;
+            self.registers.set_pc(self.registers.pc().wrapping_sub(1));
+        }
+
+        let (Cycle(cycle), OpLength(len)) = op_table(op_code)(self);
+
+        let next_pc = self.registers.pc().wrapping_add(u16::from(len));
+        self.registers.set_pc(next_pc);
 
         cycle
     }
 
+    fn dispatch_interrupt(&mut self, interrupt: Interrupt) -> u8 {
+        self.ime = false;
+        self.mmu.clear_interrupt(interrupt);
+
+        let pc = self.registers.pc();
+        push(self, pc);
+        self.registers.set_pc(interrupt.vector());
+
+        DISPATCH_CYCLES
+    }
+
+    pub fn request_interrupt(&mut self, interrupt: Interrupt) {
+        self.mmu.request_interrupt(interrupt);
+    }
+
+    /// Updates the buttons the host holds. A press on a selected line
+    /// raises the joypad interrupt, and ends STOP.
+    pub fn set_buttons(&mut self, buttons: ButtonState) {
+        self.mmu.set_buttons(buttons);
+    }
+
+    fn execute_illegal_instruction(&mut self, pc: u16, op_code: u8) -> Result<u8, StepError> {
+        match self.illegal_opcode_policy.clone() {
+            IllegalOpcodePolicy::LockUp => {
+                self.locked_up = true;
+
+                Ok(4)
+            }
+            IllegalOpcodePolicy::Error => Err(StepError::IllegalOpcode {
+                pc,
+                opcode: op_code,
+            }),
+            IllegalOpcodePolicy::Callback(callback) => {
+                self.registers.set_pc(pc.wrapping_add(1));
+                callback(self, pc, op_code);
+
+                Ok(4)
+            }
+        }
+    }
+
+    pub fn set_illegal_opcode_policy(&mut self, policy: IllegalOpcodePolicy) {
+        self.illegal_opcode_policy = policy;
+    }
+
+    pub const fn locked_up(&self) -> bool {
+        self.locked_up
+    }
+
+    pub fn registers(&self) -> &Registers {
+        &self.registers
+    }
+
+    pub fn registers_mut(&mut self) -> &mut Registers {
+        &mut self.registers
+    }
+
+    pub fn mmu(&self) -> &Mmu {
+        &self.mmu
+    }
+
+    pub fn mmu_mut(&mut self) -> &mut Mmu {
+        &mut self.mmu
+    }
+
     pub fn read_hl_dref(&self) -> u8 {
         let hl = self.registers.hl();
 
@@ -39,7 +289,74 @@ impl Cpu {
         self.ime = to;
     }
 
-    #[cfg(test)]
+    /// Sets IME once the next instruction has been executed, as EI does.
+    pub fn schedule_ime(&mut self) {
+        self.ime_scheduled = true;
+    }
+
+    /// Enters HALT, or triggers the HALT bug when IME is unset and an
+    /// interrupt is already pending. Right after EI, a pending interrupt is
+    /// serviced at once and returns to the HALT, which then runs again.
+    pub fn halt(&mut self) {
+        let pending = self.mmu.pending_interrupt().is_some();
+
+        if self.ime_just_enabled && pending {
+            // Undo the PC increment, so the dispatch pushes the HALT.
+            self.registers.set_pc(self.registers.pc().wrapping_sub(1));
+        } else if !self.ime && pending {
+            self.halt_bug = true;
+        } else {
+            self.halted = true;
+        }
+    }
+
+    /// Enters STOP, or switches speed when a CGB speed switch is armed in
+    /// KEY1. Either way DIV is reset.
+    pub fn stop(&mut self) {
+        let key1 = self.mmu.read_byte(KEY1_ADDR);
+        self.mmu.timer_mut().reset_div();
+
+        if self.cgb_mode && key1 & 0b0000_0001 != 0 {
+            self.double_speed = !self.double_speed;
+
+            let new_key1 = if self.double_speed { 0b1000_0000 } else { 0 };
+            self.mmu.set_io(KEY1_ADDR, new_key1);
+        } else {
+            self.stopped = true;
+            self.mmu.joypad_mut().clear_line_fell();
+        }
+    }
+
+    /// Leaves STOP, for callers which see the joypad lines go low without
+    /// requesting the interrupt.
+    pub fn wake_from_stop(&mut self) {
+        self.stopped = false;
+    }
+
+    pub fn set_cgb_mode(&mut self, to: bool) {
+        self.cgb_mode = to;
+    }
+
+    pub const fn double_speed(&self) -> bool {
+        self.double_speed
+    }
+
+    pub fn set_halted(&mut self, to: bool) {
+        self.halted = to;
+    }
+
+    pub const fn halted(&self) -> bool {
+        self.halted
+    }
+
+    pub fn set_stopped(&mut self, to: bool) {
+        self.stopped = to;
+    }
+
+    pub const fn stopped(&self) -> bool {
+        self.stopped
+    }
+
     pub const fn ime(&self) -> bool {
         self.ime
     }
@@ -49,12 +366,33 @@ impl Cpu {
 mod test {
     use super::Cpu;
 
+    use crate::mmu::{Mmu, ROM_SIZE};
+
+    /// A CPU at `pc` with a cartridge holding each `(addr, bytes)` segment,
+    /// the rest of the ROM is NOPs.
+    fn cpu_with_rom(pc: u16, segments: &[(u16, &[u8])]) -> Cpu {
+        let mut rom = vec![0; ROM_SIZE];
+        for (addr, bytes) in segments {
+            let start = usize::from(*addr);
+            rom[start..start + bytes.len()].copy_from_slice(bytes);
+        }
+
+        let mut cpu = Cpu::with_mmu(Mmu::with_rom(rom));
+        cpu.registers.set_pc(pc);
+
+        cpu
+    }
+
+    fn cpu_with_program(pc: u16, program: &[u8]) -> Cpu {
+        cpu_with_rom(pc, &[(pc, program)])
+    }
+
     mod read_hl_dref {
         use super::super::Cpu;
 
         #[test]
         fn normal_run() {
-            let hl = 0x4242;
+            let hl = 0xC242;
             let the_value = 0x44;
 
             let mut cpu = Cpu::default();
@@ -79,7 +417,7 @@ mod test {
 
     #[test]
     fn read_byte_argument_normal() {
-        let pc = 0x42;
+        let pc = 0xC042;
 
         let arg_index = 1;
         let arg_value = 0x12;
@@ -93,23 +431,21 @@ mod test {
 
     #[test]
     fn read_byte_argument_out_of_bound() {
-        use crate::mmu::INVALID_READ_DEFAULT_VALUE;
+        use crate::mmu::OPEN_BUS_VALUE;
 
         let pc = 0x10;
         let arg_index = 0xFFFF;
 
+        // No cartridge is inserted, so the wrapped address reads open bus.
         let mut cpu = Cpu::default();
         cpu.registers.set_pc(pc);
 
-        assert_eq!(
-            cpu.read_byte_argument(arg_index),
-            INVALID_READ_DEFAULT_VALUE
-        );
+        assert_eq!(cpu.read_byte_argument(arg_index), OPEN_BUS_VALUE);
     }
 
     #[test]
     fn read_word_argument_normal() {
-        let pc = 0x42;
+        let pc = 0xC042;
 
         let arg_index = 1;
         let arg_value = 0x1234;
@@ -123,11 +459,9 @@ mod test {
 
     #[test]
     fn read_word_argument_out_of_bound() {
-        use std::u16::MAX;
-
-        let pc = 0x42_u16;
+        let pc = 0xC042_u16;
 
-        let arg_index = MAX;
+        let arg_index = u16::MAX;
         let arg_value = 0x1234;
 
         let mut cpu = Cpu::default();
@@ -138,4 +472,584 @@ mod test {
 
         assert_eq!(cpu.read_word_argument(arg_index), arg_value);
     }
+
+    mod step {
+        use super::{cpu_with_program, cpu_with_rom};
+
+        #[test]
+        fn run_nop() {
+            let mut cpu = cpu_with_program(0x100, &[0x00]);
+
+            assert_eq!(cpu.step(), Ok(4));
+            assert_eq!(cpu.registers.pc(), 0x101);
+        }
+
+        #[test]
+        fn run_instruction_with_argument() {
+            // LD B,d8
+            let mut cpu = cpu_with_program(0x100, &[0x06, 0x42]);
+
+            assert_eq!(cpu.step(), Ok(8));
+            assert_eq!(cpu.registers.b(), 0x42);
+            assert_eq!(cpu.registers.pc(), 0x102);
+        }
+
+        #[test]
+        fn run_jp_nn() {
+            let mut cpu = cpu_with_program(0x100, &[0xC3, 0x50, 0x01]);
+
+            assert_eq!(cpu.step(), Ok(16));
+            assert_eq!(cpu.registers.pc(), 0x0150);
+        }
+
+        #[test]
+        fn run_jp_to_itself() {
+            let mut cpu = cpu_with_program(0x100, &[0xC3, 0x00, 0x01]);
+
+            cpu.step().unwrap();
+            assert_eq!(cpu.registers.pc(), 0x0100);
+        }
+
+        #[test]
+        fn run_jr_taken_and_not_taken() {
+            // JR NZ,+5
+            let mut cpu = cpu_with_program(0x100, &[0x20, 0x05]);
+
+            cpu.registers.flag.set_zero(false);
+            assert_eq!(cpu.clone().step(), Ok(12));
+
+            let mut taken = cpu.clone();
+            taken.step().unwrap();
+            assert_eq!(taken.registers.pc(), 0x107);
+
+            cpu.registers.flag.set_zero(true);
+            assert_eq!(cpu.step(), Ok(8));
+            assert_eq!(cpu.registers.pc(), 0x102);
+        }
+
+        #[test]
+        fn run_jr_to_itself() {
+            // JR -2
+            let mut cpu = cpu_with_program(0x100, &[0x18, 0xFE]);
+
+            cpu.step().unwrap();
+            assert_eq!(cpu.registers.pc(), 0x100);
+        }
+
+        #[test]
+        fn run_call_and_ret() {
+            // CALL 0x0200 at 0x0100, RET at 0x0200.
+            let mut cpu = cpu_with_rom(0x100, &[(0x100, &[0xCD, 0x00, 0x02]), (0x200, &[0xC9])]);
+            cpu.registers.set_sp(0xFFFE);
+
+            assert_eq!(cpu.step(), Ok(24));
+            assert_eq!(cpu.registers.pc(), 0x0200);
+            assert_eq!(cpu.registers.sp(), 0xFFFC);
+
+            assert_eq!(cpu.step(), Ok(16));
+            assert_eq!(cpu.registers.pc(), 0x0103);
+            assert_eq!(cpu.registers.sp(), 0xFFFE);
+        }
+
+        #[test]
+        fn run_rst_and_ret() {
+            let mut cpu = cpu_with_rom(0x100, &[(0x100, &[0xEF]), (0x28, &[0xC9])]);
+            cpu.registers.set_sp(0xFFFE);
+
+            cpu.step().unwrap();
+            assert_eq!(cpu.registers.pc(), 0x0028);
+
+            cpu.step().unwrap();
+            assert_eq!(cpu.registers.pc(), 0x0101);
+        }
+
+        #[test]
+        fn run_cb_prefix() {
+            // RLC B
+            let mut cpu = cpu_with_program(0x100, &[0xCB, 0x00]);
+            cpu.registers.set_b(0b1000_0000);
+
+            assert_eq!(cpu.step(), Ok(8));
+            assert_eq!(cpu.registers.b(), 0b0000_0001);
+            assert_eq!(cpu.registers.pc(), 0x102);
+        }
+
+        #[test]
+        fn run_for_cycles() {
+            let mut cpu = cpu_with_program(0x100, &[0x00; 16]);
+
+            assert_eq!(cpu.run_for_cycles(10), Ok(12));
+            assert_eq!(cpu.registers.pc(), 0x103);
+        }
+
+        #[test]
+        fn run_until() {
+            // LD A,d8 ; NOP ; NOP
+            let mut cpu = cpu_with_program(0x100, &[0x3E, 0x42, 0x00, 0x00]);
+
+            let elapsed = cpu.run_until(|cpu| cpu.registers().pc() == 0x103);
+
+            assert_eq!(elapsed, Ok(12));
+            assert_eq!(cpu.registers.a(), 0x42);
+        }
+
+        #[test]
+        fn run_cartridge_with_bad_global_checksum() {
+            use super::super::Cpu;
+            use crate::cartridge::test::rom_image;
+            use crate::cartridge::Cartridge;
+            use crate::mmu::Mmu;
+
+            let mut rom = rom_image(0x00, 0x00, 0x00);
+            // LD A,(0x4123)
+            rom[0x100..0x103].copy_from_slice(&[0xFA, 0x23, 0x41]);
+            rom[0x4123] = 0x42;
+            assert!(Cartridge::from_bytes(&rom).is_err());
+
+            let cartridge = Cartridge::from_bytes_unchecked(&rom).unwrap();
+            let mut cpu = Cpu {
+                mmu: Mmu::with_cartridge(cartridge).unwrap(),
+                ..Cpu::default()
+            };
+            cpu.registers.set_pc(0x100);
+
+            cpu.step().unwrap();
+
+            assert_eq!(cpu.registers.a(), 0x42);
+        }
+
+        #[test]
+        fn pixel_fifo_renders_through_step() {
+            use super::super::Cpu;
+            use crate::mmu::{Mmu, ROM_SIZE};
+            use crate::ppu::{
+                Renderer, BGP_ADDR, LCDC_ADDR, LCDC_BG_ENABLE, LCDC_LCD_ENABLE, LCDC_TILE_DATA,
+            };
+
+            let frame = |renderer| {
+                let mut rom = vec![0; ROM_SIZE];
+                // JR -2
+                rom[0x100..0x102].copy_from_slice(&[0x18, 0xFE]);
+                let mut cpu = Cpu::with_mmu(Mmu::with_rom(rom).with_renderer(renderer));
+                cpu.registers.set_pc(0x100);
+
+                // Every background tile is tile 0, stripes of colors 1 and 2.
+                for row in 0..8 {
+                    cpu.mmu.write_byte(0x8000 + row * 2, 0xAA).unwrap();
+                    cpu.mmu.write_byte(0x8001 + row * 2, 0x55).unwrap();
+                }
+                cpu.mmu.write_byte(BGP_ADDR, 0xE4).unwrap();
+                let lcdc = LCDC_LCD_ENABLE | LCDC_TILE_DATA | LCDC_BG_ENABLE;
+                cpu.mmu.write_byte(LCDC_ADDR, lcdc).unwrap();
+
+                cpu.run_for_cycles(2 * 70_224).unwrap();
+                assert_eq!(cpu.mmu().ppu().renderer(), renderer);
+
+                cpu.mmu().ppu().framebuffer().to_vec()
+            };
+
+            let fifo = frame(Renderer::PixelFifo);
+            assert!(fifo.iter().any(|shade| *shade != fifo[0]));
+            assert_eq!(fifo, frame(Renderer::Scanline));
+        }
+    }
+
+    mod illegal_opcode {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        use super::super::{Cpu, IllegalOpcodePolicy, StepError};
+        use super::cpu_with_program;
+
+        fn cpu_with_illegal_opcode() -> Cpu {
+            cpu_with_program(0x100, &[0xDD])
+        }
+
+        #[test]
+        fn run_with_lock_up_policy() {
+            let mut cpu = cpu_with_illegal_opcode();
+
+            assert_eq!(cpu.step(), Ok(4));
+            assert!(cpu.locked_up());
+
+            // A locked up CPU never fetches again.
+            assert_eq!(cpu.run_for_cycles(40), Ok(40));
+            assert_eq!(cpu.registers.pc(), 0x100);
+        }
+
+        #[test]
+        fn run_with_error_policy() {
+            let mut cpu = cpu_with_illegal_opcode();
+            cpu.set_illegal_opcode_policy(IllegalOpcodePolicy::Error);
+
+            let expected_cpu = cpu.clone();
+
+            assert_eq!(
+                cpu.step(),
+                Err(StepError::IllegalOpcode {
+                    pc: 0x100,
+                    opcode: 0xDD
+                })
+            );
+            assert_eq!(cpu, expected_cpu);
+        }
+
+        #[test]
+        fn run_with_callback_policy() {
+            let seen = Rc::new(Cell::new(None));
+            let seen_in_callback = seen.clone();
+
+            let mut cpu = cpu_with_illegal_opcode();
+            cpu.set_illegal_opcode_policy(IllegalOpcodePolicy::Callback(Rc::new(
+                move |cpu: &mut Cpu, pc, opcode| {
+                    seen_in_callback.set(Some((pc, opcode, cpu.registers().pc())));
+                },
+            )));
+
+            assert_eq!(cpu.step(), Ok(4));
+            assert_eq!(seen.get(), Some((0x100, 0xDD, 0x101)));
+            assert_eq!(cpu.registers.pc(), 0x101);
+        }
+    }
+
+    mod interrupt {
+        use super::super::Cpu;
+        use super::cpu_with_program;
+
+        use crate::interrupt::{Interrupt, IE_ADDR, IF_ADDR};
+
+        fn cpu_with_nops() -> Cpu {
+            cpu_with_program_and_enable(&[])
+        }
+
+        fn cpu_with_program_and_enable(program: &[u8]) -> Cpu {
+            let mut cpu = cpu_with_program(0x100, program);
+            cpu.registers.set_sp(0xDFFE);
+            cpu.mmu.write_byte(IE_ADDR, 0xFF).unwrap();
+
+            cpu
+        }
+
+        #[test]
+        fn dispatch_pushes_pc_and_jumps_to_vector() {
+            let mut cpu = cpu_with_nops();
+            cpu.set_ime(true);
+            cpu.request_interrupt(Interrupt::Timer);
+
+            assert_eq!(cpu.step(), Ok(20));
+            assert_eq!(cpu.registers.pc(), 0x50);
+            assert_eq!(cpu.registers.sp(), 0xDFFC);
+            assert_eq!(cpu.mmu.read_word(0xDFFC), 0x100);
+            assert!(!cpu.ime());
+            // The upper 3 bits of IF are unused and read as 1.
+            assert_eq!(cpu.mmu.read_byte(IF_ADDR), 0b1110_0000);
+        }
+
+        #[test]
+        fn dispatch_follows_priority() {
+            let mut cpu = cpu_with_nops();
+            cpu.set_ime(true);
+            cpu.request_interrupt(Interrupt::Joypad);
+            cpu.request_interrupt(Interrupt::LcdStat);
+
+            cpu.step().unwrap();
+
+            assert_eq!(cpu.registers.pc(), 0x48);
+            assert_eq!(
+                cpu.mmu.read_byte(IF_ADDR),
+                0b1110_0000 | Interrupt::Joypad.bit()
+            );
+        }
+
+        #[test]
+        fn no_dispatch_without_ime() {
+            let mut cpu = cpu_with_nops();
+            cpu.request_interrupt(Interrupt::VBlank);
+
+            assert_eq!(cpu.step(), Ok(4));
+            assert_eq!(cpu.registers.pc(), 0x101);
+        }
+
+        #[test]
+        fn no_dispatch_without_enable() {
+            let mut cpu = cpu_with_nops();
+            cpu.set_ime(true);
+            cpu.mmu.write_byte(IE_ADDR, 0).unwrap();
+            cpu.request_interrupt(Interrupt::VBlank);
+
+            assert_eq!(cpu.step(), Ok(4));
+            assert_eq!(cpu.registers.pc(), 0x101);
+        }
+
+        #[test]
+        fn ei_takes_effect_after_next_instruction() {
+            // EI ; NOP ; NOP
+            let mut cpu = cpu_with_program_and_enable(&[0xFB]);
+            cpu.request_interrupt(Interrupt::VBlank);
+
+            cpu.step().unwrap();
+            assert!(!cpu.ime());
+
+            cpu.step().unwrap();
+            assert_eq!(cpu.registers.pc(), 0x102);
+            assert!(cpu.ime());
+
+            assert_eq!(cpu.step(), Ok(20));
+            assert_eq!(cpu.registers.pc(), 0x40);
+        }
+
+        #[test]
+        fn ei_followed_by_di_keeps_interrupts_disabled() {
+            // EI ; DI ; NOP
+            let mut cpu = cpu_with_program_and_enable(&[0xFB, 0xF3]);
+            cpu.request_interrupt(Interrupt::VBlank);
+
+            cpu.run_for_cycles(12).unwrap();
+
+            assert!(!cpu.ime());
+            assert_eq!(cpu.registers.pc(), 0x103);
+        }
+    }
+
+    mod low_power {
+        use super::super::{Cpu, KEY1_ADDR};
+
+        use crate::interrupt::{Interrupt, IE_ADDR, IF_ADDR};
+        use crate::joypad::ButtonState;
+
+        fn cpu_with_program(program: &[u8]) -> Cpu {
+            let mut cpu = super::cpu_with_program(0x100, program);
+            cpu.registers.set_sp(0xDFFE);
+
+            cpu
+        }
+
+        #[test]
+        fn halt_sleeps_until_interrupt_pending() {
+            // HALT ; INC A
+            let mut cpu = cpu_with_program(&[0x76, 0x3C]);
+            cpu.mmu.write_byte(IE_ADDR, Interrupt::Timer.bit()).unwrap();
+
+            cpu.step().unwrap();
+            assert!(cpu.halted());
+
+            assert_eq!(cpu.run_for_cycles(40), Ok(40));
+            assert_eq!(cpu.registers.pc(), 0x101);
+
+            // Requested but not enabled interrupts are ignored.
+            cpu.request_interrupt(Interrupt::VBlank);
+            cpu.step().unwrap();
+            assert!(cpu.halted());
+
+            cpu.request_interrupt(Interrupt::Timer);
+            cpu.step().unwrap();
+            assert!(!cpu.halted());
+            assert_eq!(cpu.registers.a(), 1);
+            assert_eq!(cpu.registers.pc(), 0x102);
+        }
+
+        #[test]
+        fn halt_with_ime_dispatches_interrupt() {
+            let mut cpu = cpu_with_program(&[0x76]);
+            cpu.set_ime(true);
+            cpu.mmu.write_byte(IE_ADDR, 0xFF).unwrap();
+
+            cpu.step().unwrap();
+            cpu.request_interrupt(Interrupt::Serial);
+
+            assert_eq!(cpu.step(), Ok(20));
+            assert!(!cpu.halted());
+            assert_eq!(cpu.registers.pc(), 0x58);
+            assert_eq!(cpu.mmu.read_word(0xDFFC), 0x101);
+        }
+
+        #[test]
+        fn halt_bug_reads_next_byte_twice() {
+            // HALT ; LD A,d8 0x14
+            let mut cpu = cpu_with_program(&[0x76, 0x3E, 0x14]);
+            cpu.mmu.write_byte(IE_ADDR, 0xFF).unwrap();
+            cpu.request_interrupt(Interrupt::VBlank);
+
+            cpu.step().unwrap();
+            assert!(!cpu.halted());
+
+            // The op code is read again as argument.
+            cpu.step().unwrap();
+            assert_eq!(cpu.registers.a(), 0x3E);
+            assert_eq!(cpu.registers.pc(), 0x102);
+        }
+
+        #[test]
+        fn halt_bug_runs_one_byte_instruction_twice() {
+            // HALT ; INC A
+            let mut cpu = cpu_with_program(&[0x76, 0x3C, 0x00]);
+            cpu.mmu.write_byte(IE_ADDR, 0xFF).unwrap();
+            cpu.request_interrupt(Interrupt::VBlank);
+
+            cpu.run_for_cycles(12).unwrap();
+
+            assert_eq!(cpu.registers.a(), 2);
+            assert_eq!(cpu.registers.pc(), 0x102);
+        }
+
+        #[test]
+        fn stop_wakes_on_joypad() {
+            // STOP 0 ; INC A
+            let mut cpu = cpu_with_program(&[0x10, 0x00, 0x3C]);
+
+            cpu.step().unwrap();
+            assert!(cpu.stopped());
+
+            // Other interrupts do not end STOP.
+            cpu.mmu.write_byte(IE_ADDR, 0xFF).unwrap();
+            cpu.request_interrupt(Interrupt::Timer);
+            cpu.step().unwrap();
+            assert!(cpu.stopped());
+
+            // Nor does the joypad bit in IF on its own.
+            cpu.request_interrupt(Interrupt::Joypad);
+            cpu.step().unwrap();
+            assert!(cpu.stopped());
+
+            cpu.set_buttons(ButtonState {
+                a: true,
+                ..ButtonState::default()
+            });
+            cpu.step().unwrap();
+            assert!(!cpu.stopped());
+            assert_eq!(cpu.registers.a(), 1);
+        }
+
+        #[test]
+        fn earlier_press_does_not_end_stop() {
+            // STOP 0 ; INC A
+            let mut cpu = cpu_with_program(&[0x10, 0x00, 0x3C]);
+            let pressed = ButtonState {
+                b: true,
+                ..ButtonState::default()
+            };
+
+            cpu.set_buttons(pressed);
+            cpu.set_buttons(ButtonState::default());
+            assert_ne!(cpu.mmu.read_byte(IF_ADDR) & Interrupt::Joypad.bit(), 0);
+
+            cpu.step().unwrap();
+            cpu.step().unwrap();
+            assert!(cpu.stopped());
+            assert_eq!(cpu.registers.a(), 0);
+        }
+
+        #[test]
+        fn stop_wakes_on_button_press() {
+            use crate::joypad::{P1_ADDR, P1_SELECT_DIRECTIONS};
+
+            // STOP 0 ; INC A
+            let mut cpu = cpu_with_program(&[0x10, 0x00, 0x3C]);
+            cpu.mmu.write_byte(P1_ADDR, P1_SELECT_DIRECTIONS).unwrap();
+            cpu.step().unwrap();
+
+            // A direction, with only the buttons selected.
+            cpu.set_buttons(ButtonState {
+                up: true,
+                ..ButtonState::default()
+            });
+            cpu.step().unwrap();
+            assert!(cpu.stopped());
+
+            cpu.set_buttons(ButtonState {
+                start: true,
+                ..ButtonState::default()
+            });
+            cpu.step().unwrap();
+            assert!(!cpu.stopped());
+            assert_eq!(cpu.registers.a(), 1);
+        }
+
+        #[test]
+        fn serial_output_is_captured() {
+            use crate::serial::{Link, SerialCapture};
+            use std::cell::RefCell;
+            use std::rc::Rc;
+
+            // The way Blargg's ROMs print: LD A, 'O' ; LDH (SB), A ;
+            // LD A, 0x81 ; LDH (SC), A ; JR -2
+            let mut cpu =
+                cpu_with_program(&[0x3E, b'O', 0xE0, 0x01, 0x3E, 0x81, 0xE0, 0x02, 0x18, 0xFE]);
+            let capture = Rc::new(RefCell::new(SerialCapture::default()));
+            cpu.mmu_mut()
+                .serial_mut()
+                .set_link(Link::Endpoint(capture.clone()));
+
+            cpu.run_for_cycles(5000).unwrap();
+
+            assert_eq!(capture.borrow().output(), "O");
+        }
+
+        #[test]
+        fn stop_switches_speed_when_armed() {
+            let mut cpu = cpu_with_program(&[0x10, 0x00, 0x10, 0x00]);
+            cpu.set_cgb_mode(true);
+            cpu.mmu.write_byte(KEY1_ADDR, 0b0000_0001).unwrap();
+
+            cpu.step().unwrap();
+            assert!(!cpu.stopped());
+            assert!(cpu.double_speed());
+            assert_eq!(cpu.mmu.read_byte(KEY1_ADDR) & 0b1000_0001, 0b1000_0000);
+
+            // Not armed anymore, so this one really stops.
+            cpu.step().unwrap();
+            assert!(cpu.stopped());
+            assert!(cpu.double_speed());
+        }
+
+        #[test]
+        fn ei_before_halt_services_pending_interrupt_and_returns_to_halt() {
+            // EI ; HALT ; INC A
+            let mut cpu = cpu_with_program(&[0xFB, 0x76, 0x3C]);
+            cpu.mmu.write_byte(IE_ADDR, Interrupt::Timer.bit()).unwrap();
+            cpu.request_interrupt(Interrupt::Timer);
+
+            cpu.step().unwrap();
+            cpu.step().unwrap();
+            assert!(!cpu.halted());
+            assert_eq!(cpu.registers.pc(), 0x101);
+
+            cpu.step().unwrap();
+            assert_eq!(cpu.registers.pc(), Interrupt::Timer.vector());
+            assert_eq!(cpu.mmu.read_word(cpu.registers.sp()), 0x101);
+        }
+
+        #[test]
+        fn stop_freezes_the_clock() {
+            use crate::ppu::{LCDC_ADDR, LCDC_LCD_ENABLE};
+
+            // STOP 0
+            let mut cpu = cpu_with_program(&[0x10, 0x00]);
+            cpu.mmu.write_byte(LCDC_ADDR, LCDC_LCD_ENABLE).unwrap();
+
+            cpu.step().unwrap();
+            assert!(cpu.stopped());
+
+            let ly = cpu.mmu.ppu().ly();
+            cpu.run_for_cycles(0x1000).unwrap();
+            assert_eq!(cpu.mmu.ppu().ly(), ly);
+        }
+
+        #[test]
+        fn stop_resets_div() {
+            use crate::timer::DIV_ADDR;
+
+            // STOP 0
+            let mut cpu = cpu_with_program(&[0x10, 0x00]);
+            cpu.mmu.tick(0x4200, false);
+            assert_eq!(cpu.mmu.read_byte(DIV_ADDR), 0x42);
+
+            cpu.step().unwrap();
+            assert!(cpu.stopped());
+            assert_eq!(cpu.mmu.read_byte(DIV_ADDR), 0x00);
+
+            cpu.run_for_cycles(0x1000).unwrap();
+            assert_eq!(cpu.mmu.timer().div(), 0x00);
+        }
+    }
 }
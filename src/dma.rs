@@ -0,0 +1,78 @@
+//! OAM DMA, copying 160 bytes into OAM at one byte per machine cycle.
+
+use std::ops::Range;
+
+use crate::mmu::{Addr, OAM_SIZE};
+
+/// Writing XX here copies XX00-XX9F into OAM.
+pub const DMA_ADDR: Addr = 0xFF46;
+/// T-cycles a whole transfer takes.
+pub const DMA_CYCLES: u32 = OAM_SIZE as u32 * CYCLES_PER_BYTE;
+
+const CYCLES_PER_BYTE: u32 = 4;
+/// Sources from 0xE000 up read the work RAM behind the echo.
+const ECHO_SOURCE_START: Addr = 0xE000;
+const ECHO_OFFSET: Addr = 0x2000;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct OamDma {
+    source: Addr,
+    /// T-cycles since the transfer started.
+    cycles: u32,
+}
+
+impl OamDma {
+    /// A transfer started by writing `value` to DMA.
+    pub fn new(value: u8) -> Self {
+        let mut source = Addr::from(value) << 8;
+        if source >= ECHO_SOURCE_START {
+            source -= ECHO_OFFSET;
+        }
+
+        Self { source, cycles: 0 }
+    }
+
+    pub fn source(&self) -> Addr {
+        self.source
+    }
+
+    /// Advances by `cycles`, returning the offsets of the bytes to copy.
+    pub fn advance(&mut self, cycles: u32) -> Range<Addr> {
+        let start = self.copied();
+        self.cycles = (self.cycles + cycles).min(DMA_CYCLES);
+
+        start..self.copied()
+    }
+
+    pub fn finished(&self) -> bool {
+        self.cycles >= DMA_CYCLES
+    }
+
+    fn copied(&self) -> Addr {
+        (self.cycles / CYCLES_PER_BYTE) as Addr
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn source_from_value() {
+        assert_eq!(OamDma::new(0xC1).source(), 0xC100);
+        assert_eq!(OamDma::new(0xFE).source(), 0xDE00);
+    }
+
+    #[test]
+    fn one_byte_per_machine_cycle() {
+        let mut dma = OamDma::new(0xC0);
+
+        assert_eq!(dma.advance(3), 0..0);
+        assert_eq!(dma.advance(1), 0..1);
+        assert_eq!(dma.advance(12), 1..4);
+        assert!(!dma.finished());
+
+        assert_eq!(dma.advance(DMA_CYCLES), 4..0xA0);
+        assert!(dma.finished());
+    }
+}
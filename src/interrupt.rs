@@ -0,0 +1,84 @@
+/// Interrupt enable register.
+pub const IE_ADDR: u16 = 0xFFFF;
+/// Interrupt flag register.
+pub const IF_ADDR: u16 = 0xFF0F;
+
+/// T-cycles the CPU spends dispatching an interrupt.
+pub const DISPATCH_CYCLES: u8 = 20;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Interrupt {
+    VBlank,
+    LcdStat,
+    Timer,
+    Serial,
+    Joypad,
+}
+
+impl Interrupt {
+    /// Every interrupt, from the highest priority to the lowest.
+    pub const ALL: [Interrupt; 5] = [
+        Interrupt::VBlank,
+        Interrupt::LcdStat,
+        Interrupt::Timer,
+        Interrupt::Serial,
+        Interrupt::Joypad,
+    ];
+
+    /// Bit of this interrupt in IE and IF.
+    pub const fn bit(self) -> u8 {
+        match self {
+            Interrupt::VBlank => 0b0000_0001,
+            Interrupt::LcdStat => 0b0000_0010,
+            Interrupt::Timer => 0b0000_0100,
+            Interrupt::Serial => 0b0000_1000,
+            Interrupt::Joypad => 0b0001_0000,
+        }
+    }
+
+    /// Address the CPU jumps to when servicing this interrupt.
+    pub const fn vector(self) -> u16 {
+        match self {
+            Interrupt::VBlank => 0x40,
+            Interrupt::LcdStat => 0x48,
+            Interrupt::Timer => 0x50,
+            Interrupt::Serial => 0x58,
+            Interrupt::Joypad => 0x60,
+        }
+    }
+
+    /// The highest priority interrupt among `flags`, if any.
+    pub fn highest_priority(flags: u8) -> Option<Interrupt> {
+        Interrupt::ALL
+            .iter()
+            .cloned()
+            .find(|interrupt| flags & interrupt.bit() != 0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn highest_priority_with_no_flags() {
+        assert_eq!(Interrupt::highest_priority(0), None);
+        assert_eq!(Interrupt::highest_priority(0b1110_0000), None);
+    }
+
+    #[test]
+    fn highest_priority_follows_bit_order() {
+        assert_eq!(
+            Interrupt::highest_priority(0b0001_1111),
+            Some(Interrupt::VBlank)
+        );
+        assert_eq!(
+            Interrupt::highest_priority(0b0001_0100),
+            Some(Interrupt::Timer)
+        );
+        assert_eq!(
+            Interrupt::highest_priority(0b0001_0000),
+            Some(Interrupt::Joypad)
+        );
+    }
+}
@@ -0,0 +1,203 @@
+//! The joypad behind P1. Writing bit 4 or 5 low selects the directions or
+//! the buttons, whose lines then pull the lower nibble low while pressed.
+
+use crate::interrupt::Interrupt;
+use crate::mmu::Addr;
+
+pub const P1_ADDR: Addr = 0xFF00;
+
+/// Selects the d-pad when low.
+pub const P1_SELECT_DIRECTIONS: u8 = 0b0001_0000;
+/// Selects A, B, Select and Start when low.
+pub const P1_SELECT_BUTTONS: u8 = 0b0010_0000;
+const P1_SELECT: u8 = P1_SELECT_DIRECTIONS | P1_SELECT_BUTTONS;
+const P1_LINES: u8 = 0b0000_1111;
+const P1_UNUSED_BITS: u8 = 0b1100_0000;
+
+/// What the host holds down, true being pressed.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct ButtonState {
+    pub right: bool,
+    pub left: bool,
+    pub up: bool,
+    pub down: bool,
+    pub a: bool,
+    pub b: bool,
+    pub select: bool,
+    pub start: bool,
+}
+
+impl ButtonState {
+    /// Lines pulled low by the d-pad, bit 0 to 3.
+    fn directions(&self) -> u8 {
+        lines([self.right, self.left, self.up, self.down])
+    }
+
+    /// Lines pulled low by the buttons, bit 0 to 3.
+    fn buttons(&self) -> u8 {
+        lines([self.a, self.b, self.select, self.start])
+    }
+}
+
+fn lines(pressed: [bool; 4]) -> u8 {
+    pressed
+        .iter()
+        .enumerate()
+        .fold(0, |lines, (line, pressed)| {
+            lines | (u8::from(*pressed) << line)
+        })
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Joypad {
+    /// P1 bits 4 and 5 as last written.
+    select: u8,
+    buttons: ButtonState,
+    /// A selected line went low since the last `clear_line_fell`.
+    line_fell: bool,
+}
+
+impl Joypad {
+    pub fn is_register(addr: Addr) -> bool {
+        addr == P1_ADDR
+    }
+
+    pub fn read_register(&self, addr: Addr) -> u8 {
+        debug_assert!(Self::is_register(addr));
+
+        P1_UNUSED_BITS | self.select | (!self.low_lines() & P1_LINES)
+    }
+
+    /// Returns the interrupts raised in IF, selecting a group whose buttons
+    /// are held pulls its lines low too.
+    pub fn write_register(&mut self, addr: Addr, value: u8) -> u8 {
+        debug_assert!(Self::is_register(addr));
+
+        self.update(|joypad| joypad.select = value & P1_SELECT)
+    }
+
+    pub fn buttons(&self) -> ButtonState {
+        self.buttons
+    }
+
+    /// Presses and releases buttons as the host sees them, returns the
+    /// interrupts raised in IF.
+    pub fn set_buttons(&mut self, buttons: ButtonState) -> u8 {
+        self.update(|joypad| joypad.buttons = buttons)
+    }
+
+    /// Whether a selected line went from high to low since the last
+    /// `clear_line_fell`, which is what ends STOP.
+    pub fn line_fell(&self) -> bool {
+        self.line_fell
+    }
+
+    pub fn clear_line_fell(&mut self) {
+        self.line_fell = false;
+    }
+
+    /// Lines of the lower nibble driven low, the selected groups ORed.
+    fn low_lines(&self) -> u8 {
+        let mut lines = 0;
+        if self.select & P1_SELECT_DIRECTIONS == 0 {
+            lines |= self.buttons.directions();
+        }
+        if self.select & P1_SELECT_BUTTONS == 0 {
+            lines |= self.buttons.buttons();
+        }
+
+        lines
+    }
+
+    /// Applies `change`, requesting the joypad interrupt when a line goes
+    /// from high to low.
+    fn update<F: FnOnce(&mut Self)>(&mut self, change: F) -> u8 {
+        let before = self.low_lines();
+        change(self);
+
+        if self.low_lines() & !before != 0 {
+            self.line_fell = true;
+            Interrupt::Joypad.bit()
+        } else {
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn selecting(select: u8) -> Joypad {
+        let mut joypad = Joypad::default();
+        joypad.write_register(P1_ADDR, select);
+
+        joypad
+    }
+
+    #[test]
+    fn nothing_pressed_reads_high() {
+        assert_eq!(selecting(0x00).read_register(P1_ADDR), 0xCF);
+        assert_eq!(selecting(0xFF).read_register(P1_ADDR), 0xFF);
+    }
+
+    #[test]
+    fn reads_are_active_low_per_group() {
+        let mut joypad = selecting(P1_SELECT_BUTTONS);
+        joypad.set_buttons(ButtonState {
+            down: true,
+            a: true,
+            start: true,
+            ..ButtonState::default()
+        });
+
+        // Directions selected.
+        assert_eq!(joypad.read_register(P1_ADDR), 0b1110_0111);
+
+        joypad.write_register(P1_ADDR, P1_SELECT_DIRECTIONS);
+        assert_eq!(joypad.read_register(P1_ADDR), 0b1101_0110);
+
+        joypad.write_register(P1_ADDR, 0x00);
+        assert_eq!(joypad.read_register(P1_ADDR), 0b1100_0110);
+
+        joypad.write_register(P1_ADDR, P1_SELECT);
+        assert_eq!(joypad.read_register(P1_ADDR), 0b1111_1111);
+    }
+
+    #[test]
+    fn pressing_a_selected_button_interrupts() {
+        let mut joypad = selecting(P1_SELECT_DIRECTIONS);
+        let pressed = ButtonState {
+            b: true,
+            ..ButtonState::default()
+        };
+
+        assert_eq!(joypad.set_buttons(pressed), Interrupt::Joypad.bit());
+        assert!(joypad.line_fell());
+        joypad.clear_line_fell();
+
+        // Held, no new falling edge.
+        assert_eq!(joypad.set_buttons(pressed), 0);
+        assert!(!joypad.line_fell());
+        // Releasing is a rising edge.
+        assert_eq!(joypad.set_buttons(ButtonState::default()), 0);
+        assert_eq!(joypad.buttons(), ButtonState::default());
+    }
+
+    #[test]
+    fn unselected_buttons_do_not_interrupt() {
+        let mut joypad = selecting(P1_SELECT_BUTTONS);
+
+        let interrupts = joypad.set_buttons(ButtonState {
+            start: true,
+            ..ButtonState::default()
+        });
+
+        assert_eq!(interrupts, 0);
+        // Selecting the held button's group pulls its line low.
+        assert_eq!(
+            joypad.write_register(P1_ADDR, P1_SELECT_DIRECTIONS),
+            Interrupt::Joypad.bit()
+        );
+    }
+}
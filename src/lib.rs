@@ -1,9 +1,19 @@
 #![allow(dead_code)]
 
+pub mod apu;
 pub mod carry_test;
+pub mod cartridge;
 pub mod cpu;
+pub mod dma;
+pub mod interrupt;
+pub mod joypad;
+pub mod mbc;
 /// This is a module for cpu
 pub mod mmu;
+pub mod ppu;
 pub mod registers;
+pub mod save;
+pub mod serial;
+pub mod timer;
 
 mod opcode;
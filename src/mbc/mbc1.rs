@@ -0,0 +1,256 @@
+use crate::cartridge::{LOGO_END, LOGO_START, NINTENDO_LOGO, ROM_BANK_SIZE};
+use crate::mmu::{Addr, OPEN_BUS_VALUE};
+
+use super::{ram_byte, rom_byte, set_ram_byte};
+
+/// Banks per game on an MBC1M multicart.
+const MULTICART_GAME_BANKS: usize = 0x10;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Mbc1 {
+    ram_enabled: bool,
+    /// The 5-bit BANK1 register at 0x2000-0x3FFF.
+    bank1: u8,
+    /// The 2-bit BANK2 register at 0x4000-0x5FFF.
+    bank2: u8,
+    /// Mode 1 also applies BANK2 to 0x0000-0x3FFF and to RAM.
+    mode: bool,
+    /// MBC1M wiring, where BANK2 sits right above the lower 4 bits of BANK1.
+    multicart: bool,
+}
+
+impl Default for Mbc1 {
+    fn default() -> Self {
+        Mbc1::new(false)
+    }
+}
+
+impl Mbc1 {
+    pub fn new(multicart: bool) -> Self {
+        Self {
+            ram_enabled: false,
+            bank1: 1,
+            bank2: 0,
+            mode: false,
+            multicart,
+        }
+    }
+
+    /// Detects an MBC1M multicart by the logo of a second game found at
+    /// bank 0x10 of a 1 MiB image.
+    pub fn for_rom(rom: &[u8]) -> Self {
+        let second_game = MULTICART_GAME_BANKS * ROM_BANK_SIZE;
+        let multicart = rom.len() == 0x10_0000
+            && rom[second_game + LOGO_START..second_game + LOGO_END] == NINTENDO_LOGO;
+
+        Mbc1::new(multicart)
+    }
+
+    pub fn multicart(&self) -> bool {
+        self.multicart
+    }
+
+    pub fn ram_enabled(&self) -> bool {
+        self.ram_enabled
+    }
+
+    fn bank2_shift(&self) -> u8 {
+        if self.multicart {
+            4
+        } else {
+            5
+        }
+    }
+
+    fn lower_bank1(&self) -> u8 {
+        if self.multicart {
+            self.bank1 & 0x0F
+        } else {
+            self.bank1
+        }
+    }
+
+    /// Bank mapped at 0x0000-0x3FFF.
+    pub fn rom0_bank(&self) -> usize {
+        if self.mode {
+            usize::from(self.bank2) << self.bank2_shift()
+        } else {
+            0
+        }
+    }
+
+    /// Bank mapped at 0x4000-0x7FFF.
+    pub fn rom1_bank(&self) -> usize {
+        (usize::from(self.bank2) << self.bank2_shift()) | usize::from(self.lower_bank1())
+    }
+
+    pub fn ram_bank(&self) -> usize {
+        if self.mode {
+            usize::from(self.bank2)
+        } else {
+            0
+        }
+    }
+
+    pub fn read_rom(&self, rom: &[u8], addr: Addr) -> u8 {
+        match addr {
+            0x0000..=0x3FFF => rom_byte(rom, self.rom0_bank(), addr),
+            _ => rom_byte(rom, self.rom1_bank(), addr - 0x4000),
+        }
+    }
+
+    pub fn write_rom(&mut self, addr: Addr, value: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enabled = value & 0x0F == 0x0A,
+            0x2000..=0x3FFF => {
+                // Bank 0 can not be selected, the check is done on all 5 bits
+                // so 0x20, 0x40 and 0x60 are unreachable as well.
+                self.bank1 = match value & 0x1F {
+                    0 => 1,
+                    bank => bank,
+                };
+            }
+            0x4000..=0x5FFF => self.bank2 = value & 0x03,
+            _ => self.mode = value & 0x01 != 0,
+        }
+    }
+
+    pub fn read_ram(&self, ram: &[u8], offset: Addr) -> u8 {
+        if !self.ram_enabled {
+            return OPEN_BUS_VALUE;
+        }
+
+        ram_byte(ram, self.ram_bank(), offset)
+    }
+
+    pub fn write_ram(&mut self, ram: &mut [u8], offset: Addr, value: u8) {
+        if self.ram_enabled {
+            set_ram_byte(ram, self.ram_bank(), offset, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::cartridge::RAM_BANK_SIZE;
+    use crate::mbc::test::numbered_rom;
+
+    #[test]
+    fn bank_0_selects_bank_1() {
+        let rom = numbered_rom(64);
+        let mut mbc = Mbc1::default();
+
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 1);
+
+        mbc.write_rom(0x2000, 0x00);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 1);
+
+        mbc.write_rom(0x2000, 0x05);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 5);
+
+        // Only the lower 5 bits are kept.
+        mbc.write_rom(0x3FFF, 0xE3);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 3);
+    }
+
+    #[test]
+    fn bank2_extends_rom_bank() {
+        let rom = numbered_rom(128);
+        let mut mbc = Mbc1::default();
+
+        mbc.write_rom(0x4000, 0x02);
+        mbc.write_rom(0x2000, 0x03);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0x43);
+
+        // Banks 0x20, 0x40 and 0x60 read as the one after.
+        mbc.write_rom(0x2000, 0x00);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0x41);
+
+        // Mode 0 keeps bank 0 at the bottom.
+        assert_eq!(mbc.read_rom(&rom, 0x0000), 0x00);
+
+        mbc.write_rom(0x6000, 0x01);
+        assert_eq!(mbc.read_rom(&rom, 0x0000), 0x40);
+    }
+
+    #[test]
+    fn rom_bank_wraps_to_rom_size() {
+        let rom = numbered_rom(4);
+        let mut mbc = Mbc1::default();
+
+        mbc.write_rom(0x2000, 0x06);
+
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 2);
+    }
+
+    #[test]
+    fn ram_needs_enable() {
+        let mut ram = vec![0; RAM_BANK_SIZE];
+        let mut mbc = Mbc1::default();
+
+        mbc.write_ram(&mut ram, 0x0010, 0x42);
+        assert_eq!(mbc.read_ram(&ram, 0x0010), OPEN_BUS_VALUE);
+        assert_eq!(ram[0x10], 0);
+
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_ram(&mut ram, 0x0010, 0x42);
+        assert_eq!(mbc.read_ram(&ram, 0x0010), 0x42);
+
+        // Only the lower nibble matters.
+        mbc.write_rom(0x1FFF, 0xFA);
+        assert!(mbc.ram_enabled());
+
+        mbc.write_rom(0x0000, 0x00);
+        assert_eq!(mbc.read_ram(&ram, 0x0010), OPEN_BUS_VALUE);
+    }
+
+    #[test]
+    fn ram_banking_needs_mode_1() {
+        let mut ram = vec![0; RAM_BANK_SIZE * 4];
+        let mut mbc = Mbc1::default();
+        mbc.write_rom(0x0000, 0x0A);
+
+        mbc.write_rom(0x4000, 0x02);
+        mbc.write_ram(&mut ram, 0x0000, 0x11);
+        assert_eq!(ram[0], 0x11);
+
+        mbc.write_rom(0x6000, 0x01);
+        mbc.write_ram(&mut ram, 0x0000, 0x22);
+        assert_eq!(ram[2 * RAM_BANK_SIZE], 0x22);
+        assert_eq!(mbc.read_ram(&ram, 0x0000), 0x22);
+    }
+
+    #[test]
+    fn multicart_wiring() {
+        let mut rom = numbered_rom(64);
+        let second_game = MULTICART_GAME_BANKS * ROM_BANK_SIZE;
+        rom[second_game + LOGO_START..second_game + LOGO_END].copy_from_slice(&NINTENDO_LOGO);
+
+        let mut mbc = Mbc1::for_rom(&rom);
+        assert!(mbc.multicart());
+
+        // BANK1 bit 4 is not connected.
+        mbc.write_rom(0x2000, 0x12);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0x02);
+
+        mbc.write_rom(0x4000, 0x01);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0x12);
+
+        // 0x10 passes the zero check, so the game's bank 0 is reachable.
+        mbc.write_rom(0x2000, 0x10);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0x10);
+
+        mbc.write_rom(0x6000, 0x01);
+        assert_eq!(mbc.read_rom(&rom, 0x0000), 0x10);
+    }
+
+    #[test]
+    fn regular_1_mib_rom_is_not_multicart() {
+        let mut rom = numbered_rom(64);
+        rom[LOGO_START..LOGO_END].copy_from_slice(&NINTENDO_LOGO);
+
+        assert!(!Mbc1::for_rom(&rom).multicart());
+    }
+}
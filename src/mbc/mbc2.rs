@@ -0,0 +1,162 @@
+use crate::mmu::{Addr, OPEN_BUS_VALUE};
+
+use super::rom_byte;
+
+/// 512 half-bytes of RAM built into the controller.
+pub const RAM_SIZE: usize = 0x200;
+
+/// Address bit 8 tells a ROM bank write from a RAM enable write.
+const REGISTER_SELECT_BIT: Addr = 0x0100;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Mbc2 {
+    ram_enabled: bool,
+    /// The 4-bit ROM bank register.
+    rom_bank: u8,
+}
+
+impl Default for Mbc2 {
+    fn default() -> Self {
+        Self {
+            ram_enabled: false,
+            rom_bank: 1,
+        }
+    }
+}
+
+impl Mbc2 {
+    pub fn ram_enabled(&self) -> bool {
+        self.ram_enabled
+    }
+
+    /// Bank mapped at 0x4000-0x7FFF.
+    pub fn rom_bank(&self) -> usize {
+        usize::from(self.rom_bank)
+    }
+
+    pub fn read_rom(&self, rom: &[u8], addr: Addr) -> u8 {
+        match addr {
+            0x0000..=0x3FFF => rom_byte(rom, 0, addr),
+            _ => rom_byte(rom, self.rom_bank(), addr - 0x4000),
+        }
+    }
+
+    pub fn write_rom(&mut self, addr: Addr, value: u8) {
+        match addr {
+            0x0000..=0x3FFF if addr & REGISTER_SELECT_BIT == 0 => {
+                self.ram_enabled = value & 0x0F == 0x0A;
+            }
+            0x0000..=0x3FFF => {
+                self.rom_bank = match value & 0x0F {
+                    0 => 1,
+                    bank => bank,
+                };
+            }
+            _ => {}
+        }
+    }
+
+    /// Only 9 address lines reach the RAM, so it repeats through the whole
+    /// window, and the upper nibble is not driven.
+    pub fn read_ram(&self, ram: &[u8], offset: Addr) -> u8 {
+        if !self.ram_enabled {
+            return OPEN_BUS_VALUE;
+        }
+
+        ram.get(usize::from(offset) % RAM_SIZE)
+            .map(|nibble| nibble | 0xF0)
+            .unwrap_or(OPEN_BUS_VALUE)
+    }
+
+    pub fn write_ram(&mut self, ram: &mut [u8], offset: Addr, value: u8) {
+        if !self.ram_enabled {
+            return;
+        }
+
+        if let Some(nibble) = ram.get_mut(usize::from(offset) % RAM_SIZE) {
+            *nibble = value & 0x0F;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::mbc::test::numbered_rom;
+
+    #[test]
+    fn register_selected_by_address_bit_8() {
+        let rom = numbered_rom(16);
+        let mut mbc = Mbc2::default();
+
+        // Bit 8 clear, RAM enable.
+        mbc.write_rom(0x0000, 0x0A);
+        assert!(mbc.ram_enabled());
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 1);
+
+        // Bit 8 set, ROM bank.
+        mbc.write_rom(0x0100, 0x0A);
+        assert!(mbc.ram_enabled());
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0x0A);
+
+        mbc.write_rom(0x3EFF, 0x00);
+        assert!(!mbc.ram_enabled());
+
+        mbc.write_rom(0x3FFF, 0x07);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0x07);
+    }
+
+    #[test]
+    fn rom_bank_has_4_bits_and_skips_0() {
+        let rom = numbered_rom(16);
+        let mut mbc = Mbc2::default();
+
+        mbc.write_rom(0x2100, 0xF3);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0x03);
+
+        mbc.write_rom(0x2100, 0x10);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0x01);
+
+        assert_eq!(mbc.read_rom(&rom, 0x0000), 0x00);
+    }
+
+    #[test]
+    fn writes_above_0x4000_are_ignored() {
+        let mut mbc = Mbc2::default();
+
+        mbc.write_rom(0x4100, 0x05);
+        mbc.write_rom(0x6000, 0x0A);
+
+        assert_eq!(mbc, Mbc2::default());
+    }
+
+    #[test]
+    fn ram_holds_half_bytes() {
+        let mut ram = vec![0; RAM_SIZE];
+        let mut mbc = Mbc2::default();
+
+        mbc.write_ram(&mut ram, 0x0000, 0x42);
+        assert_eq!(mbc.read_ram(&ram, 0x0000), OPEN_BUS_VALUE);
+
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_ram(&mut ram, 0x0000, 0x42);
+        assert_eq!(ram[0], 0x02);
+        assert_eq!(mbc.read_ram(&ram, 0x0000), 0xF2);
+    }
+
+    #[test]
+    fn ram_echoes_through_window() {
+        let mut ram = vec![0; RAM_SIZE];
+        let mut mbc = Mbc2::default();
+        mbc.write_rom(0x0000, 0x0A);
+
+        mbc.write_ram(&mut ram, 0x01FF, 0x05);
+
+        assert_eq!(mbc.read_ram(&ram, 0x03FF), 0xF5);
+        assert_eq!(mbc.read_ram(&ram, 0x1FFF), 0xF5);
+
+        mbc.write_ram(&mut ram, 0x0200, 0x0C);
+        assert_eq!(mbc.read_ram(&ram, 0x0000), 0xFC);
+    }
+}
@@ -0,0 +1,479 @@
+use std::fmt;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::mmu::{Addr, OPEN_BUS_VALUE};
+
+use super::{ram_byte, rom_byte, set_ram_byte};
+
+/// Bytes of the RTC trailer appended to the save RAM.
+pub const RTC_SAVE_SIZE: usize = 48;
+
+const RTC_SECONDS: usize = 0;
+const RTC_MINUTES: usize = 1;
+const RTC_HOURS: usize = 2;
+const RTC_DAYS_LOW: usize = 3;
+const RTC_DAYS_HIGH: usize = 4;
+
+/// Bit 0 of the upper day register is bit 8 of the day counter.
+const DAYS_HIGH_DAY_BIT: u8 = 0b0000_0001;
+const DAYS_HIGH_HALT: u8 = 0b0100_0000;
+const DAYS_HIGH_CARRY: u8 = 0b1000_0000;
+
+/// Bits each RTC register really has.
+const RTC_MASKS: [u8; 5] = [0x3F, 0x3F, 0x1F, 0xFF, 0xC1];
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+const DAYS_PER_COUNTER: u64 = 512;
+
+/// Where the RTC gets the time from, in seconds since the Unix epoch.
+#[derive(Clone, Default)]
+pub enum TimeSource {
+    /// The host's wall clock.
+    #[default]
+    System,
+    Custom(Rc<dyn Fn() -> u64>),
+}
+
+impl TimeSource {
+    pub fn now(&self) -> u64 {
+        match self {
+            TimeSource::System => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs())
+                .unwrap_or(0),
+            TimeSource::Custom(now) => now(),
+        }
+    }
+}
+
+impl fmt::Debug for TimeSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TimeSource::System => write!(f, "System"),
+            TimeSource::Custom(_) => write!(f, "Custom(..)"),
+        }
+    }
+}
+
+impl PartialEq for TimeSource {
+    fn eq(&self, rhs: &Self) -> bool {
+        match (self, rhs) {
+            (TimeSource::System, TimeSource::System) => true,
+            (TimeSource::Custom(lhs), TimeSource::Custom(rhs)) => Rc::ptr_eq(lhs, rhs),
+            _ => false,
+        }
+    }
+}
+
+/// The MBC3 real-time clock, only brought up to date when the game latches
+/// or writes it.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Rtc {
+    /// Seconds, minutes, hours, lower and upper day registers.
+    clock: [u8; 5],
+    latched: [u8; 5],
+    /// A 0x00 was written to the latch register last.
+    latch_armed: bool,
+    /// When `clock` was last brought up to date.
+    last_sync: u64,
+    time_source: TimeSource,
+}
+
+impl Rtc {
+    pub fn new(time_source: TimeSource) -> Self {
+        Self {
+            clock: [0; 5],
+            latched: [0; 5],
+            latch_armed: false,
+            last_sync: time_source.now(),
+            time_source,
+        }
+    }
+
+    pub fn set_time_source(&mut self, time_source: TimeSource) {
+        self.sync();
+        self.last_sync = time_source.now();
+        self.time_source = time_source;
+    }
+
+    pub fn halted(&self) -> bool {
+        self.clock[RTC_DAYS_HIGH] & DAYS_HIGH_HALT != 0
+    }
+
+    /// Adds the time passed since the last sync, unless halted.
+    fn sync(&mut self) {
+        let now = self.time_source.now();
+
+        if !self.halted() {
+            self.advance(now.saturating_sub(self.last_sync));
+        }
+
+        self.last_sync = now;
+    }
+
+    /// Out of range register values are normalized by the first carry out
+    /// of them.
+    fn advance(&mut self, seconds: u64) {
+        if seconds == 0 {
+            return;
+        }
+
+        let days = (u64::from(self.clock[RTC_DAYS_HIGH] & DAYS_HIGH_DAY_BIT) << 8)
+            | u64::from(self.clock[RTC_DAYS_LOW]);
+        let total = seconds
+            + u64::from(self.clock[RTC_SECONDS])
+            + u64::from(self.clock[RTC_MINUTES]) * 60
+            + u64::from(self.clock[RTC_HOURS]) * 60 * 60
+            + days * SECONDS_PER_DAY;
+
+        let days = total / SECONDS_PER_DAY;
+        let mut days_high = self.clock[RTC_DAYS_HIGH] & !DAYS_HIGH_DAY_BIT;
+        if days >= DAYS_PER_COUNTER {
+            // Sticks until the game clears it.
+            days_high |= DAYS_HIGH_CARRY;
+        }
+        let days = days % DAYS_PER_COUNTER;
+
+        self.clock[RTC_SECONDS] = (total % 60) as u8;
+        self.clock[RTC_MINUTES] = (total / 60 % 60) as u8;
+        self.clock[RTC_HOURS] = (total / (60 * 60) % 24) as u8;
+        self.clock[RTC_DAYS_LOW] = days as u8;
+        self.clock[RTC_DAYS_HIGH] = days_high | (days >> 8) as u8;
+    }
+
+    /// Writes to 0x6000-0x7FFF, 0x00 followed by 0x01 latches the clock.
+    pub fn write_latch(&mut self, value: u8) {
+        if self.latch_armed && value == 0x01 {
+            self.sync();
+            self.latched = self.clock;
+        }
+
+        self.latch_armed = value == 0x00;
+    }
+
+    /// Reads the latched copy of register 0x08-0x0C.
+    pub fn read(&self, register: u8) -> u8 {
+        self.latched[usize::from(register - 0x08)]
+    }
+
+    pub fn write(&mut self, register: u8, value: u8) {
+        let index = usize::from(register - 0x08);
+
+        // Account for the time passed under the old values first.
+        self.sync();
+        self.clock[index] = value & RTC_MASKS[index];
+    }
+
+    /// The 48-byte trailer: the clock and the latched registers as 32-bit
+    /// little-endian words, then a 64-bit little-endian Unix timestamp.
+    pub fn save(&mut self) -> [u8; RTC_SAVE_SIZE] {
+        self.sync();
+
+        let mut trailer = [0; RTC_SAVE_SIZE];
+        let registers = self.clock.iter().chain(self.latched.iter());
+        for (chunk, register) in trailer.chunks_mut(4).zip(registers) {
+            chunk.copy_from_slice(&u32::from(*register).to_le_bytes());
+        }
+        trailer[40..].copy_from_slice(&self.last_sync.to_le_bytes());
+
+        trailer
+    }
+
+    /// Restores a trailer made by `save` and catches up with the time passed
+    /// since.
+    pub fn load(&mut self, trailer: &[u8; RTC_SAVE_SIZE]) {
+        for (index, chunk) in trailer[..40].chunks(4).enumerate() {
+            let register = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]) as u8;
+            let mask = RTC_MASKS[index % 5];
+
+            if index < 5 {
+                self.clock[index] = register & mask;
+            } else {
+                self.latched[index - 5] = register & mask;
+            }
+        }
+
+        let mut timestamp = [0; 8];
+        timestamp.copy_from_slice(&trailer[40..]);
+        self.last_sync = u64::from_le_bytes(timestamp);
+
+        self.sync();
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Mbc3 {
+    /// Enables both RAM and the RTC registers.
+    ram_enabled: bool,
+    /// The 7-bit ROM bank register.
+    rom_bank: u8,
+    /// 0x00-0x03 map a RAM bank, 0x08-0x0C an RTC register.
+    ram_select: u8,
+    rtc: Option<Rtc>,
+}
+
+impl Mbc3 {
+    /// An MBC3, with a clock running from `TimeSource::System` for
+    /// cartridges carrying one.
+    pub fn new(timer: bool) -> Self {
+        Self {
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_select: 0,
+            rtc: if timer {
+                Some(Rtc::new(TimeSource::System))
+            } else {
+                None
+            },
+        }
+    }
+
+    pub fn ram_enabled(&self) -> bool {
+        self.ram_enabled
+    }
+
+    /// Whether 0xA000-0xBFFF maps an RTC register instead of RAM.
+    pub fn rtc_selected(&self) -> bool {
+        self.rtc.is_some() && matches!(self.ram_select, 0x08..=0x0C)
+    }
+
+    pub fn rtc(&self) -> Option<&Rtc> {
+        self.rtc.as_ref()
+    }
+
+    pub fn rtc_mut(&mut self) -> Option<&mut Rtc> {
+        self.rtc.as_mut()
+    }
+
+    /// Bank mapped at 0x4000-0x7FFF.
+    pub fn rom_bank(&self) -> usize {
+        usize::from(self.rom_bank)
+    }
+
+    pub fn read_rom(&self, rom: &[u8], addr: Addr) -> u8 {
+        match addr {
+            0x0000..=0x3FFF => rom_byte(rom, 0, addr),
+            _ => rom_byte(rom, self.rom_bank(), addr - 0x4000),
+        }
+    }
+
+    pub fn write_rom(&mut self, addr: Addr, value: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enabled = value & 0x0F == 0x0A,
+            0x2000..=0x3FFF => {
+                self.rom_bank = match value & 0x7F {
+                    0 => 1,
+                    bank => bank,
+                };
+            }
+            0x4000..=0x5FFF => self.ram_select = value,
+            _ => {
+                if let Some(rtc) = self.rtc.as_mut() {
+                    rtc.write_latch(value);
+                }
+            }
+        }
+    }
+
+    pub fn read_ram(&self, ram: &[u8], offset: Addr) -> u8 {
+        if !self.ram_enabled {
+            return OPEN_BUS_VALUE;
+        }
+
+        match (self.ram_select, self.rtc.as_ref()) {
+            (bank @ 0x00..=0x03, _) => ram_byte(ram, usize::from(bank), offset),
+            (register @ 0x08..=0x0C, Some(rtc)) => rtc.read(register),
+            _ => OPEN_BUS_VALUE,
+        }
+    }
+
+    pub fn write_ram(&mut self, ram: &mut [u8], offset: Addr, value: u8) {
+        if !self.ram_enabled {
+            return;
+        }
+
+        match (self.ram_select, self.rtc.as_mut()) {
+            (bank @ 0x00..=0x03, _) => set_ram_byte(ram, usize::from(bank), offset, value),
+            (register @ 0x08..=0x0C, Some(rtc)) => rtc.write(register, value),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+
+    use super::*;
+
+    use crate::cartridge::RAM_BANK_SIZE;
+    use crate::mbc::test::numbered_rom;
+
+    fn fake_clock() -> (Rc<Cell<u64>>, TimeSource) {
+        let now = Rc::new(Cell::new(1_000_000));
+        let now_in_source = now.clone();
+
+        (
+            now,
+            TimeSource::Custom(Rc::new(move || now_in_source.get())),
+        )
+    }
+
+    fn mbc3_with_fake_clock() -> (Rc<Cell<u64>>, Mbc3) {
+        let (now, time_source) = fake_clock();
+
+        let mut mbc = Mbc3::new(true);
+        mbc.rtc_mut().unwrap().set_time_source(time_source);
+        mbc.write_rom(0x0000, 0x0A);
+
+        (now, mbc)
+    }
+
+    fn latch(mbc: &mut Mbc3) {
+        mbc.write_rom(0x6000, 0x00);
+        mbc.write_rom(0x6000, 0x01);
+    }
+
+    fn read_rtc(mbc: &mut Mbc3, register: u8) -> u8 {
+        mbc.write_rom(0x4000, register);
+        mbc.read_ram(&[], 0x0000)
+    }
+
+    fn write_rtc(mbc: &mut Mbc3, register: u8, value: u8) {
+        mbc.write_rom(0x4000, register);
+        mbc.write_ram(&mut [], 0x0000, value);
+    }
+
+    #[test]
+    fn rom_bank_has_7_bits() {
+        let rom = numbered_rom(128);
+        let mut mbc = Mbc3::new(false);
+
+        mbc.write_rom(0x2000, 0x7F);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0x7F);
+
+        mbc.write_rom(0x2000, 0x80);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0x01);
+
+        mbc.write_rom(0x2000, 0x20);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0x20);
+    }
+
+    #[test]
+    fn ram_banks() {
+        let mut ram = vec![0; RAM_BANK_SIZE * 4];
+        let mut mbc = Mbc3::new(false);
+        mbc.write_rom(0x0000, 0x0A);
+
+        mbc.write_rom(0x4000, 0x03);
+        mbc.write_ram(&mut ram, 0x0001, 0x42);
+
+        assert_eq!(ram[3 * RAM_BANK_SIZE + 1], 0x42);
+        assert_eq!(mbc.read_ram(&ram, 0x0001), 0x42);
+
+        // No clock on this cartridge.
+        mbc.write_rom(0x4000, 0x08);
+        assert_eq!(mbc.read_ram(&ram, 0x0001), OPEN_BUS_VALUE);
+    }
+
+    #[test]
+    fn rtc_reads_latched_value() {
+        let (now, mut mbc) = mbc3_with_fake_clock();
+
+        now.set(now.get() + 5);
+        assert_eq!(read_rtc(&mut mbc, 0x08), 0);
+
+        latch(&mut mbc);
+        assert_eq!(read_rtc(&mut mbc, 0x08), 5);
+
+        // The latched value holds while time goes on.
+        now.set(now.get() + 60 * 60 + 2 * 60);
+        assert_eq!(read_rtc(&mut mbc, 0x08), 5);
+
+        latch(&mut mbc);
+        assert_eq!(read_rtc(&mut mbc, 0x08), 5);
+        assert_eq!(read_rtc(&mut mbc, 0x09), 2);
+        assert_eq!(read_rtc(&mut mbc, 0x0A), 1);
+    }
+
+    #[test]
+    fn latch_needs_0_then_1() {
+        let (now, mut mbc) = mbc3_with_fake_clock();
+        now.set(now.get() + 7);
+
+        mbc.write_rom(0x6000, 0x01);
+        assert_eq!(read_rtc(&mut mbc, 0x08), 0);
+
+        mbc.write_rom(0x6000, 0x00);
+        mbc.write_rom(0x6000, 0x02);
+        mbc.write_rom(0x6000, 0x01);
+        assert_eq!(read_rtc(&mut mbc, 0x08), 0);
+
+        latch(&mut mbc);
+        assert_eq!(read_rtc(&mut mbc, 0x08), 7);
+    }
+
+    #[test]
+    fn halt_stops_the_clock() {
+        let (now, mut mbc) = mbc3_with_fake_clock();
+
+        write_rtc(&mut mbc, 0x0C, DAYS_HIGH_HALT);
+        now.set(now.get() + 100);
+        latch(&mut mbc);
+        assert_eq!(read_rtc(&mut mbc, 0x08), 0);
+
+        write_rtc(&mut mbc, 0x0C, 0);
+        now.set(now.get() + 3);
+        latch(&mut mbc);
+        assert_eq!(read_rtc(&mut mbc, 0x08), 3);
+    }
+
+    #[test]
+    fn day_counter_carries() {
+        let (now, mut mbc) = mbc3_with_fake_clock();
+
+        write_rtc(&mut mbc, 0x0B, 0xFF);
+        write_rtc(&mut mbc, 0x0C, DAYS_HIGH_DAY_BIT);
+        write_rtc(&mut mbc, 0x0A, 23);
+        write_rtc(&mut mbc, 0x09, 59);
+        write_rtc(&mut mbc, 0x08, 59);
+
+        now.set(now.get() + 1);
+        latch(&mut mbc);
+
+        assert_eq!(read_rtc(&mut mbc, 0x0B), 0);
+        assert_eq!(read_rtc(&mut mbc, 0x0C), DAYS_HIGH_CARRY);
+
+        // The carry stays until written.
+        now.set(now.get() + SECONDS_PER_DAY);
+        latch(&mut mbc);
+        assert_eq!(read_rtc(&mut mbc, 0x0B), 1);
+        assert_eq!(read_rtc(&mut mbc, 0x0C), DAYS_HIGH_CARRY);
+
+        write_rtc(&mut mbc, 0x0C, 0);
+        latch(&mut mbc);
+        assert_eq!(read_rtc(&mut mbc, 0x0C), 0);
+    }
+
+    #[test]
+    fn save_and_load_rtc() {
+        let (now, mut mbc) = mbc3_with_fake_clock();
+        write_rtc(&mut mbc, 0x0A, 5);
+        latch(&mut mbc);
+
+        let trailer = mbc.rtc_mut().unwrap().save();
+        assert_eq!(&trailer[8..12], &[5, 0, 0, 0]);
+        assert_eq!(&trailer[28..32], &[5, 0, 0, 0]);
+        assert_eq!(&trailer[40..], &now.get().to_le_bytes());
+
+        // Powered off for 90 seconds.
+        now.set(now.get() + 90);
+        let mut rtc = Rtc::new(mbc.rtc().unwrap().time_source.clone());
+        rtc.load(&trailer);
+
+        assert_eq!(rtc.latched[RTC_HOURS], 5);
+        assert_eq!(rtc.clock[RTC_MINUTES], 1);
+        assert_eq!(rtc.clock[RTC_SECONDS], 30);
+    }
+}
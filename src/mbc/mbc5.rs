@@ -0,0 +1,175 @@
+use crate::mmu::{Addr, OPEN_BUS_VALUE};
+
+use super::{ram_byte, rom_byte, set_ram_byte};
+
+/// On rumble cartridges bit 3 of the RAM bank register drives the motor.
+const RUMBLE_MOTOR_BIT: u8 = 0b0000_1000;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Mbc5 {
+    ram_enabled: bool,
+    /// The 9-bit ROM bank register, bank 0 included.
+    rom_bank: u16,
+    /// The 4-bit RAM bank register.
+    ram_bank: u8,
+    rumble: bool,
+    motor_on: bool,
+}
+
+impl Mbc5 {
+    pub fn new(rumble: bool) -> Self {
+        Self {
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            rumble,
+            motor_on: false,
+        }
+    }
+
+    pub fn ram_enabled(&self) -> bool {
+        self.ram_enabled
+    }
+
+    pub fn rumble(&self) -> bool {
+        self.rumble
+    }
+
+    /// Whether the rumble motor is spinning right now.
+    pub fn motor_on(&self) -> bool {
+        self.motor_on
+    }
+
+    /// Bank mapped at 0x4000-0x7FFF.
+    pub fn rom_bank(&self) -> usize {
+        usize::from(self.rom_bank)
+    }
+
+    pub fn ram_bank(&self) -> usize {
+        usize::from(self.ram_bank)
+    }
+
+    pub fn read_rom(&self, rom: &[u8], addr: Addr) -> u8 {
+        match addr {
+            0x0000..=0x3FFF => rom_byte(rom, 0, addr),
+            _ => rom_byte(rom, self.rom_bank(), addr - 0x4000),
+        }
+    }
+
+    pub fn write_rom(&mut self, addr: Addr, value: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enabled = value & 0x0F == 0x0A,
+            0x2000..=0x2FFF => self.rom_bank = (self.rom_bank & 0x100) | u16::from(value),
+            0x3000..=0x3FFF => {
+                self.rom_bank = (u16::from(value & 0x01) << 8) | (self.rom_bank & 0xFF);
+            }
+            0x4000..=0x5FFF if self.rumble => {
+                self.motor_on = value & RUMBLE_MOTOR_BIT != 0;
+                self.ram_bank = value & 0x07;
+            }
+            0x4000..=0x5FFF => self.ram_bank = value & 0x0F,
+            _ => {}
+        }
+    }
+
+    pub fn read_ram(&self, ram: &[u8], offset: Addr) -> u8 {
+        if !self.ram_enabled {
+            return OPEN_BUS_VALUE;
+        }
+
+        ram_byte(ram, self.ram_bank(), offset)
+    }
+
+    pub fn write_ram(&mut self, ram: &mut [u8], offset: Addr, value: u8) {
+        if self.ram_enabled {
+            set_ram_byte(ram, self.ram_bank(), offset, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::cartridge::RAM_BANK_SIZE;
+    use crate::mbc::test::numbered_rom_u16;
+
+    fn read_rom_bank(mbc: &Mbc5, rom: &[u8]) -> u16 {
+        u16::from_le_bytes([mbc.read_rom(rom, 0x4000), mbc.read_rom(rom, 0x4001)])
+    }
+
+    #[test]
+    fn nine_bit_rom_bank() {
+        let rom = numbered_rom_u16(512);
+        let mut mbc = Mbc5::new(false);
+
+        mbc.write_rom(0x2000, 0xFF);
+        assert_eq!(read_rom_bank(&mbc, &rom), 0x0FF);
+
+        mbc.write_rom(0x3000, 0x01);
+        assert_eq!(read_rom_bank(&mbc, &rom), 0x1FF);
+
+        // Only bit 0 of the upper register is used.
+        mbc.write_rom(0x3FFF, 0xFE);
+        assert_eq!(read_rom_bank(&mbc, &rom), 0x0FF);
+
+        mbc.write_rom(0x3000, 0x01);
+        mbc.write_rom(0x2FFF, 0x23);
+        assert_eq!(read_rom_bank(&mbc, &rom), 0x123);
+    }
+
+    #[test]
+    fn bank_0_is_selectable() {
+        let rom = numbered_rom_u16(4);
+        let mut mbc = Mbc5::new(false);
+
+        mbc.write_rom(0x2000, 0x00);
+
+        assert_eq!(read_rom_bank(&mbc, &rom), 0);
+    }
+
+    #[test]
+    fn sixteen_ram_banks() {
+        let mut ram = vec![0; RAM_BANK_SIZE * 16];
+        let mut mbc = Mbc5::new(false);
+
+        mbc.write_rom(0x4000, 0x0F);
+        mbc.write_ram(&mut ram, 0x0000, 0x42);
+        assert_eq!(ram[15 * RAM_BANK_SIZE], 0);
+
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_ram(&mut ram, 0x0000, 0x42);
+        assert_eq!(ram[15 * RAM_BANK_SIZE], 0x42);
+        assert_eq!(mbc.read_ram(&ram, 0x0000), 0x42);
+
+        mbc.write_rom(0x4000, 0x00);
+        assert_eq!(mbc.read_ram(&ram, 0x0000), 0x00);
+    }
+
+    #[test]
+    fn rumble_motor() {
+        let mut ram = vec![0; RAM_BANK_SIZE * 8];
+        let mut mbc = Mbc5::new(true);
+        mbc.write_rom(0x0000, 0x0A);
+
+        mbc.write_rom(0x4000, 0x0B);
+        assert!(mbc.motor_on());
+        assert_eq!(mbc.ram_bank(), 3);
+
+        mbc.write_ram(&mut ram, 0x0000, 0x42);
+        assert_eq!(ram[3 * RAM_BANK_SIZE], 0x42);
+
+        mbc.write_rom(0x4000, 0x03);
+        assert!(!mbc.motor_on());
+    }
+
+    #[test]
+    fn no_motor_without_rumble() {
+        let mut mbc = Mbc5::new(false);
+
+        mbc.write_rom(0x4000, 0x08);
+
+        assert!(!mbc.motor_on());
+        assert_eq!(mbc.ram_bank(), 8);
+    }
+}
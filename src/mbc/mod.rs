@@ -0,0 +1,185 @@
+//! Cartridge bank controllers, mapping the CPU's 0x0000-0x7FFF and
+//! 0xA000-0xBFFF windows onto the ROM image and the external RAM.
+
+pub mod mbc1;
+pub mod mbc2;
+pub mod mbc3;
+pub mod mbc5;
+
+use crate::cartridge::{self, Cartridge, RAM_BANK_SIZE, ROM_BANK_SIZE};
+use crate::mmu::{Addr, OPEN_BUS_VALUE};
+
+use self::mbc1::Mbc1;
+use self::mbc2::Mbc2;
+use self::mbc3::{Mbc3, Rtc};
+use self::mbc5::Mbc5;
+
+/// The bank controller of the inserted cartridge together with its registers.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub enum Controller {
+    /// No controller, 32 KiB of ROM and up to 8 KiB of RAM always mapped.
+    #[default]
+    None,
+    Mbc1(Mbc1),
+    Mbc2(Mbc2),
+    Mbc3(Mbc3),
+    Mbc5(Mbc5),
+}
+
+impl Controller {
+    /// The controller the header asks for, failing for kinds not emulated
+    /// yet rather than running them with the wrong banking.
+    pub fn for_cartridge(cartridge: &Cartridge) -> cartridge::Result<Controller> {
+        let cartridge_type = cartridge.header().cartridge_type;
+
+        Ok(match cartridge_type.mbc {
+            cartridge::Mbc::None => Controller::None,
+            cartridge::Mbc::Mbc1 => Controller::Mbc1(Mbc1::for_rom(cartridge.rom())),
+            cartridge::Mbc::Mbc2 => Controller::Mbc2(Mbc2::default()),
+            cartridge::Mbc::Mbc3 => Controller::Mbc3(Mbc3::new(cartridge_type.timer)),
+            cartridge::Mbc::Mbc5 => Controller::Mbc5(Mbc5::new(cartridge_type.rumble)),
+            mbc => return Err(cartridge::Error::UnsupportedMbc(mbc)),
+        })
+    }
+
+    /// Bytes of external RAM the cartridge carries, including RAM built into
+    /// the controller.
+    pub fn ram_size(cartridge: &Cartridge) -> usize {
+        match cartridge.header().cartridge_type.mbc {
+            cartridge::Mbc::Mbc2 => mbc2::RAM_SIZE,
+            _ => cartridge.header().ram_size().unwrap_or(0),
+        }
+    }
+
+    /// Whether the CPU can reach external RAM, or the RTC, right now.
+    pub fn ram_enabled(&self) -> bool {
+        match self {
+            Controller::None => true,
+            Controller::Mbc1(mbc) => mbc.ram_enabled(),
+            Controller::Mbc2(mbc) => mbc.ram_enabled(),
+            Controller::Mbc3(mbc) => mbc.ram_enabled(),
+            Controller::Mbc5(mbc) => mbc.ram_enabled(),
+        }
+    }
+
+    /// Whether 0xA000-0xBFFF maps an RTC register instead of RAM.
+    pub fn rtc_selected(&self) -> bool {
+        match self {
+            Controller::Mbc3(mbc) => mbc.rtc_selected(),
+            _ => false,
+        }
+    }
+
+    pub fn rtc_mut(&mut self) -> Option<&mut Rtc> {
+        match self {
+            Controller::Mbc3(mbc) => mbc.rtc_mut(),
+            _ => None,
+        }
+    }
+
+    /// Reads 0x0000-0x7FFF.
+    pub fn read_rom(&self, rom: &[u8], addr: Addr) -> u8 {
+        match self {
+            Controller::None => rom
+                .get(usize::from(addr))
+                .cloned()
+                .unwrap_or(OPEN_BUS_VALUE),
+            Controller::Mbc1(mbc) => mbc.read_rom(rom, addr),
+            Controller::Mbc2(mbc) => mbc.read_rom(rom, addr),
+            Controller::Mbc3(mbc) => mbc.read_rom(rom, addr),
+            Controller::Mbc5(mbc) => mbc.read_rom(rom, addr),
+        }
+    }
+
+    /// Writes to 0x0000-0x7FFF, which set controller registers.
+    pub fn write_rom(&mut self, addr: Addr, value: u8) {
+        match self {
+            Controller::None => {}
+            Controller::Mbc1(mbc) => mbc.write_rom(addr, value),
+            Controller::Mbc2(mbc) => mbc.write_rom(addr, value),
+            Controller::Mbc3(mbc) => mbc.write_rom(addr, value),
+            Controller::Mbc5(mbc) => mbc.write_rom(addr, value),
+        }
+    }
+
+    /// Reads external RAM, `offset` is relative to 0xA000.
+    pub fn read_ram(&self, ram: &[u8], offset: Addr) -> u8 {
+        match self {
+            Controller::None => ram_byte(ram, 0, offset),
+            Controller::Mbc1(mbc) => mbc.read_ram(ram, offset),
+            Controller::Mbc2(mbc) => mbc.read_ram(ram, offset),
+            Controller::Mbc3(mbc) => mbc.read_ram(ram, offset),
+            Controller::Mbc5(mbc) => mbc.read_ram(ram, offset),
+        }
+    }
+
+    /// Writes external RAM, `offset` is relative to 0xA000.
+    pub fn write_ram(&mut self, ram: &mut [u8], offset: Addr, value: u8) {
+        match self {
+            Controller::None => set_ram_byte(ram, 0, offset, value),
+            Controller::Mbc1(mbc) => mbc.write_ram(ram, offset, value),
+            Controller::Mbc2(mbc) => mbc.write_ram(ram, offset, value),
+            Controller::Mbc3(mbc) => mbc.write_ram(ram, offset, value),
+            Controller::Mbc5(mbc) => mbc.write_ram(ram, offset, value),
+        }
+    }
+}
+
+/// The byte at `offset` of ROM bank `bank`, bank numbers beyond the image
+/// wrap around as the unconnected address lines are ignored.
+pub(crate) fn rom_byte(rom: &[u8], bank: usize, offset: Addr) -> u8 {
+    let banks = (rom.len() / ROM_BANK_SIZE).max(1);
+    let index = (bank % banks) * ROM_BANK_SIZE + usize::from(offset);
+
+    rom.get(index).cloned().unwrap_or(OPEN_BUS_VALUE)
+}
+
+fn ram_index(ram: &[u8], bank: usize, offset: Addr) -> Option<usize> {
+    if ram.is_empty() {
+        return None;
+    }
+
+    Some((bank * RAM_BANK_SIZE + usize::from(offset)) % ram.len())
+}
+
+/// The byte at `offset` of RAM bank `bank`, RAM smaller than the window
+/// repeats through it.
+pub(crate) fn ram_byte(ram: &[u8], bank: usize, offset: Addr) -> u8 {
+    ram_index(ram, bank, offset)
+        .map(|index| ram[index])
+        .unwrap_or(OPEN_BUS_VALUE)
+}
+
+pub(crate) fn set_ram_byte(ram: &mut [u8], bank: usize, offset: Addr, value: u8) {
+    if let Some(index) = ram_index(ram, bank, offset) {
+        ram[index] = value;
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use crate::cartridge::ROM_BANK_SIZE;
+
+    /// A ROM whose every bank starts with its own number.
+    pub(crate) fn numbered_rom(banks: usize) -> Vec<u8> {
+        let mut rom = vec![0; banks * ROM_BANK_SIZE];
+        for bank in 0..banks {
+            rom[bank * ROM_BANK_SIZE] = bank as u8;
+        }
+
+        rom
+    }
+
+    /// A ROM whose every bank starts with its own number as a little-endian
+    /// u16, for controllers with more than 256 banks.
+    pub(crate) fn numbered_rom_u16(banks: usize) -> Vec<u8> {
+        let mut rom = vec![0; banks * ROM_BANK_SIZE];
+        for bank in 0..banks {
+            let [low, high] = (bank as u16).to_le_bytes();
+            rom[bank * ROM_BANK_SIZE] = low;
+            rom[bank * ROM_BANK_SIZE + 1] = high;
+        }
+
+        rom
+    }
+}
@@ -1,4 +1,14 @@
-use std::fmt;
+use crate::apu::Apu;
+use crate::cartridge::{self, Cartridge};
+use crate::dma::{OamDma, DMA_ADDR};
+use crate::interrupt::{Interrupt, IE_ADDR, IF_ADDR};
+use crate::joypad::{ButtonState, Joypad};
+use crate::mbc::mbc3::RTC_SAVE_SIZE;
+use crate::mbc::Controller;
+use crate::ppu::{Ppu, Renderer};
+use crate::save;
+use crate::serial::Serial;
+use crate::timer::Timer;
 
 #[derive(Debug, PartialEq)]
 pub enum Error {
@@ -8,92 +18,532 @@ pub enum Error {
 pub type Result<T> = std::result::Result<T, Error>;
 pub type Addr = u16;
 
-pub const MEM_SIZE: u16 = 0xFFFF;
+pub const MEM_SIZE: usize = 0x1_0000;
 pub const INVALID_READ_DEFAULT_VALUE: u8 = 0;
 
+/// What the CPU sees when nothing drives the data bus.
+pub const OPEN_BUS_VALUE: u8 = 0xFF;
+
+/// RTC trailer written by emulators keeping a 32-bit timestamp.
+const LEGACY_RTC_SAVE_SIZE: usize = RTC_SAVE_SIZE - 4;
+
 pub const INVALID_MEM_ACCESS_EXPECT: &str = "Invalid address access";
 
-#[derive(Clone)]
+pub const ROM_START: Addr = 0x0000;
+pub const VRAM_START: Addr = 0x8000;
+pub const EXTERNAL_RAM_START: Addr = 0xA000;
+pub const WRAM_START: Addr = 0xC000;
+pub const ECHO_RAM_START: Addr = 0xE000;
+pub const OAM_START: Addr = 0xFE00;
+pub const UNUSABLE_START: Addr = 0xFEA0;
+pub const IO_START: Addr = 0xFF00;
+pub const HRAM_START: Addr = 0xFF80;
+
+pub const ROM_SIZE: usize = 0x8000;
+pub const VRAM_SIZE: usize = 0x2000;
+pub const EXTERNAL_RAM_SIZE: usize = 0x2000;
+pub const WRAM_SIZE: usize = 0x2000;
+pub const OAM_SIZE: usize = 0xA0;
+pub const IO_SIZE: usize = 0x80;
+pub const HRAM_SIZE: usize = 0x7F;
+
+/// The component an address is routed to.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Region {
+    /// 0x0000-0x7FFF, cartridge ROM, writes go to the cartridge controller.
+    Rom,
+    /// 0x8000-0x9FFF.
+    Vram,
+    /// 0xA000-0xBFFF, cartridge RAM.
+    ExternalRam,
+    /// 0xC000-0xDFFF.
+    Wram,
+    /// 0xE000-0xFDFF, mirrors 0xC000-0xDDFF.
+    EchoRam,
+    /// 0xFE00-0xFE9F.
+    Oam,
+    /// 0xFEA0-0xFEFF, reads 0x00 and ignores writes.
+    Unusable,
+    /// 0xFF00-0xFF7F.
+    Io,
+    /// 0xFF80-0xFFFE.
+    Hram,
+    /// 0xFFFF.
+    Ie,
+}
+
+impl Region {
+    pub fn of(addr: Addr) -> Region {
+        match addr {
+            0x0000..=0x7FFF => Region::Rom,
+            0x8000..=0x9FFF => Region::Vram,
+            0xA000..=0xBFFF => Region::ExternalRam,
+            0xC000..=0xDFFF => Region::Wram,
+            0xE000..=0xFDFF => Region::EchoRam,
+            0xFE00..=0xFE9F => Region::Oam,
+            0xFEA0..=0xFEFF => Region::Unusable,
+            0xFF00..=0xFF7F => Region::Io,
+            0xFF80..=0xFFFE => Region::Hram,
+            0xFFFF => Region::Ie,
+        }
+    }
+}
+
+/// Bits of an I/O register which are not backed by anything and always read
+/// as 1, unmapped registers read back as `OPEN_BUS_VALUE`.
+fn io_unused_bits(addr: Addr) -> u8 {
+    match addr {
+        // P1, SB, SC.
+        0xFF00 => 0xC0,
+        0xFF01 => 0x00,
+        0xFF02 => 0x7E,
+        // DIV, TIMA, TMA, TAC.
+        0xFF04 => 0x00,
+        0xFF05 => 0x00,
+        0xFF06 => 0x00,
+        0xFF07 => 0xF8,
+        // IF.
+        0xFF0F => 0xE0,
+        // NR10-NR52.
+        0xFF10 => 0x80,
+        0xFF11 => 0x3F,
+        0xFF12 => 0x00,
+        0xFF13 => 0xFF,
+        0xFF14 => 0xBF,
+        0xFF16 => 0x3F,
+        0xFF17 => 0x00,
+        0xFF18 => 0xFF,
+        0xFF19 => 0xBF,
+        0xFF1A => 0x7F,
+        0xFF1B => 0xFF,
+        0xFF1C => 0x9F,
+        0xFF1D => 0xFF,
+        0xFF1E => 0xBF,
+        0xFF20 => 0xFF,
+        0xFF21 => 0x00,
+        0xFF22 => 0x00,
+        0xFF23 => 0xBF,
+        0xFF24 => 0x00,
+        0xFF25 => 0x00,
+        0xFF26 => 0x70,
+        // Wave RAM.
+        0xFF30..=0xFF3F => 0x00,
+        // LCDC, STAT, SCY, SCX, LY, LYC, DMA, BGP, OBP0, OBP1, WY, WX.
+        0xFF40 => 0x00,
+        0xFF41 => 0x80,
+        0xFF42..=0xFF4B => 0x00,
+        // KEY1, only bit 7 and bit 0 exist.
+        0xFF4D => 0x7E,
+        _ => 0xFF,
+    }
+}
+
+/// Bits of an I/O register the CPU can not change by writing to it.
+fn io_read_only_bits(addr: Addr) -> u8 {
+    match addr {
+        // KEY1 current speed.
+        0xFF4D => 0x80,
+        _ => 0x00,
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
 pub struct Mmu {
-    memory: [u8; MEM_SIZE as usize],
+    rom: Vec<u8>,
+    external_ram: Vec<u8>,
+    controller: Controller,
+    /// The cartridge keeps its RAM powered by a battery.
+    battery: bool,
+    /// External RAM changed since the last export.
+    save_dirty: bool,
+    ppu: Ppu,
+    /// The OAM DMA in progress, if any.
+    dma: Option<OamDma>,
+    timer: Timer,
+    apu: Apu,
+    joypad: Joypad,
+    serial: Serial,
+    wram: [u8; WRAM_SIZE],
+    io: [u8; IO_SIZE],
+    hram: [u8; HRAM_SIZE],
+    ie: u8,
 }
 
 impl Default for Mmu {
     fn default() -> Self {
         Self {
-            memory: [0; MEM_SIZE as usize],
+            rom: Vec::new(),
+            external_ram: Vec::new(),
+            controller: Controller::None,
+            battery: false,
+            save_dirty: false,
+            ppu: Ppu::default(),
+            dma: None,
+            timer: Timer::default(),
+            apu: Apu::default(),
+            joypad: Joypad::default(),
+            serial: Serial::default(),
+            wram: [0; WRAM_SIZE],
+            io: [0; IO_SIZE],
+            hram: [0; HRAM_SIZE],
+            ie: 0,
         }
     }
 }
 
-impl fmt::Debug for Mmu {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        f.debug_struct("Mmu")
-            .field("memory", &self.memory.to_vec())
-            .finish()
+impl Mmu {
+    /// A memory map with `rom` inserted as a cartridge without a bank
+    /// controller, ROM beyond 32 KiB is not reachable.
+    pub fn with_rom(rom: Vec<u8>) -> Self {
+        Self {
+            rom,
+            ..Self::default()
+        }
     }
-}
 
-impl PartialEq for Mmu {
-    fn eq(&self, rhs: &Self) -> bool {
-        self.memory[..] == rhs.memory[..]
+    /// A memory map with `cartridge` inserted behind its bank controller,
+    /// failing if that controller is not emulated.
+    pub fn with_cartridge(cartridge: Cartridge) -> cartridge::Result<Self> {
+        let ram_size = Controller::ram_size(&cartridge);
+        let controller = Controller::for_cartridge(&cartridge)?;
+        let battery = cartridge.header().cartridge_type.battery;
+
+        Ok(Self {
+            rom: cartridge.into_rom(),
+            external_ram: vec![0; ram_size],
+            controller,
+            battery,
+            ..Self::default()
+        })
     }
-}
 
-impl Mmu {
+    /// Draws with `renderer` instead of the default scanline renderer, as in
+    /// `Mmu::with_cartridge(cartridge)?.with_renderer(Renderer::PixelFifo)`.
+    pub fn with_renderer(self, renderer: Renderer) -> Self {
+        Self {
+            ppu: Ppu::new(renderer),
+            ..self
+        }
+    }
+
+    /// The bank controller of the inserted cartridge, e.g. to give an MBC3
+    /// clock its time source.
+    pub fn controller(&self) -> &Controller {
+        &self.controller
+    }
+
+    pub fn controller_mut(&mut self) -> &mut Controller {
+        &mut self.controller
+    }
+
+    /// Whether the cartridge keeps its RAM, and clock, across power offs.
+    pub fn has_battery(&self) -> bool {
+        self.battery
+    }
+
+    /// Whether battery-backed RAM changed since the last import or export.
+    pub fn save_dirty(&self) -> bool {
+        self.save_dirty
+    }
+
+    /// The `.sav` contents, external RAM followed by the RTC trailer on
+    /// cartridges with a clock.
+    pub fn export_save(&mut self) -> Vec<u8> {
+        let mut save = self.external_ram.clone();
+        if let Some(rtc) = self.controller.rtc_mut() {
+            save.extend_from_slice(&rtc.save());
+        }
+
+        self.save_dirty = false;
+
+        save
+    }
+
+    /// Restores a `.sav`, the RTC trailer is optional and may carry a 32-bit
+    /// timestamp instead of a 64-bit one.
+    pub fn import_save(&mut self, save: &[u8]) -> save::Result<()> {
+        if !self.battery {
+            return Err(save::Error::NoBattery);
+        }
+
+        let ram_size = self.external_ram.len();
+        let has_rtc = self.controller.rtc_mut().is_some();
+        let mismatch = save::Error::SizeMismatch {
+            expected: if has_rtc {
+                ram_size + RTC_SAVE_SIZE
+            } else {
+                ram_size
+            },
+            actual: save.len(),
+        };
+
+        if save.len() < ram_size {
+            return Err(mismatch);
+        }
+        let (ram, trailer) = save.split_at(ram_size);
+
+        let trailer_fits = match trailer.len() {
+            0 => true,
+            RTC_SAVE_SIZE | LEGACY_RTC_SAVE_SIZE => has_rtc,
+            _ => false,
+        };
+        if !trailer_fits {
+            return Err(mismatch);
+        }
+
+        self.external_ram.copy_from_slice(ram);
+        if let Some(rtc) = self.controller.rtc_mut() {
+            if !trailer.is_empty() {
+                // The upper half of a 32-bit timestamp is zero.
+                let mut full = [0; RTC_SAVE_SIZE];
+                full[..trailer.len()].copy_from_slice(trailer);
+                rtc.load(&full);
+            }
+        }
+
+        self.save_dirty = false;
+
+        Ok(())
+    }
+
+    /// Reads as the CPU does, which only reaches HRAM and the registers
+    /// while an OAM DMA runs.
     #[inline]
     pub fn read_byte(&self, addr: Addr) -> u8 {
-        let addr = addr as usize;
+        if self.dma_blocks(addr) {
+            return OPEN_BUS_VALUE;
+        }
 
-        self.memory
-            .get(addr)
-            .cloned()
-            .unwrap_or(INVALID_READ_DEFAULT_VALUE)
+        self.read_bus(addr)
     }
 
+    fn read_bus(&self, addr: Addr) -> u8 {
+        match Region::of(addr) {
+            Region::Rom => self.controller.read_rom(&self.rom, addr - ROM_START),
+            Region::Vram if self.ppu.vram_locked() => OPEN_BUS_VALUE,
+            Region::Vram => self.ppu.read_vram(addr - VRAM_START),
+            Region::ExternalRam => self
+                .controller
+                .read_ram(&self.external_ram, addr - EXTERNAL_RAM_START),
+            Region::Wram => self.wram[usize::from(addr - WRAM_START)],
+            Region::EchoRam => self.wram[usize::from(addr - ECHO_RAM_START)],
+            Region::Oam if self.ppu.oam_locked() => OPEN_BUS_VALUE,
+            Region::Oam => self.ppu.read_oam(addr - OAM_START),
+            Region::Unusable => 0x00,
+            Region::Io if Ppu::is_register(addr) => self.ppu.read_register(addr),
+            Region::Io if Timer::is_register(addr) => self.timer.read_register(addr),
+            Region::Io if Apu::is_register(addr) => self.apu.read_register(addr),
+            Region::Io if Joypad::is_register(addr) => self.joypad.read_register(addr),
+            Region::Io if Serial::is_register(addr) => self.serial.read_register(addr),
+            Region::Io => self.io[usize::from(addr - IO_START)] | io_unused_bits(addr),
+            Region::Hram => self.hram[usize::from(addr - HRAM_START)],
+            Region::Ie => self.ie,
+        }
+    }
+
+    /// Reads a little-endian word, the high byte of 0xFFFF wraps to 0x0000.
     #[inline]
     pub fn read_word(&self, addr: Addr) -> u16 {
-        let addr = addr as usize;
-
-        let h_addr = addr;
-        let l_addr = addr.saturating_add(1);
-
-        let h = u16::from(
-            *self
-                .memory
-                .get(h_addr)
-                .unwrap_or(&INVALID_READ_DEFAULT_VALUE),
-        );
-        let l = u16::from(
-            *self
-                .memory
-                .get(l_addr)
-                .unwrap_or(&INVALID_READ_DEFAULT_VALUE),
-        );
+        let l = u16::from(self.read_byte(addr));
+        let h = u16::from(self.read_byte(addr.wrapping_add(1)));
 
         (h << 8) + l
     }
 
     #[inline]
     pub fn write_byte(&mut self, addr: Addr, value: u8) -> Result<()> {
-        let addr = addr as usize;
+        if self.dma_blocks(addr) {
+            return Ok(());
+        }
 
-        let byte = self.memory.get_mut(addr).ok_or(Error::OutOfBound)?;
-        *byte = value;
+        match Region::of(addr) {
+            Region::Rom => self.controller.write_rom(addr - ROM_START, value),
+            Region::Vram if self.ppu.vram_locked() => {}
+            Region::Vram => self.ppu.write_vram(addr - VRAM_START, value),
+            Region::ExternalRam => {
+                let offset = addr - EXTERNAL_RAM_START;
+                let before = self.controller.read_ram(&self.external_ram, offset);
+                self.controller
+                    .write_ram(&mut self.external_ram, offset, value);
+
+                // RTC registers read back their latched copy, so a write to
+                // one always counts.
+                let changed = self.controller.rtc_selected()
+                    || self.controller.read_ram(&self.external_ram, offset) != before;
+                self.save_dirty |= self.battery && self.controller.ram_enabled() && changed;
+            }
+            Region::Wram => self.wram[usize::from(addr - WRAM_START)] = value,
+            Region::EchoRam => self.wram[usize::from(addr - ECHO_RAM_START)] = value,
+            Region::Oam if self.ppu.oam_locked() => {}
+            Region::Oam => self.ppu.write_oam(addr - OAM_START, value),
+            Region::Unusable => {}
+            Region::Io => self.write_io(addr, value),
+            Region::Hram => self.hram[usize::from(addr - HRAM_START)] = value,
+            Region::Ie => self.ie = value,
+        }
 
         Ok(())
     }
 
+    /// Writes a little-endian word, the high byte of 0xFFFF wraps to 0x0000.
     #[inline]
     pub fn write_word(&mut self, addr: Addr, value: u16) -> Result<()> {
-        let addr = addr as usize;
+        let [l, h] = value.to_le_bytes();
+
+        self.write_byte(addr, l)?;
+        self.write_byte(addr.wrapping_add(1), h)
+    }
 
-        self.memory.get(addr + 1).ok_or(Error::OutOfBound)?;
+    fn write_io(&mut self, addr: Addr, value: u8) {
+        let index = usize::from(addr - IO_START);
 
-        self.memory[addr] = ((value & 0xff00) >> 8) as u8;
-        self.memory[addr + 1] = (value & 0x00ff) as u8;
+        if Ppu::is_register(addr) {
+            let interrupts = self.ppu.write_register(addr, value);
+            self.io[usize::from(IF_ADDR - IO_START)] |= interrupts;
+            return;
+        }
 
-        Ok(())
+        if Timer::is_register(addr) {
+            self.timer.write_register(addr, value);
+            return;
+        }
+
+        if Apu::is_register(addr) {
+            self.apu.write_register(addr, value);
+            return;
+        }
+
+        if Joypad::is_register(addr) {
+            let interrupts = self.joypad.write_register(addr, value);
+            self.io[usize::from(IF_ADDR - IO_START)] |= interrupts;
+            return;
+        }
+
+        if Serial::is_register(addr) {
+            self.serial.write_register(addr, value);
+            return;
+        }
+
+        if addr == DMA_ADDR {
+            // A new transfer replaces one in progress.
+            self.dma = Some(OamDma::new(value));
+        }
+
+        let read_only = io_read_only_bits(addr);
+        self.io[index] = (self.io[index] & read_only) | (value & !read_only);
+    }
+
+    /// Sets an I/O register regardless of which bits the CPU may write, for
+    /// the hardware owning it (e.g. LY or the STAT mode).
+    pub fn set_io(&mut self, addr: Addr, value: u8) {
+        self.io[usize::from(addr - IO_START)] = value;
+    }
+
+    pub fn ppu(&self) -> &Ppu {
+        &self.ppu
+    }
+
+    pub fn ppu_mut(&mut self) -> &mut Ppu {
+        &mut self.ppu
+    }
+
+    pub fn timer(&self) -> &Timer {
+        &self.timer
+    }
+
+    pub fn timer_mut(&mut self) -> &mut Timer {
+        &mut self.timer
+    }
+
+    pub fn apu(&self) -> &Apu {
+        &self.apu
+    }
+
+    pub fn apu_mut(&mut self) -> &mut Apu {
+        &mut self.apu
+    }
+
+    pub fn joypad(&self) -> &Joypad {
+        &self.joypad
+    }
+
+    pub fn joypad_mut(&mut self) -> &mut Joypad {
+        &mut self.joypad
+    }
+
+    /// Updates the buttons held, raising the joypad interrupt when one of
+    /// the selected lines goes low.
+    pub fn set_buttons(&mut self, buttons: ButtonState) {
+        let interrupts = self.joypad.set_buttons(buttons);
+        self.io[usize::from(IF_ADDR - IO_START)] |= interrupts;
+    }
+
+    pub fn serial(&self) -> &Serial {
+        &self.serial
+    }
+
+    pub fn serial_mut(&mut self) -> &mut Serial {
+        &mut self.serial
+    }
+
+    pub fn dma_active(&self) -> bool {
+        self.dma.is_some()
+    }
+
+    /// The CPU loses the bus to the DMA below the I/O registers, leaving it
+    /// HRAM to run from.
+    fn dma_blocks(&self, addr: Addr) -> bool {
+        self.dma.is_some() && addr < IO_START
+    }
+
+    /// Advances the hardware clocked alongside the CPU by the `cycles` an
+    /// instruction took, raising the interrupts it produced in IF.
+    pub fn tick(&mut self, cycles: u32, double_speed: bool) {
+        self.tick_dma(cycles);
+
+        // The PPU and APU keep their pace when the CPU runs at double speed.
+        let dots = if double_speed { cycles / 2 } else { cycles };
+        self.apu.tick(dots);
+
+        let interrupts = self.timer.tick(cycles) | self.serial.tick(cycles) | self.ppu.tick(dots);
+        self.io[usize::from(IF_ADDR - IO_START)] |= interrupts;
+    }
+
+    fn tick_dma(&mut self, cycles: u32) {
+        let mut dma = match self.dma {
+            Some(dma) => dma,
+            None => return,
+        };
+
+        for offset in dma.advance(cycles) {
+            // The DMA writes OAM whatever mode the PPU is in.
+            let value = self.read_bus(dma.source() + offset);
+            self.ppu.write_oam(offset, value);
+        }
+
+        self.dma = if dma.finished() { None } else { Some(dma) };
+    }
+
+    /// Raises `interrupt` in IF, the CPU services it once IE and IME allow.
+    pub fn request_interrupt(&mut self, interrupt: Interrupt) {
+        let flags = self.read_byte(IF_ADDR);
+
+        self.write_byte(IF_ADDR, flags | interrupt.bit())
+            .expect(INVALID_MEM_ACCESS_EXPECT);
+    }
+
+    pub fn clear_interrupt(&mut self, interrupt: Interrupt) {
+        let flags = self.read_byte(IF_ADDR);
+
+        self.write_byte(IF_ADDR, flags & !interrupt.bit())
+            .expect(INVALID_MEM_ACCESS_EXPECT);
+    }
+
+    /// The highest priority interrupt both requested and enabled.
+    pub fn pending_interrupt(&self) -> Option<Interrupt> {
+        let pending = self.read_byte(IE_ADDR) & self.read_byte(IF_ADDR);
+
+        Interrupt::highest_priority(pending)
     }
 }
 
@@ -103,7 +553,7 @@ mod test {
 
     #[test]
     fn read_byte_with_correct_address() {
-        const ADDR: Addr = 0x42;
+        const ADDR: Addr = 0xC042;
         const VAL: u8 = 0x99;
 
         const EXPECTED_VAL: u8 = VAL;
@@ -116,9 +566,7 @@ mod test {
 
     #[test]
     fn read_byte_with_max_minus_one_address() {
-        use std::u16::MAX;
-
-        const ADDR: Addr = MAX - 1;
+        const ADDR: Addr = u16::MAX - 1;
         const VAL: u8 = 0x99;
 
         const EXPECTED_VAL: u8 = VAL;
@@ -131,21 +579,20 @@ mod test {
 
     #[test]
     fn read_byte_with_max_address() {
-        use std::u16::MAX;
-
-        const ADDR: Addr = MAX;
+        const ADDR: Addr = u16::MAX;
         const VAL: u8 = 0x99;
 
         const EXPECTED_VAL: u8 = VAL;
 
         let mut mmu = Mmu::default();
-        assert!(mmu.write_byte(ADDR, VAL).is_err());
-        assert_eq!(mmu.read_byte(ADDR), INVALID_READ_DEFAULT_VALUE);
+        mmu.write_byte(ADDR, VAL).unwrap();
+
+        assert_eq!(mmu.read_byte(ADDR), VAL);
     }
 
     #[test]
     fn read_word_with_correct_address() {
-        const ADDR: Addr = 0x42;
+        const ADDR: Addr = 0xC042;
         const VAL: u16 = 0x99;
 
         const EXPECTED_VAL: u16 = VAL;
@@ -158,12 +605,10 @@ mod test {
 
     #[test]
     fn read_word_with_max_minus_one_address() {
-        use std::u16::MAX;
-
-        const ADDR: Addr = MAX - 1;
+        const ADDR: Addr = u16::MAX - 1;
         const VAL: u8 = 0x99;
 
-        const EXPECTED_VAL: u16 = (VAL as u16) << 8;
+        const EXPECTED_VAL: u16 = VAL as u16;
 
         let mut mmu = Mmu::default();
         mmu.write_byte(ADDR, VAL).unwrap();
@@ -173,15 +618,16 @@ mod test {
 
     #[test]
     fn read_word_with_max_address() {
-        use std::u16::MAX;
-
-        const ADDR: Addr = MAX;
+        const ADDR: Addr = u16::MAX;
         const VAL: u8 = 0x99;
 
-        const EXPECTED_VAL: u16 = INVALID_READ_DEFAULT_VALUE as u16;
+        const HIGH_VAL: u8 = 0x42;
+
+        const EXPECTED_VAL: u16 = ((HIGH_VAL as u16) << 8) + VAL as u16;
+
+        let mut mmu = Mmu::with_rom(vec![HIGH_VAL]);
+        mmu.write_byte(ADDR, VAL).unwrap();
 
-        let mut mmu = Mmu::default();
-        assert!(mmu.write_byte(ADDR, VAL).is_err());
         assert_eq!(mmu.read_word(ADDR), EXPECTED_VAL);
     }
 
@@ -196,51 +642,448 @@ mod test {
             assert_eq!(mmu.read_byte(addr), value);
         };
 
-        test(0x00);
-        test(0x42);
-        test(MEM_SIZE - 1);
+        test(0xC000);
+        test(0xC042);
+        test((MEM_SIZE - 1) as Addr);
     }
 
     #[test]
-    fn write_byte_with_incorrect_address() {
+    fn write_word_with_correct_address() {
         let test = |addr| {
             let mut mmu = Mmu::default();
 
-            let value = 42;
+            let value = 1024;
 
-            assert_eq!(mmu.write_byte(addr, value), Err(Error::OutOfBound));
+            assert!(mmu.write_word(addr, value).is_ok());
+            assert_eq!(mmu.read_word(addr), value);
         };
 
-        test(MEM_SIZE);
+        test(0xC000);
+        test(0xC042);
+        test((MEM_SIZE - 2) as Addr);
     }
 
     #[test]
-    fn write_word_with_correct_address() {
-        let test = |addr| {
-            let mut mmu = Mmu::default();
+    fn write_word_is_little_endian() {
+        let mut mmu = Mmu::default();
 
-            let value = 1024;
+        mmu.write_word(0xC042, 0x1234).unwrap();
 
-            assert!(mmu.write_word(addr, value).is_ok());
-            assert_eq!(mmu.read_word(addr), value);
-        };
+        assert_eq!(mmu.read_byte(0xC042), 0x34);
+        assert_eq!(mmu.read_byte(0xC043), 0x12);
+    }
+
+    #[test]
+    fn write_word_with_max_address_wraps() {
+        let mut mmu = Mmu::with_rom(vec![0xAB]);
 
-        test(0x00);
-        test(0x42);
-        test(MEM_SIZE - 2);
+        assert!(mmu.write_word(u16::MAX, 0x1234).is_ok());
+
+        // The high byte lands on ROM, which ignores it.
+        assert_eq!(mmu.read_byte(u16::MAX), 0x34);
+        assert_eq!(mmu.read_byte(0x0000), 0xAB);
+        assert_eq!(mmu.read_word(u16::MAX), 0xAB34);
     }
 
     #[test]
-    fn write_word_with_incorrect_address() {
-        let test = |addr| {
-            let mut mmu = Mmu::default();
+    fn region_of_boundaries() {
+        assert_eq!(Region::of(0x0000), Region::Rom);
+        assert_eq!(Region::of(0x7FFF), Region::Rom);
+        assert_eq!(Region::of(0x8000), Region::Vram);
+        assert_eq!(Region::of(0x9FFF), Region::Vram);
+        assert_eq!(Region::of(0xA000), Region::ExternalRam);
+        assert_eq!(Region::of(0xBFFF), Region::ExternalRam);
+        assert_eq!(Region::of(0xC000), Region::Wram);
+        assert_eq!(Region::of(0xDFFF), Region::Wram);
+        assert_eq!(Region::of(0xE000), Region::EchoRam);
+        assert_eq!(Region::of(0xFDFF), Region::EchoRam);
+        assert_eq!(Region::of(0xFE00), Region::Oam);
+        assert_eq!(Region::of(0xFE9F), Region::Oam);
+        assert_eq!(Region::of(0xFEA0), Region::Unusable);
+        assert_eq!(Region::of(0xFEFF), Region::Unusable);
+        assert_eq!(Region::of(0xFF00), Region::Io);
+        assert_eq!(Region::of(0xFF7F), Region::Io);
+        assert_eq!(Region::of(0xFF80), Region::Hram);
+        assert_eq!(Region::of(0xFFFE), Region::Hram);
+        assert_eq!(Region::of(0xFFFF), Region::Ie);
+    }
 
-            let value = 42;
+    #[test]
+    fn rom_ignores_writes() {
+        let mut mmu = Mmu::with_rom(vec![0x11, 0x22]);
+
+        mmu.write_byte(0x0001, 0x99).unwrap();
+
+        assert_eq!(mmu.read_byte(0x0000), 0x11);
+        assert_eq!(mmu.read_byte(0x0001), 0x22);
+    }
+
+    #[test]
+    fn cartridge_is_mapped() {
+        use crate::cartridge::test::rom_image;
+
+        let rom = rom_image(0x08, 0x00, 0x02);
+        let mut mmu = Mmu::with_cartridge(Cartridge::from_bytes(&rom).unwrap()).unwrap();
+
+        assert_eq!(mmu.read_byte(0x0134), b'G');
+
+        mmu.write_byte(0xA042, 0x42).unwrap();
+        assert_eq!(mmu.read_byte(0xA042), 0x42);
+    }
+
+    #[test]
+    fn mbc1_cartridge_switches_banks() {
+        use crate::cartridge::test::{fix_checksums, rom_image};
+        use crate::cartridge::ROM_BANK_SIZE;
+
+        let mut rom = rom_image(0x03, 0x02, 0x03);
+        rom[3 * ROM_BANK_SIZE] = 0x33;
+        fix_checksums(&mut rom);
+        let mut mmu = Mmu::with_cartridge(Cartridge::from_bytes(&rom).unwrap()).unwrap();
+
+        mmu.write_byte(0x2000, 0x03).unwrap();
+        assert_eq!(mmu.read_byte(0x4000), 0x33);
+
+        // RAM is disabled until 0x0A is written to 0x0000-0x1FFF.
+        mmu.write_byte(0xA000, 0x42).unwrap();
+        assert_eq!(mmu.read_byte(0xA000), OPEN_BUS_VALUE);
+
+        mmu.write_byte(0x0000, 0x0A).unwrap();
+        mmu.write_byte(0xA000, 0x42).unwrap();
+        assert_eq!(mmu.read_byte(0xA000), 0x42);
+    }
+
+    #[test]
+    fn mbc2_cartridge_has_built_in_ram() {
+        use crate::cartridge::test::rom_image;
+
+        let rom = rom_image(0x06, 0x01, 0x00);
+        let mut mmu = Mmu::with_cartridge(Cartridge::from_bytes(&rom).unwrap()).unwrap();
+
+        mmu.write_byte(0x0000, 0x0A).unwrap();
+        mmu.write_byte(0xA1FF, 0x3C).unwrap();
+
+        assert_eq!(mmu.read_byte(0xA1FF), 0xFC);
+        assert_eq!(mmu.read_byte(0xBFFF), 0xFC);
+    }
+
+    #[test]
+    fn mbc3_cartridge_has_clock() {
+        use std::rc::Rc;
+
+        use crate::cartridge::test::rom_image;
+        use crate::mbc::mbc3::TimeSource;
+
+        let rom = rom_image(0x10, 0x01, 0x03);
+        let mut mmu = Mmu::with_cartridge(Cartridge::from_bytes(&rom).unwrap()).unwrap();
+
+        match mmu.controller_mut() {
+            Controller::Mbc3(mbc) => mbc
+                .rtc_mut()
+                .unwrap()
+                .set_time_source(TimeSource::Custom(Rc::new(|| 0))),
+            controller => panic!("unexpected controller {:?}", controller),
+        }
+
+        mmu.write_byte(0x0000, 0x0A).unwrap();
+        mmu.write_byte(0x4000, 0x0A).unwrap();
+        mmu.write_byte(0xA000, 0x17).unwrap();
+        mmu.write_byte(0x6000, 0x00).unwrap();
+        mmu.write_byte(0x6000, 0x01).unwrap();
+
+        assert_eq!(mmu.read_byte(0xA000), 0x17);
+    }
+
+    #[test]
+    fn mbc5_rumble_is_observable() {
+        use crate::cartridge::test::rom_image;
+
+        let rom = rom_image(0x1C, 0x01, 0x00);
+        let mut mmu = Mmu::with_cartridge(Cartridge::from_bytes(&rom).unwrap()).unwrap();
+
+        mmu.write_byte(0x4000, 0x08).unwrap();
+
+        match mmu.controller() {
+            Controller::Mbc5(mbc) => assert!(mbc.motor_on()),
+            controller => panic!("unexpected controller {:?}", controller),
+        }
+    }
+
+    #[test]
+    fn unsupported_controller_is_an_error() {
+        use crate::cartridge::test::rom_image;
+        use crate::cartridge::{Error, Mbc};
+
+        let rom = rom_image(0xFF, 0x01, 0x02);
+        let result = Mmu::with_cartridge(Cartridge::from_bytes(&rom).unwrap());
+
+        assert!(matches!(result, Err(Error::UnsupportedMbc(Mbc::HuC1))));
+    }
+
+    #[test]
+    fn missing_cartridge_reads_open_bus() {
+        let mut mmu = Mmu::default();
+
+        mmu.write_byte(0xA000, 0x42).unwrap();
+
+        assert_eq!(mmu.read_byte(0x0100), OPEN_BUS_VALUE);
+        assert_eq!(mmu.read_byte(0xA000), OPEN_BUS_VALUE);
+    }
+
+    #[test]
+    fn echo_ram_mirrors_wram() {
+        let mut mmu = Mmu::default();
+
+        mmu.write_byte(0xC123, 0x42).unwrap();
+        assert_eq!(mmu.read_byte(0xE123), 0x42);
+
+        mmu.write_byte(0xFDFF, 0x24).unwrap();
+        assert_eq!(mmu.read_byte(0xDDFF), 0x24);
+    }
+
+    #[test]
+    fn unusable_region_reads_zero() {
+        let mut mmu = Mmu::default();
+
+        mmu.write_byte(0xFEA0, 0x42).unwrap();
+
+        assert_eq!(mmu.read_byte(0xFEA0), 0x00);
+        assert_eq!(mmu.read_byte(0xFEFF), 0x00);
+    }
+
+    #[test]
+    fn io_unused_bits_read_as_one() {
+        let mut mmu = Mmu::default();
+
+        // TAC only has 3 bits.
+        mmu.write_byte(0xFF07, 0x00).unwrap();
+        assert_eq!(mmu.read_byte(0xFF07), 0xF8);
+
+        // Unmapped registers read open bus whatever was written.
+        mmu.write_byte(0xFF03, 0x00).unwrap();
+        assert_eq!(mmu.read_byte(0xFF03), OPEN_BUS_VALUE);
+
+        // Wave RAM is fully backed.
+        mmu.write_byte(0xFF30, 0x5A).unwrap();
+        assert_eq!(mmu.read_byte(0xFF30), 0x5A);
+    }
+
+    #[test]
+    fn div_write_resets_it() {
+        use crate::timer::DIV_ADDR;
+
+        let mut mmu = Mmu::default();
+        mmu.tick(0x4200, false);
+        assert_eq!(mmu.read_byte(DIV_ADDR), 0x42);
+
+        mmu.write_byte(DIV_ADDR, 0x99).unwrap();
+
+        assert_eq!(mmu.read_byte(DIV_ADDR), 0x00);
+    }
 
-            assert_eq!(mmu.write_word(addr, value), Err(Error::OutOfBound));
+    #[test]
+    fn read_only_io_bits_ignore_writes() {
+        use crate::cpu::KEY1_ADDR;
+
+        let mut mmu = Mmu::default();
+        mmu.set_io(KEY1_ADDR, 0b1000_0000);
+
+        mmu.write_byte(KEY1_ADDR, 0b0000_0001).unwrap();
+
+        assert_eq!(mmu.read_byte(KEY1_ADDR), 0b1111_1111);
+
+        mmu.set_io(KEY1_ADDR, 0b0000_0000);
+        mmu.write_byte(KEY1_ADDR, 0b1000_0000).unwrap();
+
+        assert_eq!(mmu.read_byte(KEY1_ADDR), 0b0111_1110);
+    }
+
+    #[test]
+    fn lcd_registers_go_to_the_ppu() {
+        use crate::ppu::{LCDC_ADDR, LCDC_LCD_ENABLE, LY_ADDR};
+
+        let mut mmu = Mmu::default();
+        mmu.write_byte(VRAM_START, 0x42).unwrap();
+        mmu.write_byte(OAM_START, 0x24).unwrap();
+        mmu.write_byte(LCDC_ADDR, LCDC_LCD_ENABLE).unwrap();
+
+        mmu.tick(456 * 144, false);
+
+        assert_eq!(mmu.ppu().read_vram(0), 0x42);
+        assert_eq!(mmu.ppu().read_oam(0), 0x24);
+        assert_eq!(mmu.read_byte(LY_ADDR), 144);
+        assert_eq!(mmu.pending_interrupt(), None);
+        assert_ne!(mmu.read_byte(IF_ADDR) & Interrupt::VBlank.bit(), 0);
+    }
+
+    #[test]
+    fn renderer_chosen_at_construction() {
+        let mmu = Mmu::with_rom(vec![0; ROM_SIZE]).with_renderer(Renderer::PixelFifo);
+
+        assert_eq!(mmu.ppu().renderer(), Renderer::PixelFifo);
+        assert_eq!(Mmu::default().ppu().renderer(), Renderer::Scanline);
+    }
+
+    #[test]
+    fn vram_and_oam_locked_while_drawing() {
+        use crate::ppu::{LCDC_ADDR, LCDC_LCD_ENABLE};
+
+        let mut mmu = Mmu::default();
+        mmu.write_byte(VRAM_START, 0x42).unwrap();
+        mmu.write_byte(OAM_START, 0x24).unwrap();
+        mmu.write_byte(LCDC_ADDR, LCDC_LCD_ENABLE).unwrap();
+
+        // OAM scan.
+        assert_eq!(mmu.read_byte(VRAM_START), 0x42);
+        assert_eq!(mmu.read_byte(OAM_START), OPEN_BUS_VALUE);
+        mmu.write_byte(OAM_START, 0x99).unwrap();
+
+        // Drawing.
+        mmu.tick(80, false);
+        assert_eq!(mmu.read_byte(VRAM_START), OPEN_BUS_VALUE);
+        mmu.write_byte(VRAM_START, 0x99).unwrap();
+
+        // HBlank.
+        mmu.tick(172, false);
+        assert_eq!(mmu.read_byte(VRAM_START), 0x42);
+        assert_eq!(mmu.read_byte(OAM_START), 0x24);
+    }
+
+    #[test]
+    fn oam_dma_copies_over_640_cycles() {
+        let mut mmu = Mmu::default();
+        for offset in 0..OAM_SIZE as Addr {
+            mmu.write_byte(0xC100 + offset, offset as u8 ^ 0x5A)
+                .unwrap();
+        }
+
+        mmu.write_byte(DMA_ADDR, 0xC1).unwrap();
+        assert!(mmu.dma_active());
+        assert_eq!(mmu.read_byte(DMA_ADDR), 0xC1);
+
+        mmu.tick(636, false);
+        assert!(mmu.dma_active());
+        assert_eq!(mmu.ppu().read_oam(0x9E), 0x9E ^ 0x5A);
+        assert_eq!(mmu.ppu().read_oam(0x9F), 0x00);
+
+        mmu.tick(4, false);
+        assert!(!mmu.dma_active());
+        for offset in 0..OAM_SIZE as Addr {
+            assert_eq!(mmu.read_byte(OAM_START + offset), offset as u8 ^ 0x5A);
+        }
+    }
+
+    #[test]
+    fn oam_dma_leaves_cpu_hram() {
+        let mut mmu = Mmu::default();
+        mmu.write_byte(0xC000, 0x42).unwrap();
+        mmu.write_byte(DMA_ADDR, 0xC0).unwrap();
+
+        assert_eq!(mmu.read_byte(0xC000), OPEN_BUS_VALUE);
+        mmu.write_byte(0xC001, 0x99).unwrap();
+        mmu.write_byte(HRAM_START, 0x24).unwrap();
+        assert_eq!(mmu.read_byte(HRAM_START), 0x24);
+
+        mmu.tick(640, false);
+        assert_eq!(mmu.read_byte(0xC000), 0x42);
+        assert_eq!(mmu.read_byte(0xC001), 0x00);
+    }
+
+    #[test]
+    fn timer_overflow_raises_interrupt() {
+        use crate::timer::{TAC_ADDR, TAC_ENABLE, TIMA_ADDR};
+
+        let mut mmu = Mmu::default();
+        mmu.write_byte(TIMA_ADDR, 0xFF).unwrap();
+        mmu.write_byte(TAC_ADDR, TAC_ENABLE | 0b01).unwrap();
+
+        mmu.tick(16 + 4, true);
+
+        assert_ne!(mmu.read_byte(IF_ADDR) & Interrupt::Timer.bit(), 0);
+    }
+
+    #[test]
+    fn double_speed_halves_ppu_dots() {
+        use crate::ppu::{LCDC_ADDR, LCDC_LCD_ENABLE, LY_ADDR};
+
+        let mut mmu = Mmu::default();
+        mmu.write_byte(LCDC_ADDR, LCDC_LCD_ENABLE).unwrap();
+
+        mmu.tick(456 * 2, true);
+
+        assert_eq!(mmu.read_byte(LY_ADDR), 1);
+    }
+
+    #[test]
+    fn joypad_press_raises_interrupt() {
+        use crate::joypad::{P1_ADDR, P1_SELECT_BUTTONS};
+
+        let mut mmu = Mmu::default();
+        mmu.write_byte(P1_ADDR, P1_SELECT_BUTTONS).unwrap();
+        assert_eq!(mmu.read_byte(P1_ADDR), 0xEF);
+
+        mmu.set_buttons(ButtonState {
+            left: true,
+            ..ButtonState::default()
+        });
+
+        assert_eq!(mmu.read_byte(P1_ADDR), 0xED);
+        assert_eq!(
+            mmu.read_byte(IF_ADDR) & Interrupt::Joypad.bit(),
+            Interrupt::Joypad.bit()
+        );
+    }
+
+    #[test]
+    fn serial_transfer_raises_interrupt() {
+        use crate::serial::{
+            Link, SerialCapture, INTERNAL_CLOCK_PERIOD, SB_ADDR, SC_ADDR, SC_INTERNAL_CLOCK,
+            SC_TRANSFER,
         };
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let capture = Rc::new(RefCell::new(SerialCapture::default()));
+        let mut mmu = Mmu::default();
+        mmu.serial_mut().set_link(Link::Endpoint(capture.clone()));
+
+        mmu.write_byte(SB_ADDR, b'!').unwrap();
+        mmu.write_byte(SC_ADDR, SC_TRANSFER | SC_INTERNAL_CLOCK)
+            .unwrap();
+        mmu.tick(8 * INTERNAL_CLOCK_PERIOD, false);
+
+        assert_eq!(capture.borrow().output(), "!");
+        assert_eq!(mmu.read_byte(SC_ADDR), 0x7F);
+        assert_eq!(
+            mmu.read_byte(IF_ADDR) & Interrupt::Serial.bit(),
+            Interrupt::Serial.bit()
+        );
+    }
+
+    #[test]
+    fn request_and_clear_interrupt() {
+        let mut mmu = Mmu::default();
+
+        mmu.request_interrupt(Interrupt::Timer);
+        mmu.request_interrupt(Interrupt::Joypad);
+        assert_eq!(mmu.read_byte(IF_ADDR), 0b1111_0100);
+
+        mmu.clear_interrupt(Interrupt::Timer);
+        assert_eq!(mmu.read_byte(IF_ADDR), 0b1111_0000);
+    }
+
+    #[test]
+    fn pending_interrupt_needs_enable() {
+        let mut mmu = Mmu::default();
+
+        mmu.request_interrupt(Interrupt::VBlank);
+        mmu.request_interrupt(Interrupt::Serial);
+        assert_eq!(mmu.pending_interrupt(), None);
+
+        mmu.write_byte(IE_ADDR, Interrupt::Serial.bit()).unwrap();
+        assert_eq!(mmu.pending_interrupt(), Some(Interrupt::Serial));
 
-        test(MEM_SIZE - 1);
-        test(MEM_SIZE);
+        mmu.write_byte(IE_ADDR, 0xFF).unwrap();
+        assert_eq!(mmu.pending_interrupt(), Some(Interrupt::VBlank));
     }
 }
@@ -1,16 +1,31 @@
+use crate::carry_test::CarryTestResult;
 use crate::cpu::Cpu;
 use crate::opcode::types::{LoadWordFromRegFn, StoreWordToRegFn};
 
 pub fn inc_d16(cpu: &mut Cpu, load_from_reg: &LoadWordFromRegFn, store_to_reg: &StoreWordToRegFn) {
     let value = load_from_reg(&cpu.registers);
 
-    // TODO Handle overflow.
-    store_to_reg(&mut cpu.registers, value + 1);
+    store_to_reg(&mut cpu.registers, value.wrapping_add(1));
 }
 
 pub fn dec_d16(cpu: &mut Cpu, load_from_reg: &LoadWordFromRegFn, store_to_reg: &StoreWordToRegFn) {
     let value = load_from_reg(&cpu.registers);
 
-    // TODO Handle underflow.
-    store_to_reg(&mut cpu.registers, value - 1);
+    store_to_reg(&mut cpu.registers, value.wrapping_sub(1));
+}
+
+/// SP plus a signed 8 bits offset, shared by "ADD SP,r8" and "LD HL,SP+r8".
+///
+/// Both carries are taken from the unsigned addition of the lower byte of SP
+/// and the raw offset, no matter which sign the offset has.
+pub fn sp_add_r8(sp: u16, raw_r8: u8) -> CarryTestResult<u16> {
+    let signed_r8 = i8::from_ne_bytes([raw_r8]);
+
+    let low_sp = (sp & 0x00FF) as u8;
+
+    CarryTestResult {
+        val: sp.wrapping_add(signed_r8 as u16),
+        half_carry: (low_sp & 0x0F) + (raw_r8 & 0x0F) > 0x0F,
+        carry: low_sp.checked_add(raw_r8).is_none(),
+    }
 }
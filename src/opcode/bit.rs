@@ -0,0 +1,359 @@
+use crate::cpu::Cpu;
+use crate::opcode::table::{Cycle, OpLength};
+use crate::opcode::types::InstructionResult;
+
+macro_rules! cb {
+    (bit => $fn_name:ident, $bit:expr, hl) => {
+        pub fn $fn_name(cpu: &mut Cpu) -> InstructionResult {
+            bit(cpu, $bit, cpu.read_hl_dref());
+
+            (Cycle(12), OpLength(1))
+        }
+    };
+
+    (bit => $fn_name:ident, $bit:expr, $getter:ident, $setter:ident) => {
+        pub fn $fn_name(cpu: &mut Cpu) -> InstructionResult {
+            bit(cpu, $bit, cpu.registers.$getter());
+
+            (Cycle(8), OpLength(1))
+        }
+    };
+
+    ($op:ident => $fn_name:ident, $bit:expr, hl) => {
+        pub fn $fn_name(cpu: &mut Cpu) -> InstructionResult {
+            let hl = cpu.registers.hl();
+            let new_value = $op($bit, cpu.read_hl_dref());
+
+            cpu.mmu.write_byte(hl, new_value).unwrap();
+
+            (Cycle(16), OpLength(1))
+        }
+    };
+
+    ($op:ident => $fn_name:ident, $bit:expr, $getter:ident, $setter:ident) => {
+        pub fn $fn_name(cpu: &mut Cpu) -> InstructionResult {
+            let new_value = $op($bit, cpu.registers.$getter());
+
+            cpu.registers.$setter(new_value);
+
+            (Cycle(8), OpLength(1))
+        }
+    };
+}
+
+cb!(bit => cb_bit_0_a, 0, a, set_a);
+cb!(bit => cb_bit_0_b, 0, b, set_b);
+cb!(bit => cb_bit_0_c, 0, c, set_c);
+cb!(bit => cb_bit_0_d, 0, d, set_d);
+cb!(bit => cb_bit_0_e, 0, e, set_e);
+cb!(bit => cb_bit_0_h, 0, h, set_h);
+cb!(bit => cb_bit_0_l, 0, l, set_l);
+cb!(bit => cb_bit_0_hl_dref, 0, hl);
+
+cb!(bit => cb_bit_1_a, 1, a, set_a);
+cb!(bit => cb_bit_1_b, 1, b, set_b);
+cb!(bit => cb_bit_1_c, 1, c, set_c);
+cb!(bit => cb_bit_1_d, 1, d, set_d);
+cb!(bit => cb_bit_1_e, 1, e, set_e);
+cb!(bit => cb_bit_1_h, 1, h, set_h);
+cb!(bit => cb_bit_1_l, 1, l, set_l);
+cb!(bit => cb_bit_1_hl_dref, 1, hl);
+
+cb!(bit => cb_bit_2_a, 2, a, set_a);
+cb!(bit => cb_bit_2_b, 2, b, set_b);
+cb!(bit => cb_bit_2_c, 2, c, set_c);
+cb!(bit => cb_bit_2_d, 2, d, set_d);
+cb!(bit => cb_bit_2_e, 2, e, set_e);
+cb!(bit => cb_bit_2_h, 2, h, set_h);
+cb!(bit => cb_bit_2_l, 2, l, set_l);
+cb!(bit => cb_bit_2_hl_dref, 2, hl);
+
+cb!(bit => cb_bit_3_a, 3, a, set_a);
+cb!(bit => cb_bit_3_b, 3, b, set_b);
+cb!(bit => cb_bit_3_c, 3, c, set_c);
+cb!(bit => cb_bit_3_d, 3, d, set_d);
+cb!(bit => cb_bit_3_e, 3, e, set_e);
+cb!(bit => cb_bit_3_h, 3, h, set_h);
+cb!(bit => cb_bit_3_l, 3, l, set_l);
+cb!(bit => cb_bit_3_hl_dref, 3, hl);
+
+cb!(bit => cb_bit_4_a, 4, a, set_a);
+cb!(bit => cb_bit_4_b, 4, b, set_b);
+cb!(bit => cb_bit_4_c, 4, c, set_c);
+cb!(bit => cb_bit_4_d, 4, d, set_d);
+cb!(bit => cb_bit_4_e, 4, e, set_e);
+cb!(bit => cb_bit_4_h, 4, h, set_h);
+cb!(bit => cb_bit_4_l, 4, l, set_l);
+cb!(bit => cb_bit_4_hl_dref, 4, hl);
+
+cb!(bit => cb_bit_5_a, 5, a, set_a);
+cb!(bit => cb_bit_5_b, 5, b, set_b);
+cb!(bit => cb_bit_5_c, 5, c, set_c);
+cb!(bit => cb_bit_5_d, 5, d, set_d);
+cb!(bit => cb_bit_5_e, 5, e, set_e);
+cb!(bit => cb_bit_5_h, 5, h, set_h);
+cb!(bit => cb_bit_5_l, 5, l, set_l);
+cb!(bit => cb_bit_5_hl_dref, 5, hl);
+
+cb!(bit => cb_bit_6_a, 6, a, set_a);
+cb!(bit => cb_bit_6_b, 6, b, set_b);
+cb!(bit => cb_bit_6_c, 6, c, set_c);
+cb!(bit => cb_bit_6_d, 6, d, set_d);
+cb!(bit => cb_bit_6_e, 6, e, set_e);
+cb!(bit => cb_bit_6_h, 6, h, set_h);
+cb!(bit => cb_bit_6_l, 6, l, set_l);
+cb!(bit => cb_bit_6_hl_dref, 6, hl);
+
+cb!(bit => cb_bit_7_a, 7, a, set_a);
+cb!(bit => cb_bit_7_b, 7, b, set_b);
+cb!(bit => cb_bit_7_c, 7, c, set_c);
+cb!(bit => cb_bit_7_d, 7, d, set_d);
+cb!(bit => cb_bit_7_e, 7, e, set_e);
+cb!(bit => cb_bit_7_h, 7, h, set_h);
+cb!(bit => cb_bit_7_l, 7, l, set_l);
+cb!(bit => cb_bit_7_hl_dref, 7, hl);
+
+cb!(res => cb_res_0_a, 0, a, set_a);
+cb!(res => cb_res_0_b, 0, b, set_b);
+cb!(res => cb_res_0_c, 0, c, set_c);
+cb!(res => cb_res_0_d, 0, d, set_d);
+cb!(res => cb_res_0_e, 0, e, set_e);
+cb!(res => cb_res_0_h, 0, h, set_h);
+cb!(res => cb_res_0_l, 0, l, set_l);
+cb!(res => cb_res_0_hl_dref, 0, hl);
+
+cb!(res => cb_res_1_a, 1, a, set_a);
+cb!(res => cb_res_1_b, 1, b, set_b);
+cb!(res => cb_res_1_c, 1, c, set_c);
+cb!(res => cb_res_1_d, 1, d, set_d);
+cb!(res => cb_res_1_e, 1, e, set_e);
+cb!(res => cb_res_1_h, 1, h, set_h);
+cb!(res => cb_res_1_l, 1, l, set_l);
+cb!(res => cb_res_1_hl_dref, 1, hl);
+
+cb!(res => cb_res_2_a, 2, a, set_a);
+cb!(res => cb_res_2_b, 2, b, set_b);
+cb!(res => cb_res_2_c, 2, c, set_c);
+cb!(res => cb_res_2_d, 2, d, set_d);
+cb!(res => cb_res_2_e, 2, e, set_e);
+cb!(res => cb_res_2_h, 2, h, set_h);
+cb!(res => cb_res_2_l, 2, l, set_l);
+cb!(res => cb_res_2_hl_dref, 2, hl);
+
+cb!(res => cb_res_3_a, 3, a, set_a);
+cb!(res => cb_res_3_b, 3, b, set_b);
+cb!(res => cb_res_3_c, 3, c, set_c);
+cb!(res => cb_res_3_d, 3, d, set_d);
+cb!(res => cb_res_3_e, 3, e, set_e);
+cb!(res => cb_res_3_h, 3, h, set_h);
+cb!(res => cb_res_3_l, 3, l, set_l);
+cb!(res => cb_res_3_hl_dref, 3, hl);
+
+cb!(res => cb_res_4_a, 4, a, set_a);
+cb!(res => cb_res_4_b, 4, b, set_b);
+cb!(res => cb_res_4_c, 4, c, set_c);
+cb!(res => cb_res_4_d, 4, d, set_d);
+cb!(res => cb_res_4_e, 4, e, set_e);
+cb!(res => cb_res_4_h, 4, h, set_h);
+cb!(res => cb_res_4_l, 4, l, set_l);
+cb!(res => cb_res_4_hl_dref, 4, hl);
+
+cb!(res => cb_res_5_a, 5, a, set_a);
+cb!(res => cb_res_5_b, 5, b, set_b);
+cb!(res => cb_res_5_c, 5, c, set_c);
+cb!(res => cb_res_5_d, 5, d, set_d);
+cb!(res => cb_res_5_e, 5, e, set_e);
+cb!(res => cb_res_5_h, 5, h, set_h);
+cb!(res => cb_res_5_l, 5, l, set_l);
+cb!(res => cb_res_5_hl_dref, 5, hl);
+
+cb!(res => cb_res_6_a, 6, a, set_a);
+cb!(res => cb_res_6_b, 6, b, set_b);
+cb!(res => cb_res_6_c, 6, c, set_c);
+cb!(res => cb_res_6_d, 6, d, set_d);
+cb!(res => cb_res_6_e, 6, e, set_e);
+cb!(res => cb_res_6_h, 6, h, set_h);
+cb!(res => cb_res_6_l, 6, l, set_l);
+cb!(res => cb_res_6_hl_dref, 6, hl);
+
+cb!(res => cb_res_7_a, 7, a, set_a);
+cb!(res => cb_res_7_b, 7, b, set_b);
+cb!(res => cb_res_7_c, 7, c, set_c);
+cb!(res => cb_res_7_d, 7, d, set_d);
+cb!(res => cb_res_7_e, 7, e, set_e);
+cb!(res => cb_res_7_h, 7, h, set_h);
+cb!(res => cb_res_7_l, 7, l, set_l);
+cb!(res => cb_res_7_hl_dref, 7, hl);
+
+cb!(set => cb_set_0_a, 0, a, set_a);
+cb!(set => cb_set_0_b, 0, b, set_b);
+cb!(set => cb_set_0_c, 0, c, set_c);
+cb!(set => cb_set_0_d, 0, d, set_d);
+cb!(set => cb_set_0_e, 0, e, set_e);
+cb!(set => cb_set_0_h, 0, h, set_h);
+cb!(set => cb_set_0_l, 0, l, set_l);
+cb!(set => cb_set_0_hl_dref, 0, hl);
+
+cb!(set => cb_set_1_a, 1, a, set_a);
+cb!(set => cb_set_1_b, 1, b, set_b);
+cb!(set => cb_set_1_c, 1, c, set_c);
+cb!(set => cb_set_1_d, 1, d, set_d);
+cb!(set => cb_set_1_e, 1, e, set_e);
+cb!(set => cb_set_1_h, 1, h, set_h);
+cb!(set => cb_set_1_l, 1, l, set_l);
+cb!(set => cb_set_1_hl_dref, 1, hl);
+
+cb!(set => cb_set_2_a, 2, a, set_a);
+cb!(set => cb_set_2_b, 2, b, set_b);
+cb!(set => cb_set_2_c, 2, c, set_c);
+cb!(set => cb_set_2_d, 2, d, set_d);
+cb!(set => cb_set_2_e, 2, e, set_e);
+cb!(set => cb_set_2_h, 2, h, set_h);
+cb!(set => cb_set_2_l, 2, l, set_l);
+cb!(set => cb_set_2_hl_dref, 2, hl);
+
+cb!(set => cb_set_3_a, 3, a, set_a);
+cb!(set => cb_set_3_b, 3, b, set_b);
+cb!(set => cb_set_3_c, 3, c, set_c);
+cb!(set => cb_set_3_d, 3, d, set_d);
+cb!(set => cb_set_3_e, 3, e, set_e);
+cb!(set => cb_set_3_h, 3, h, set_h);
+cb!(set => cb_set_3_l, 3, l, set_l);
+cb!(set => cb_set_3_hl_dref, 3, hl);
+
+cb!(set => cb_set_4_a, 4, a, set_a);
+cb!(set => cb_set_4_b, 4, b, set_b);
+cb!(set => cb_set_4_c, 4, c, set_c);
+cb!(set => cb_set_4_d, 4, d, set_d);
+cb!(set => cb_set_4_e, 4, e, set_e);
+cb!(set => cb_set_4_h, 4, h, set_h);
+cb!(set => cb_set_4_l, 4, l, set_l);
+cb!(set => cb_set_4_hl_dref, 4, hl);
+
+cb!(set => cb_set_5_a, 5, a, set_a);
+cb!(set => cb_set_5_b, 5, b, set_b);
+cb!(set => cb_set_5_c, 5, c, set_c);
+cb!(set => cb_set_5_d, 5, d, set_d);
+cb!(set => cb_set_5_e, 5, e, set_e);
+cb!(set => cb_set_5_h, 5, h, set_h);
+cb!(set => cb_set_5_l, 5, l, set_l);
+cb!(set => cb_set_5_hl_dref, 5, hl);
+
+cb!(set => cb_set_6_a, 6, a, set_a);
+cb!(set => cb_set_6_b, 6, b, set_b);
+cb!(set => cb_set_6_c, 6, c, set_c);
+cb!(set => cb_set_6_d, 6, d, set_d);
+cb!(set => cb_set_6_e, 6, e, set_e);
+cb!(set => cb_set_6_h, 6, h, set_h);
+cb!(set => cb_set_6_l, 6, l, set_l);
+cb!(set => cb_set_6_hl_dref, 6, hl);
+
+cb!(set => cb_set_7_a, 7, a, set_a);
+cb!(set => cb_set_7_b, 7, b, set_b);
+cb!(set => cb_set_7_c, 7, c, set_c);
+cb!(set => cb_set_7_d, 7, d, set_d);
+cb!(set => cb_set_7_e, 7, e, set_e);
+cb!(set => cb_set_7_h, 7, h, set_h);
+cb!(set => cb_set_7_l, 7, l, set_l);
+cb!(set => cb_set_7_hl_dref, 7, hl);
+
+// Z 0 1 -
+#[inline]
+fn bit(cpu: &mut Cpu, bit: u8, input: u8) {
+    cpu.registers.flag.set_zero(input & (1 << bit) == 0);
+    cpu.registers.flag.set_sub(false);
+    cpu.registers.flag.set_half_carry(true);
+}
+
+// - - - -
+#[inline]
+fn res(bit: u8, input: u8) -> u8 {
+    input & !(1 << bit)
+}
+
+// - - - -
+#[inline]
+fn set(bit: u8, input: u8) -> u8 {
+    input | (1 << bit)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn run_bit_test(bit_is_set: bool, init_carry: bool) {
+        let mut actual_cpu = Cpu::default();
+        actual_cpu
+            .registers
+            .set_h(if bit_is_set { 0b1000_0000 } else { 0b0111_1111 });
+        actual_cpu.registers.flag.set_zero(bit_is_set);
+        actual_cpu.registers.flag.set_sub(true);
+        actual_cpu.registers.flag.set_half_carry(false);
+        actual_cpu.registers.flag.set_carry(init_carry);
+
+        let mut expected_cpu = actual_cpu.clone();
+        expected_cpu.registers.flag.set_zero(!bit_is_set);
+        expected_cpu.registers.flag.set_sub(false);
+        expected_cpu.registers.flag.set_half_carry(true);
+        // Carry flag remain the same.
+        expected_cpu.registers.flag.set_carry(init_carry);
+
+        cb_bit_7_h(&mut actual_cpu);
+
+        assert_eq!(actual_cpu, expected_cpu);
+    }
+
+    #[test]
+    fn run_bit_with_bit_set() {
+        run_bit_test(true, false);
+    }
+
+    #[test]
+    fn run_bit_with_bit_unset() {
+        run_bit_test(false, true);
+    }
+
+    #[test]
+    fn run_bit_hl_dref() {
+        let mut actual_cpu = Cpu::default();
+        actual_cpu.registers.set_hl(0xC000);
+        actual_cpu.mmu.write_byte(0xC000, 0b0000_1000).unwrap();
+
+        let mut expected_cpu = actual_cpu.clone();
+        expected_cpu.registers.flag.set_zero(false);
+        expected_cpu.registers.flag.set_half_carry(true);
+
+        let (Cycle(cycle), _) = cb_bit_3_hl_dref(&mut actual_cpu);
+
+        assert_eq!(cycle, 12);
+        assert_eq!(actual_cpu, expected_cpu);
+    }
+
+    #[test]
+    fn run_res() {
+        let mut actual_cpu = Cpu::default();
+        actual_cpu.registers.set_b(0xFF);
+        actual_cpu.registers.flag.set_zero(true);
+
+        let mut expected_cpu = actual_cpu.clone();
+        expected_cpu.registers.set_b(0b1111_1011);
+
+        cb_res_2_b(&mut actual_cpu);
+
+        assert_eq!(actual_cpu, expected_cpu);
+    }
+
+    #[test]
+    fn run_set_hl_dref() {
+        let mut actual_cpu = Cpu::default();
+        actual_cpu.registers.set_hl(0xC000);
+
+        let mut expected_cpu = actual_cpu.clone();
+        expected_cpu.mmu.write_byte(0xC000, 0b0010_0000).unwrap();
+
+        let (Cycle(cycle), _) = cb_set_5_hl_dref(&mut actual_cpu);
+
+        assert_eq!(cycle, 16);
+        assert_eq!(actual_cpu, expected_cpu);
+    }
+}
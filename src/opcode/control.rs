@@ -5,6 +5,38 @@ pub fn nop(_: &mut Cpu) -> (Cycle, OpLength) {
     (Cycle(4), OpLength(1))
 }
 
+// HALT
+// 1  4
+pub fn halt(cpu: &mut Cpu) -> (Cycle, OpLength) {
+    cpu.halt();
+
+    (Cycle(4), OpLength(1))
+}
+
+// STOP 0
+// 2  4
+pub fn stop(cpu: &mut Cpu) -> (Cycle, OpLength) {
+    cpu.stop();
+
+    (Cycle(4), OpLength(2))
+}
+
+// DI
+// 1  4
+pub fn di(cpu: &mut Cpu) -> (Cycle, OpLength) {
+    cpu.set_ime(false);
+
+    (Cycle(4), OpLength(1))
+}
+
+// EI
+// 1  4
+pub fn ei(cpu: &mut Cpu) -> (Cycle, OpLength) {
+    cpu.schedule_ime();
+
+    (Cycle(4), OpLength(1))
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -18,4 +50,54 @@ mod test {
 
         assert!(cpu == new_cpu);
     }
+
+    #[test]
+    fn run_halt() {
+        let mut actual_cpu = Cpu::default();
+
+        let mut expected_cpu = actual_cpu.clone();
+        expected_cpu.set_halted(true);
+
+        halt(&mut actual_cpu);
+
+        assert_eq!(actual_cpu, expected_cpu);
+    }
+
+    #[test]
+    fn run_stop() {
+        let mut actual_cpu = Cpu::default();
+
+        let mut expected_cpu = actual_cpu.clone();
+        expected_cpu.set_stopped(true);
+
+        stop(&mut actual_cpu);
+
+        assert_eq!(actual_cpu, expected_cpu);
+    }
+
+    #[test]
+    fn run_di() {
+        let mut actual_cpu = Cpu::default();
+        actual_cpu.set_ime(true);
+
+        let mut expected_cpu = actual_cpu.clone();
+        expected_cpu.set_ime(false);
+
+        di(&mut actual_cpu);
+
+        assert_eq!(actual_cpu, expected_cpu);
+    }
+
+    #[test]
+    fn run_ei() {
+        let mut actual_cpu = Cpu::default();
+        actual_cpu.set_ime(false);
+
+        let mut expected_cpu = actual_cpu.clone();
+        expected_cpu.schedule_ime();
+
+        ei(&mut actual_cpu);
+
+        assert_eq!(actual_cpu, expected_cpu);
+    }
 }
@@ -1,5 +1,6 @@
-use super::arithmetic_logical_utils::{dec_d16, inc_d16};
+use super::arithmetic_logical_utils::{dec_d16, inc_d16, sp_add_r8};
 
+use crate::carry_test::CarryTestResult;
 use crate::cpu::Cpu;
 use crate::opcode::table::{Cycle, OpLength};
 use crate::opcode::types::{InstructionResult, LoadWordFromRegFn};
@@ -12,9 +13,8 @@ fn add_hl(cpu: &mut Cpu, load_from_reg: &LoadWordFromRegFn) -> (Cycle, OpLength)
 
     let (result_value, carry) = hl.overflowing_add(value_to_add);
 
-    // TODO Figure out if behaviour of half-carry is correct.
-    let half_result_value = (0x00FF & result_value) as u8;
-    let half_carry = half_result_value < cpu.registers.l();
+    // Half carry comes from bit 11.
+    let half_carry = (hl & 0x0FFF) + (value_to_add & 0x0FFF) > 0x0FFF;
 
     cpu.registers.set_hl(result_value);
 
@@ -69,29 +69,23 @@ pub fn add_hl_sp(cpu: &mut Cpu) -> (Cycle, OpLength) {
     add_hl(cpu, &Registers::sp)
 }
 
+// Affect flags: 0 0 H C (Z N H C).
 pub fn add_sp_r8(cpu: &mut Cpu) -> InstructionResult {
-    let pc = cpu.registers.pc();
-
     let sp = cpu.registers.sp();
-    let raw_r8 = cpu.mmu.read_byte((pc + 1) as u16);
-
-    let signed_r8 = i8::from_ne_bytes([raw_r8]);
-
-    let (new_sp, carry) = if signed_r8.is_positive() {
-        let (new_sp, carry) = sp.overflowing_add(u16::from(raw_r8));
-
-        (new_sp, carry)
-    } else {
-        let (new_sp, _) = sp.overflowing_sub(u16::from(0b1111_1111 - raw_r8 + 1));
+    let raw_r8 = cpu.read_byte_argument(1);
 
-        (new_sp, false)
-    };
-
-    let half_carry = (new_sp & (1 << 7)) < (sp & (1 << 7));
+    let CarryTestResult {
+        val: new_sp,
+        half_carry,
+        carry,
+    } = sp_add_r8(sp, raw_r8);
 
     cpu.registers.set_sp(new_sp);
-    cpu.registers.flag.set_carry(carry);
+
+    cpu.registers.flag.set_zero(false);
+    cpu.registers.flag.set_sub(false);
     cpu.registers.flag.set_half_carry(half_carry);
+    cpu.registers.flag.set_carry(carry);
 
     (Cycle(16), OpLength(2))
 }
@@ -156,13 +150,11 @@ mod test {
         with_carry: bool,
         with_half_carry: bool,
     ) {
-        let init_hl = 0x0101;
-
-        let init_value = {
-            let high_init_value = if with_carry { 0xFF00 } else { 0x1100 };
-            let low_init_value = if with_half_carry { 0x00FF } else { 0x0011 };
-
-            high_init_value + low_init_value
+        let (init_hl, init_value) = match (with_carry, with_half_carry) {
+            (false, false) => (0x1001, 0x2002),
+            (true, false) => (0x9001, 0x8002),
+            (false, true) => (0x0801, 0x0802),
+            (true, true) => (0x8801, 0x8802),
         };
 
         let result_hl = u16::wrapping_add(init_hl, init_value);
@@ -185,7 +177,7 @@ mod test {
         expected_cpu.registers.flag.set_sub(false);
         // Carry flag set when carrys from 16bits.
         expected_cpu.registers.flag.set_carry(with_carry);
-        // Half carry flag set when carrys from lower 8bits.
+        // Half carry flag set when carrys from lower 12bits.
         expected_cpu.registers.flag.set_half_carry(with_half_carry);
 
         inst_to_test(&mut actual_cpu);
@@ -194,11 +186,11 @@ mod test {
     }
 
     fn run_add_hl_hl(with_carry: bool, with_half_carry: bool) {
-        let init_hl = {
-            let high_init_value = if with_carry { 0xFF00 } else { 0x1100 };
-            let low_init_value = if with_half_carry { 0x00FF } else { 0x0011 };
-
-            high_init_value + low_init_value
+        let init_hl = match (with_carry, with_half_carry) {
+            (false, false) => 0x1001,
+            (true, false) => 0x9001,
+            (false, true) => 0x0801,
+            (true, true) => 0x8801,
         };
 
         let result_hl = u16::wrapping_add(init_hl, init_hl);
@@ -218,7 +210,7 @@ mod test {
         expected_cpu.registers.flag.set_sub(false);
         // Carry flag set when carrys from 16bits.
         expected_cpu.registers.flag.set_carry(with_carry);
-        // Half carry flag set when carrys from lower 8bits.
+        // Half carry flag set when carrys from lower 12bits.
         expected_cpu.registers.flag.set_half_carry(with_half_carry);
 
         add_hl_hl(&mut actual_cpu);
@@ -312,7 +304,7 @@ mod test {
 
     #[test]
     fn run_add_sp_r8_with_negtive_value() {
-        let init_pc = 0x0042;
+        let init_pc = 0xC042;
         let init_sp = 0xCCCC;
 
         let positive_r8 = 7;
@@ -326,21 +318,21 @@ mod test {
 
         actual_cpu.registers.flag.set_zero(true);
         actual_cpu.registers.flag.set_sub(true);
-        actual_cpu.registers.flag.set_carry(true);
-        actual_cpu.registers.flag.set_half_carry(true);
+        actual_cpu.registers.flag.set_carry(false);
+        actual_cpu.registers.flag.set_half_carry(false);
 
         let mut expected_cpu = actual_cpu.clone();
         expected_cpu
             .registers
             .set_sp(init_sp - u16::from(positive_r8));
 
-        // Zero flag remain the same.
-        expected_cpu.registers.flag.set_zero(true);
-        // Sub flag remain the same.
-        expected_cpu.registers.flag.set_sub(true);
-        // Substraction has no carry.
-        expected_cpu.registers.flag.set_carry(false);
-        expected_cpu.registers.flag.set_half_carry(false);
+        // Zero flag reset.
+        expected_cpu.registers.flag.set_zero(false);
+        // Sub flag reset.
+        expected_cpu.registers.flag.set_sub(false);
+        // Carries come from the unsigned addition of lower byte, 0xCC + 0xF9.
+        expected_cpu.registers.flag.set_carry(true);
+        expected_cpu.registers.flag.set_half_carry(true);
 
         add_sp_r8(&mut actual_cpu);
 
@@ -348,13 +340,13 @@ mod test {
     }
 
     fn run_add_sp_r8_positive(with_carry: bool, with_half_carry: bool) {
-        let init_pc = 0x0042;
+        let init_pc = 0xC042;
 
         let (init_sp, r8) = match (with_carry, with_half_carry) {
             (true, true) => (0b1111111111111111, 0b00000001),
             (false, false) => (1, 1),
-            (false, true) => (0b0000000011111111, 1),
-            (true, false) => panic!("This is impossible to achieve"),
+            (false, true) => (0b0000000000001111, 1),
+            (true, false) => (0b0000000011110000, 0b00010000),
         };
 
         let mut actual_cpu = Cpu::default();
@@ -373,13 +365,13 @@ mod test {
         let mut expected_cpu = actual_cpu.clone();
         expected_cpu.registers.set_sp(expected_sp);
 
-        // Zero flag remain the same.
-        expected_cpu.registers.flag.set_zero(true);
-        // Sub flag remain the same.
-        expected_cpu.registers.flag.set_sub(true);
-        // Carry flag set when carrys from 16bits.
+        // Zero flag reset.
+        expected_cpu.registers.flag.set_zero(false);
+        // Sub flag reset.
+        expected_cpu.registers.flag.set_sub(false);
+        // Carry flag set when carrys from lower 8bits.
         expected_cpu.registers.flag.set_carry(with_carry);
-        // Half carry flag set when carrys from lower 8bits.
+        // Half carry flag set when carrys from lower 4bits.
         expected_cpu.registers.flag.set_half_carry(with_half_carry);
 
         add_sp_r8(&mut actual_cpu);
@@ -401,4 +393,21 @@ mod test {
     fn run_add_sp_r8_without_carry_with_half_carry_with_positive_r8() {
         run_add_sp_r8_positive(false, true);
     }
+
+    #[test]
+    fn run_add_sp_r8_with_carry_without_half_carry_with_positive_r8() {
+        run_add_sp_r8_positive(true, false);
+    }
+
+    #[test]
+    fn run_inc_dec_wrap_around() {
+        let mut actual_cpu = Cpu::default();
+        actual_cpu.registers.set_bc(0xFFFF);
+
+        inc_bc(&mut actual_cpu);
+        assert_eq!(actual_cpu.registers.bc(), 0x0000);
+
+        dec_bc(&mut actual_cpu);
+        assert_eq!(actual_cpu.registers.bc(), 0xFFFF);
+    }
 }
@@ -559,6 +559,9 @@ pub fn dec_hl_dref(cpu: &mut Cpu) -> InstructionResult {
 // - 0 0 C
 pub fn ccf(cpu: &mut Cpu) -> InstructionResult {
     let carry = cpu.registers.flag.carry();
+
+    cpu.registers.flag.set_sub(false);
+    cpu.registers.flag.set_half_carry(false);
     cpu.registers.flag.set_carry(!carry);
 
     (Cycle(4), OpLength(1))
@@ -588,6 +591,43 @@ pub fn scf(cpu: &mut Cpu) -> InstructionResult {
     (Cycle(4), OpLength(1))
 }
 
+// DAA
+// 1  4
+// Z - 0 C
+pub fn daa(cpu: &mut Cpu) -> InstructionResult {
+    let a = cpu.registers.a();
+    let sub = cpu.registers.flag.sub();
+    let half_carry = cpu.registers.flag.half_carry();
+    let mut carry = cpu.registers.flag.carry();
+
+    let mut adjustment = 0;
+
+    // After an addition, adjust when a digit overflowed; after a subtraction,
+    // only undo borrows the flags tell us about.
+    if half_carry || (!sub && (a & 0x0F) > 0x09) {
+        adjustment |= 0x06;
+    }
+
+    if carry || (!sub && a > 0x99) {
+        adjustment |= 0x60;
+        carry = true;
+    }
+
+    let result = if sub {
+        a.wrapping_sub(adjustment)
+    } else {
+        a.wrapping_add(adjustment)
+    };
+
+    cpu.registers.set_a(result);
+
+    cpu.registers.flag.set_zero(result == 0);
+    cpu.registers.flag.set_half_carry(false);
+    cpu.registers.flag.set_carry(carry);
+
+    (Cycle(4), OpLength(1))
+}
+
 #[cfg(test)]
 mod tests {
     mod add_a {
@@ -1273,9 +1313,9 @@ mod tests {
             let val = 0;
 
             let expected_result = if with_old_carry { 0xFF } else { 0 };
-            let expected_half_carry = if with_old_carry { true } else { false };
-            let expected_carry = if with_old_carry { true } else { false };
-            let expected_zero = if with_old_carry { false } else { true };
+            let expected_half_carry = with_old_carry;
+            let expected_carry = with_old_carry;
+            let expected_zero = !with_old_carry;
 
             let is_sub = true;
 
@@ -1978,9 +2018,13 @@ mod tests {
             let expected_carry = false;
 
             let mut actual_cpu = Cpu::default();
+            actual_cpu.registers.flag.set_sub(true);
+            actual_cpu.registers.flag.set_half_carry(true);
             actual_cpu.registers.flag.set_carry(init_carry);
 
             let mut expected_cpu = actual_cpu.clone();
+            expected_cpu.registers.flag.set_sub(false);
+            expected_cpu.registers.flag.set_half_carry(false);
             expected_cpu.registers.flag.set_carry(expected_carry);
 
             ccf(&mut actual_cpu);
@@ -1995,9 +2039,13 @@ mod tests {
             let expected_carry = true;
 
             let mut actual_cpu = Cpu::default();
+            actual_cpu.registers.flag.set_sub(true);
+            actual_cpu.registers.flag.set_half_carry(true);
             actual_cpu.registers.flag.set_carry(init_carry);
 
             let mut expected_cpu = actual_cpu.clone();
+            expected_cpu.registers.flag.set_sub(false);
+            expected_cpu.registers.flag.set_half_carry(false);
             expected_cpu.registers.flag.set_carry(expected_carry);
 
             ccf(&mut actual_cpu);
@@ -2082,4 +2130,92 @@ mod tests {
             run_with_carry_or_not(false);
         }
     }
+
+    mod daa {
+        use super::super::daa;
+
+        use crate::cpu::Cpu;
+
+        fn run_test(
+            init_a: u8,
+            init_sub: bool,
+            init_half_carry: bool,
+            init_carry: bool,
+            expected_a: u8,
+            expected_carry: bool,
+        ) {
+            let mut actual_cpu = Cpu::default();
+            actual_cpu.registers.set_a(init_a);
+            actual_cpu.registers.flag.set_zero(expected_a != 0);
+            actual_cpu.registers.flag.set_sub(init_sub);
+            actual_cpu.registers.flag.set_half_carry(init_half_carry);
+            actual_cpu.registers.flag.set_carry(init_carry);
+
+            let mut expected_cpu = actual_cpu.clone();
+            expected_cpu.registers.set_a(expected_a);
+            expected_cpu.registers.flag.set_zero(expected_a == 0);
+            // Sub flag remain the same.
+            expected_cpu.registers.flag.set_sub(init_sub);
+            expected_cpu.registers.flag.set_half_carry(false);
+            expected_cpu.registers.flag.set_carry(expected_carry);
+
+            daa(&mut actual_cpu);
+
+            assert_eq!(actual_cpu, expected_cpu);
+        }
+
+        #[test]
+        fn run_after_add_without_adjustment() {
+            // 0x12 + 0x34
+            run_test(0x46, false, false, false, 0x46, false);
+        }
+
+        #[test]
+        fn run_after_add_with_low_digit_overflow() {
+            // 0x15 + 0x27
+            run_test(0x3C, false, false, false, 0x42, false);
+        }
+
+        #[test]
+        fn run_after_add_with_half_carry() {
+            // 0x19 + 0x28
+            run_test(0x41, false, true, false, 0x47, false);
+        }
+
+        #[test]
+        fn run_after_add_with_high_digit_overflow() {
+            // 0x90 + 0x20
+            run_test(0xB0, false, false, false, 0x10, true);
+        }
+
+        #[test]
+        fn run_after_add_with_carry() {
+            // 0x90 + 0x90
+            run_test(0x20, false, false, true, 0x80, true);
+        }
+
+        #[test]
+        fn run_after_add_with_zero_result() {
+            // 0x99 + 0x01
+            run_test(0x9A, false, false, false, 0x00, true);
+        }
+
+        #[test]
+        fn run_after_sub_with_half_carry() {
+            // 0x42 - 0x15
+            run_test(0x2D, true, true, false, 0x27, false);
+        }
+
+        #[test]
+        fn run_after_sub_with_carry() {
+            // 0x12 - 0x30
+            run_test(0xE2, true, false, true, 0x82, true);
+        }
+
+        #[test]
+        fn run_after_sub_without_adjustment() {
+            // 0x42 - 0x12
+            run_test(0x30, true, false, false, 0x30, false);
+        }
+    }
 }
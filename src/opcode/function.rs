@@ -36,16 +36,15 @@ pub fn pop_hl(cpu: &mut Cpu) -> InstructionResult {
 }
 
 #[inline]
-fn push(cpu: &mut Cpu, val: u16) {
-    let old_sp = cpu.registers.sp();
-    let new_sp = old_sp.wrapping_sub(2);
+pub fn push(cpu: &mut Cpu, val: u16) {
+    let new_sp = cpu.registers.sp().wrapping_sub(2);
 
-    cpu.mmu.write_word(old_sp, val).unwrap();
+    cpu.mmu.write_word(new_sp, val).unwrap();
     cpu.registers.set_sp(new_sp);
 }
 
 #[inline]
-fn push_reg(cpu: &mut Cpu, reg: &Fn(&Registers) -> u16) -> InstructionResult {
+fn push_reg(cpu: &mut Cpu, reg: &dyn Fn(&Registers) -> u16) -> InstructionResult {
     push(cpu, reg(&cpu.registers));
 
     (Cycle(16), OpLength(1))
@@ -62,7 +61,7 @@ fn pop(cpu: &mut Cpu) -> u16 {
 }
 
 #[inline]
-fn pop_reg(cpu: &mut Cpu, set_reg: &Fn(&mut Registers, u16)) -> InstructionResult {
+fn pop_reg(cpu: &mut Cpu, set_reg: &dyn Fn(&mut Registers, u16)) -> InstructionResult {
     let value = pop(cpu);
     set_reg(&mut cpu.registers, value);
 
@@ -90,17 +89,15 @@ pub fn call_c(cpu: &mut Cpu) -> InstructionResult {
 }
 
 #[inline]
-fn call_if(cpu: &mut Cpu, cond: &Fn(&Registers) -> bool) -> InstructionResult {
+fn call_if(cpu: &mut Cpu, cond: &dyn Fn(&Registers) -> bool) -> InstructionResult {
     if cond(&cpu.registers) {
         let new_pc = cpu.read_word_argument(1);
-        let new_sp = cpu.registers.sp() - 2;
-        let ret_pc = cpu.registers.pc() + 3;
+        let ret_pc = cpu.registers.pc().wrapping_add(3);
 
+        push(cpu, ret_pc);
         cpu.registers.set_pc(new_pc);
-        cpu.registers.set_sp(new_sp);
-        cpu.mmu.write_word(new_sp, ret_pc).unwrap();
 
-        (Cycle(24), OpLength(3))
+        (Cycle(24), OpLength(0))
     } else {
         (Cycle(12), OpLength(3))
     }
@@ -110,10 +107,10 @@ pub fn ret(cpu: &mut Cpu) -> InstructionResult {
     let sp = cpu.registers.sp();
     let ret_pc = cpu.mmu.read_word(sp);
 
-    cpu.registers.set_sp(sp + 2);
+    cpu.registers.set_sp(sp.wrapping_add(2));
     cpu.registers.set_pc(ret_pc);
 
-    (Cycle(16), OpLength(1))
+    (Cycle(16), OpLength(0))
 }
 
 pub fn reti(cpu: &mut Cpu) -> InstructionResult {
@@ -139,11 +136,11 @@ pub fn ret_c(cpu: &mut Cpu) -> InstructionResult {
 }
 
 #[inline]
-fn ret_if(cpu: &mut Cpu, cond: &Fn(&Registers) -> bool) -> InstructionResult {
+fn ret_if(cpu: &mut Cpu, cond: &dyn Fn(&Registers) -> bool) -> InstructionResult {
     if cond(&cpu.registers) {
         let _ = ret(cpu);
 
-        (Cycle(20), OpLength(1))
+        (Cycle(20), OpLength(0))
     } else {
         (Cycle(8), OpLength(1))
     }
@@ -188,7 +185,7 @@ fn rst_to(cpu: &mut Cpu, new_pc: u16) -> InstructionResult {
     push(cpu, ret_pc);
     cpu.registers.set_pc(new_pc);
 
-    (Cycle(16), OpLength(1))
+    (Cycle(16), OpLength(0))
 }
 
 #[cfg(test)]
@@ -199,7 +196,7 @@ mod test {
 
     #[test]
     fn run_push() {
-        let init_sp = 0x42;
+        let init_sp = 0xC042;
         let expected_sp = init_sp - 2;
 
         let pushed_value = 0x4242;
@@ -209,7 +206,10 @@ mod test {
 
         let mut expected_cpu = actual_cpu.clone();
         expected_cpu.registers.set_sp(expected_sp);
-        expected_cpu.mmu.write_word(init_sp, pushed_value).unwrap();
+        expected_cpu
+            .mmu
+            .write_word(expected_sp, pushed_value)
+            .unwrap();
 
         push(&mut actual_cpu, pushed_value);
 
@@ -218,7 +218,7 @@ mod test {
 
     #[test]
     fn run_pop() {
-        let init_sp = 0x42;
+        let init_sp = 0xC042;
         let expected_sp = init_sp + 2;
 
         let expected_popped_value = 0x4242;
@@ -241,8 +241,8 @@ mod test {
 
     #[test]
     fn run_call_if_with_true() {
-        let init_pc = 0x55;
-        let init_sp = 0x42;
+        let init_pc = 0xC055;
+        let init_sp = 0xC042;
 
         let nn = 0x12;
 
@@ -271,8 +271,8 @@ mod test {
 
     #[test]
     fn run_call_if_with_false() {
-        let init_pc = 0x55;
-        let init_sp = 0x42;
+        let init_pc = 0xC055;
+        let init_sp = 0xC042;
 
         let nn = 0x12;
 
@@ -290,8 +290,8 @@ mod test {
 
     #[test]
     fn run_ret() {
-        let init_pc = 0x55;
-        let init_sp = 0x42;
+        let init_pc = 0xC055;
+        let init_sp = 0xC042;
 
         let ret_pc = 0x12;
 
@@ -314,8 +314,8 @@ mod test {
 
     #[test]
     fn run_reti() {
-        let init_pc = 0x55;
-        let init_sp = 0x42;
+        let init_pc = 0xC055;
+        let init_sp = 0xC042;
         let init_ime = false;
 
         let ret_pc = 0x12;
@@ -342,8 +342,8 @@ mod test {
 
     #[test]
     fn run_ret_if_with_true() {
-        let init_pc = 0x55;
-        let init_sp = 0x42;
+        let init_pc = 0xC055;
+        let init_sp = 0xC042;
 
         let ret_pc = 0x12;
 
@@ -366,8 +366,8 @@ mod test {
 
     #[test]
     fn run_ret_if_with_false() {
-        let init_pc = 0x55;
-        let init_sp = 0x42;
+        let init_pc = 0xC055;
+        let init_sp = 0xC042;
 
         let ret_pc = 0x12;
 
@@ -385,8 +385,8 @@ mod test {
 
     #[test]
     fn run_rst_to() {
-        let init_pc = 0x01;
-        let init_sp = 0x64;
+        let init_pc = 0xC001;
+        let init_sp = 0xC064;
 
         let new_pc = 0x42;
         let ret_pc = init_pc + 1;
@@ -401,10 +401,28 @@ mod test {
         let mut expected_cpu = actual_cpu.clone();
         expected_cpu.registers.set_pc(expected_pc);
         expected_cpu.registers.set_sp(expected_sp);
-        expected_cpu.mmu.write_word(init_sp, ret_pc).unwrap();
+        expected_cpu.mmu.write_word(expected_sp, ret_pc).unwrap();
 
         rst_to(&mut actual_cpu, new_pc);
 
         assert_eq!(actual_cpu, expected_cpu);
     }
+
+    #[test]
+    fn run_push_af_pop_af() {
+        for flag in 0..=0xFF_u8 {
+            let af = 0x4200 + u16::from(flag);
+
+            let mut cpu = Cpu::default();
+            cpu.registers.set_sp(0xDFFE);
+            cpu.registers.set_af(af);
+
+            push_af(&mut cpu);
+            cpu.registers.set_af(0);
+            pop_af(&mut cpu);
+
+            assert_eq!(cpu.registers.af(), af & 0xFFF0);
+            assert_eq!(cpu.registers.sp(), 0xDFFE);
+        }
+    }
 }
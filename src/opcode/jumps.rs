@@ -7,7 +7,7 @@ pub fn jp_nn(cpu: &mut Cpu) -> InstructionResult {
     let nn = cpu.read_word_argument(1);
     cpu.registers.set_pc(nn);
 
-    (Cycle(12), OpLength(3))
+    (Cycle(16), OpLength(0))
 }
 
 pub fn jp_hl(cpu: &mut Cpu) -> InstructionResult {
@@ -15,7 +15,7 @@ pub fn jp_hl(cpu: &mut Cpu) -> InstructionResult {
 
     cpu.registers.set_pc(hl);
 
-    (Cycle(4), OpLength(1))
+    (Cycle(4), OpLength(0))
 }
 
 pub fn jp_nz(cpu: &mut Cpu) -> InstructionResult {
@@ -35,12 +35,12 @@ pub fn jp_c(cpu: &mut Cpu) -> InstructionResult {
 }
 
 #[inline]
-fn jp_if(cpu: &mut Cpu, cond: &Fn(&Registers) -> bool) -> InstructionResult {
+fn jp_if(cpu: &mut Cpu, cond: &dyn Fn(&Registers) -> bool) -> InstructionResult {
     let new_pc = cpu.read_word_argument(1);
 
     if cond(&cpu.registers) {
         cpu.registers.set_pc(new_pc);
-        (Cycle(16), OpLength(3))
+        (Cycle(16), OpLength(0))
     } else {
         (Cycle(12), OpLength(3))
     }
@@ -67,20 +67,17 @@ pub fn jr_c(cpu: &mut Cpu) -> InstructionResult {
 }
 
 #[inline]
-fn jr_if(cpu: &mut Cpu, cond: &Fn(&Registers) -> bool) -> InstructionResult {
-    let pc = cpu.registers.pc();
+fn jr_if(cpu: &mut Cpu, cond: &dyn Fn(&Registers) -> bool) -> InstructionResult {
     let pc_offset = cpu.read_byte_argument(1) as i8;
 
     if cond(&cpu.registers) {
-        let new_pc = if pc_offset < 0 {
-            pc - (pc_offset.abs() as u16)
-        } else {
-            pc + (pc_offset as u16)
-        };
+        // The offset is relative to the instruction following this one.
+        let next_pc = cpu.registers.pc().wrapping_add(2);
+        let new_pc = next_pc.wrapping_add(pc_offset as u16);
 
         cpu.registers.set_pc(new_pc);
 
-        (Cycle(12), OpLength(2))
+        (Cycle(12), OpLength(0))
     } else {
         (Cycle(8), OpLength(2))
     }
@@ -94,7 +91,7 @@ mod test {
 
     #[test]
     fn run_jp_nn() {
-        let init_pc = 0xcc;
+        let init_pc = 0xC0CC;
         let nn = 0x42;
 
         let mut actual_cpu = Cpu::default();
@@ -112,7 +109,7 @@ mod test {
 
     #[test]
     fn run_jp_hl() {
-        let init_pc = 0xcc;
+        let init_pc = 0xC0CC;
         let init_hl = 0x42;
 
         let expected_pc = init_hl;
@@ -131,7 +128,7 @@ mod test {
 
     #[test]
     fn run_jp_nz_with_zero_flag_set() {
-        let init_pc = 0xcc;
+        let init_pc = 0xC0CC;
         let zero_flag = true;
 
         let expected_pc = 0x4242;
@@ -150,7 +147,7 @@ mod test {
 
     #[test]
     fn run_jp_nz_with_zero_flag_unset() {
-        let init_pc = 0xcc;
+        let init_pc = 0xC0CC;
         let zero_flag = false;
 
         let expected_pc = 0x4242;
@@ -170,7 +167,7 @@ mod test {
 
     #[test]
     fn run_jp_z_with_zero_flag_set() {
-        let init_pc = 0xcc;
+        let init_pc = 0xC0CC;
         let zero_flag = true;
 
         let expected_pc = 0x4242;
@@ -190,7 +187,7 @@ mod test {
 
     #[test]
     fn run_jp_z_with_zero_flag_unset() {
-        let init_pc = 0xcc;
+        let init_pc = 0xC0CC;
         let zero_flag = false;
 
         let expected_pc = 0x4242;
@@ -209,7 +206,7 @@ mod test {
 
     #[test]
     fn run_jp_nc_with_carry_flag_set() {
-        let init_pc = 0xcc;
+        let init_pc = 0xC0CC;
         let carry_flag = true;
 
         let expected_pc = 0x4242;
@@ -228,7 +225,7 @@ mod test {
 
     #[test]
     fn run_jp_nc_with_carry_flag_unset() {
-        let init_pc = 0xcc;
+        let init_pc = 0xC0CC;
         let carry_flag = false;
 
         let expected_pc = 0x4242;
@@ -248,7 +245,7 @@ mod test {
 
     #[test]
     fn run_jp_c_with_carry_flag_set() {
-        let init_pc = 0xcc;
+        let init_pc = 0xC0CC;
         let carry_flag = true;
 
         let expected_pc = 0x4242;
@@ -268,7 +265,7 @@ mod test {
 
     #[test]
     fn run_jp_c_with_carry_flag_unset() {
-        let init_pc = 0xcc;
+        let init_pc = 0xC0CC;
         let carry_flag = false;
 
         let expected_pc = 0x4242;
@@ -287,10 +284,10 @@ mod test {
 
     #[test]
     fn run_jr_n_with_positive_value() {
-        let init_pc = 0xcc;
+        let init_pc = 0xC0CC;
         let n = 0x10;
 
-        let expected_pc = init_pc + u16::from(n);
+        let expected_pc = init_pc + 2 + u16::from(n);
 
         let mut actual_cpu = Cpu::default();
         actual_cpu.registers.set_pc(init_pc);
@@ -306,11 +303,11 @@ mod test {
 
     #[test]
     fn run_jr_n_with_negative_value() {
-        let init_pc = 0xcc;
+        let init_pc = 0xC0CC;
         let negative_n = -10_i8;
         let n = negative_n as u8;
 
-        let expected_pc = init_pc - 10;
+        let expected_pc = init_pc + 2 - 10;
 
         let mut actual_cpu = Cpu::default();
         actual_cpu.registers.set_pc(init_pc);
@@ -3,7 +3,8 @@ use crate::opcode::table::{Cycle, OpLength};
 use crate::registers::Registers;
 
 use super::ld_utils::{
-    ld, ldd_instruction, ldi_instruction, load_from_reg, store_to_pc_offset_dref, store_to_reg_dref,
+    ld, ldd_instruction, ldi_instruction, load_from_reg, read_byte_from_pc_offset,
+    store_to_pc_offset_dref, store_to_reg_dref,
 };
 
 pub fn ldi_hl_dref_a(cpu: &mut Cpu) -> (Cycle, OpLength) {
@@ -24,6 +25,36 @@ pub fn ld_a16_dref_a(cpu: &mut Cpu) -> (Cycle, OpLength) {
     (Cycle(16), OpLength(3))
 }
 
+pub fn ldh_a8_dref_a(cpu: &mut Cpu) -> (Cycle, OpLength) {
+    ld(cpu, &load_from_reg(&Registers::a), &|cpu, v| {
+        let addr = 0xFF00 + u16::from(read_byte_from_pc_offset(1)(cpu)?);
+
+        cpu.mmu.write_byte(addr, v)
+    });
+
+    (Cycle(12), OpLength(2))
+}
+
+pub fn ld_c_dref_a(cpu: &mut Cpu) -> (Cycle, OpLength) {
+    ld(cpu, &load_from_reg(&Registers::a), &|cpu, v| {
+        let addr = 0xFF00 + u16::from(cpu.registers.c());
+
+        cpu.mmu.write_byte(addr, v)
+    });
+
+    (Cycle(8), OpLength(1))
+}
+
+pub fn ld_hl_dref_d8(cpu: &mut Cpu) -> (Cycle, OpLength) {
+    ld(
+        cpu,
+        &read_byte_from_pc_offset(1),
+        &store_to_reg_dref(&Registers::hl),
+    );
+
+    (Cycle(12), OpLength(2))
+}
+
 macro_rules! ld_dref_reg_fn {
     ($fn_name:ident, $addr_reg_getter:ident, $val_reg_getter:ident) => {
         pub fn $fn_name(cpu: &mut Cpu) -> (Cycle, OpLength) {
@@ -56,7 +87,7 @@ mod test {
     #[test]
     fn run_ldi_hl_dref_a() {
         // Arrange: prepare cpu.
-        let the_addr = 0x4242;
+        let the_addr = 0xC242;
         let the_value = 0x42;
 
         let mut actual_cpu = Cpu::default();
@@ -77,7 +108,7 @@ mod test {
     #[test]
     fn run_ldd_hl_dref_a() {
         // Arrange: prepare cpu.
-        let the_addr = 0x4242;
+        let the_addr = 0xC242;
         let the_value = 0x42;
 
         let mut actual_cpu = Cpu::default();
@@ -102,9 +133,9 @@ mod test {
         let the_value = 0x42;
 
         let mut actual_cpu = Cpu::default();
-        actual_cpu.registers.set_pc(0);
-        actual_cpu.mmu.write_byte(0, 0xEA).unwrap();
-        actual_cpu.mmu.write_word(1, the_addr).unwrap();
+        actual_cpu.registers.set_pc(0xC000);
+        actual_cpu.mmu.write_byte(0xC000, 0xEA).unwrap();
+        actual_cpu.mmu.write_word(0xC001, the_addr).unwrap();
         actual_cpu.registers.set_a(the_value);
 
         let mut expected_cpu = actual_cpu.clone();
@@ -124,8 +155,8 @@ mod test {
                 // Arrange: prepare cpu.
                 // The value is for instruction like "ld_hl_dref_h" and "ld_hl_dref_h" which
                 // overlaped. Repeated value avoid unexpected behaviour.
-                let the_addr = 0x4242;
-                let the_value = 0x42;
+                let the_addr = 0xC2C2;
+                let the_value = 0xC2;
 
                 let mut actual_cpu = Cpu::default();
                 actual_cpu.registers.$addr_reg_setter(the_addr);
@@ -154,7 +185,72 @@ mod test {
     ld_dref_reg_test!(run_ld_hl_dref_h, ld_hl_dref_h, set_hl, set_h);
     ld_dref_reg_test!(run_ld_hl_dref_l, ld_hl_dref_l, set_hl, set_l);
 
-    // ld_dref_reg_test!(run_ld_c_dref_a, ld_c_dref_a, set_c, set_a);
+    #[test]
+    fn run_ldh_a8_dref_a() {
+        // Arrange: prepare cpu.
+        let the_pc = 0xC100;
+        let the_lower_addr = 0x80;
+        let the_value = 0x42;
+
+        let mut actual_cpu = Cpu::default();
+        actual_cpu.registers.set_pc(the_pc);
+        actual_cpu.registers.set_a(the_value);
+        actual_cpu.mmu.write_byte(the_pc, 0xE0).unwrap();
+        actual_cpu
+            .mmu
+            .write_byte(the_pc + 1, the_lower_addr)
+            .unwrap();
+
+        let mut expected_cpu = actual_cpu.clone();
+        expected_cpu.mmu.write_byte(0xFF80, the_value).unwrap();
+
+        // Action.
+        ldh_a8_dref_a(&mut actual_cpu);
 
-    // ld_dref_reg_test!(run_ld_a16_dref_a, ld_a16_dref_a, set_a);
+        // Assert: check cpu state.
+        assert_eq!(actual_cpu, expected_cpu);
+    }
+
+    #[test]
+    fn run_ld_c_dref_a() {
+        // Arrange: prepare cpu.
+        let the_lower_addr = 0x81;
+        let the_value = 0x42;
+
+        let mut actual_cpu = Cpu::default();
+        actual_cpu.registers.set_c(the_lower_addr);
+        actual_cpu.registers.set_a(the_value);
+
+        let mut expected_cpu = actual_cpu.clone();
+        expected_cpu.mmu.write_byte(0xFF81, the_value).unwrap();
+
+        // Action.
+        ld_c_dref_a(&mut actual_cpu);
+
+        // Assert: check cpu state.
+        assert_eq!(actual_cpu, expected_cpu);
+    }
+
+    #[test]
+    fn run_ld_hl_dref_d8() {
+        // Arrange: prepare cpu.
+        let the_pc = 0xC100;
+        let the_addr = 0xC000;
+        let the_value = 0x42;
+
+        let mut actual_cpu = Cpu::default();
+        actual_cpu.registers.set_pc(the_pc);
+        actual_cpu.registers.set_hl(the_addr);
+        actual_cpu.mmu.write_byte(the_pc, 0x36).unwrap();
+        actual_cpu.mmu.write_byte(the_pc + 1, the_value).unwrap();
+
+        let mut expected_cpu = actual_cpu.clone();
+        expected_cpu.mmu.write_byte(the_addr, the_value).unwrap();
+
+        // Action.
+        ld_hl_dref_d8(&mut actual_cpu);
+
+        // Assert: check cpu state.
+        assert_eq!(actual_cpu, expected_cpu);
+    }
 }
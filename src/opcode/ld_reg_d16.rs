@@ -19,7 +19,7 @@ pub fn ld_sp_d16(cpu: &mut Cpu) -> InstructionResult {
     ld_reg_d16(cpu, &Registers::set_sp)
 }
 
-fn ld_reg_d16(cpu: &mut Cpu, reg_setter: &Fn(&mut Registers, u16)) -> InstructionResult {
+fn ld_reg_d16(cpu: &mut Cpu, reg_setter: &dyn Fn(&mut Registers, u16)) -> InstructionResult {
     let d16 = cpu.read_word_argument(1);
     reg_setter(&mut cpu.registers, d16);
 
@@ -37,7 +37,7 @@ mod test {
 
                 #[test]
                 fn run() {
-                    let pc = 0x42;
+                    let pc = 0xC042;
                     let expected_bc = 0x1234;
 
                     let mut actual_cpu = Cpu::default();
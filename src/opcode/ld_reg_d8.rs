@@ -40,13 +40,12 @@ mod test {
         store_to_reg: &StoreByteToRegFn,
     ) {
         // Arrange: prepare CPU and its clone.
-        let init_pc = 0x00;
+        let init_pc = 0xC000;
         let the_value = 0x42;
 
         let mut cpu = Cpu::default();
-        cpu.mmu.write_byte(0x00, init_pc).unwrap();
-        cpu.mmu.write_byte(0x01, the_value).unwrap();
-        cpu.registers.set_pc(init_pc as u16);
+        cpu.mmu.write_byte(init_pc + 1, the_value).unwrap();
+        cpu.registers.set_pc(init_pc);
 
         let mut new_cpu = cpu.clone();
 
@@ -10,14 +10,14 @@ use super::types::LoadFromRegFn;
 
 pub fn ldi_instruction(cpu: &mut Cpu, op: &OpFn) -> (Cycle, OpLength) {
     let result = op(cpu);
-    cpu.registers.set_hl(cpu.registers.hl() + 1);
+    cpu.registers.set_hl(cpu.registers.hl().wrapping_add(1));
 
     result
 }
 
 pub fn ldd_instruction(cpu: &mut Cpu, op: &OpFn) -> (Cycle, OpLength) {
     let result = op(cpu);
-    cpu.registers.set_hl(cpu.registers.hl() - 1);
+    cpu.registers.set_hl(cpu.registers.hl().wrapping_sub(1));
 
     result
 }
@@ -27,7 +27,7 @@ pub fn read_byte_from_pc_offset(offset: u16) -> impl Fn(&Cpu) -> mmu::Result<u8>
     move |cpu| {
         let pc = cpu.registers.pc();
 
-        Ok(cpu.mmu.read_byte(pc + offset))
+        Ok(cpu.mmu.read_byte(pc.wrapping_add(offset)))
     }
 }
 
@@ -36,7 +36,7 @@ pub fn read_word_from_pc_offset(offset: u16) -> impl Fn(&Cpu) -> u16 {
     move |cpu| {
         let pc = cpu.registers.pc();
 
-        cpu.mmu.read_word(pc + offset)
+        cpu.mmu.read_word(pc.wrapping_add(offset))
     }
 }
 
@@ -86,7 +86,7 @@ pub fn store_to_pc_offset_dref(offset: u16) -> impl Fn(&mut Cpu, u8) -> mmu::Res
     move |cpu, v| {
         let pc = cpu.registers.pc();
 
-        let the_addr = cpu.mmu.read_word(pc + offset);
+        let the_addr = cpu.mmu.read_word(pc.wrapping_add(offset));
         cpu.mmu.write_byte(the_addr, v)?;
 
         Ok(())
@@ -103,11 +103,11 @@ pub fn ld<S>(cpu: &mut Cpu, load_from: &LoadFromFn<S>, store_to: &StoreToFn<S>)
 pub fn ldi<S>(cpu: &mut Cpu, load_from: &LoadFromFn<S>, store_to: &StoreToFn<S>) {
     ld(cpu, load_from, store_to);
 
-    cpu.registers.set_hl(cpu.registers.hl() + 1);
+    cpu.registers.set_hl(cpu.registers.hl().wrapping_add(1));
 }
 
 pub fn ldd<S>(cpu: &mut Cpu, load_from: &LoadFromFn<S>, store_to: &StoreToFn<S>) {
     ld(cpu, load_from, store_to);
 
-    cpu.registers.set_hl(cpu.registers.hl() - 1);
+    cpu.registers.set_hl(cpu.registers.hl().wrapping_sub(1));
 }
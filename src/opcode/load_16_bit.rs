@@ -1,4 +1,6 @@
-use crate::carry_test::{CarryTest, CarryTestResult};
+use super::arithmetic_logical_utils::sp_add_r8;
+
+use crate::carry_test::CarryTestResult;
 use crate::cpu::Cpu;
 use crate::opcode::table::{Cycle, OpLength};
 use crate::opcode::types::InstructionResult;
@@ -18,23 +20,16 @@ pub fn ld_a16_sp(cpu: &mut Cpu) -> InstructionResult {
     (Cycle(20), OpLength(3))
 }
 
+// Affect flags: 0 0 H C (Z N H C).
 pub fn ld_hl_sp_n(cpu: &mut Cpu) -> InstructionResult {
     let raw_arg = cpu.read_byte_argument(1);
-
-    let signed_arg = i8::from_ne_bytes([raw_arg]);
-    let unsigned_arg = signed_arg.abs() as u16;
-
     let sp = cpu.registers.sp();
 
     let CarryTestResult {
         val: new_hl,
         half_carry,
         carry,
-    } = if signed_arg.is_positive() {
-        sp.carry_add(unsigned_arg)
-    } else {
-        sp.carry_sub(unsigned_arg)
-    };
+    } = sp_add_r8(sp, raw_arg);
 
     cpu.registers.set_hl(new_hl);
 
@@ -71,10 +66,10 @@ mod test {
 
     #[test]
     fn test_ld_a16_sp() {
-        let reg_pc = 0x12;
+        let reg_pc = 0xC012;
         let reg_sp = 0x87;
 
-        let a16 = 0x42;
+        let a16 = 0xC042;
 
         let expected_value = reg_sp;
 
@@ -103,7 +98,7 @@ mod test {
 
         let id = |e| e;
 
-        let init_pc = 0x12;
+        let init_pc = 0xC012;
         let init_hl = 0;
 
         let arg = u8::from_ne_bytes(signed_arg.to_ne_bytes());
@@ -119,11 +114,11 @@ mod test {
         actual_cpu
             .registers
             .flag
-            .set_half_carry(expected_half_carry.map_or(false, &Not::not));
+            .set_half_carry(expected_half_carry.is_some_and(Not::not));
         actual_cpu
             .registers
             .flag
-            .set_carry(expected_carry.map_or(false, &Not::not));
+            .set_carry(expected_carry.is_some_and(Not::not));
 
         actual_cpu.mmu.write_byte(init_pc + 1, arg).unwrap();
 
@@ -136,11 +131,11 @@ mod test {
         expected_cpu
             .registers
             .flag
-            .set_half_carry(expected_half_carry.map_or(false, &id));
+            .set_half_carry(expected_half_carry.is_some_and(&id));
         expected_cpu
             .registers
             .flag
-            .set_carry(expected_carry.map_or(false, &id));
+            .set_carry(expected_carry.is_some_and(&id));
 
         ld_hl_sp_n(&mut actual_cpu);
 
@@ -154,12 +149,13 @@ mod test {
 
     #[test]
     fn test_ld_hl_sp_n_with_positive_arg() {
-        run_ld_hl_sp_n(123, 10, 133, None, None);
+        run_ld_hl_sp_n(0x0101, 10, 0x010B, None, None);
     }
 
     #[test]
     fn test_ld_hl_sp_n_with_negtive_arg() {
-        run_ld_hl_sp_n(123, -1, 122, None, None);
+        // Carries come from the unsigned addition 0x7B + 0xFF.
+        run_ld_hl_sp_n(123, -1, 122, Some(true), Some(true));
     }
 
     #[test]
@@ -169,6 +165,11 @@ mod test {
 
     #[test]
     fn test_ld_hl_sp_n_with_half_carry_flag() {
-        run_ld_hl_sp_n(0x00FF, 1, 0x0100, Some(true), Some(false));
+        run_ld_hl_sp_n(0x000F, 1, 0x0010, Some(true), Some(false));
+    }
+
+    #[test]
+    fn test_ld_hl_sp_n_with_carry_flag_only() {
+        run_ld_hl_sp_n(0x00F0, 0x10, 0x0100, Some(false), Some(true));
     }
 }
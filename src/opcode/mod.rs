@@ -1,3 +1,4 @@
+pub mod bit;
 pub mod control;
 pub mod d16_arithmetic_logical;
 pub mod d8_arithmetic_logical;
@@ -10,6 +11,7 @@ pub mod ld_reg_dref;
 pub mod ld_reg_reg;
 pub mod load_16_bit;
 pub mod rotate;
+pub mod shift;
 pub mod table;
 pub mod types;
 
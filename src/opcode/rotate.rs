@@ -25,6 +25,28 @@ macro_rules! cb {
             (Cycle(8), OpLength(1))
         }
     };
+
+    (rrc => $fn_name:ident, $getter:ident, $setter:ident) => {
+        pub fn $fn_name(cpu: &mut Cpu) -> InstructionResult {
+            let (new_reg, new_flag) = rrc(cpu.registers.$getter());
+
+            cpu.registers.$setter(new_reg);
+            cpu.registers.flag = new_flag;
+
+            (Cycle(8), OpLength(1))
+        }
+    };
+
+    (rr => $fn_name:ident, $getter:ident, $setter:ident) => {
+        pub fn $fn_name(cpu: &mut Cpu) -> InstructionResult {
+            let (new_reg, new_flag) = rr(cpu.registers.flag.carry(), cpu.registers.$getter());
+
+            cpu.registers.$setter(new_reg);
+            cpu.registers.flag = new_flag;
+
+            (Cycle(8), OpLength(1))
+        }
+    };
 }
 
 cb!(rlc => cb_rlca, a, set_a);
@@ -63,8 +85,46 @@ pub fn cb_rl_hl_dref(cpu: &mut Cpu) -> InstructionResult {
     (Cycle(16), OpLength(1))
 }
 
+cb!(rrc => cb_rrca, a, set_a);
+cb!(rrc => cb_rrcb, b, set_b);
+cb!(rrc => cb_rrcc, c, set_c);
+cb!(rrc => cb_rrcd, d, set_d);
+cb!(rrc => cb_rrce, e, set_e);
+cb!(rrc => cb_rrch, h, set_h);
+cb!(rrc => cb_rrcl, l, set_l);
+
+pub fn cb_rrc_hl_dref(cpu: &mut Cpu) -> InstructionResult {
+    let hl = cpu.registers.hl();
+    let (new_value, new_flag) = rrc(cpu.read_hl_dref());
+
+    cpu.mmu.write_byte(hl, new_value).unwrap();
+    cpu.registers.flag = new_flag;
+
+    (Cycle(16), OpLength(1))
+}
+
+cb!(rr => cb_rra, a, set_a);
+cb!(rr => cb_rrb, b, set_b);
+cb!(rr => cb_rrc, c, set_c);
+cb!(rr => cb_rrd, d, set_d);
+cb!(rr => cb_rre, e, set_e);
+cb!(rr => cb_rrh, h, set_h);
+cb!(rr => cb_rrl, l, set_l);
+
+pub fn cb_rr_hl_dref(cpu: &mut Cpu) -> InstructionResult {
+    let hl = cpu.registers.hl();
+    let (new_value, new_flag) = rr(cpu.registers.flag.carry(), cpu.read_hl_dref());
+
+    cpu.mmu.write_byte(hl, new_value).unwrap();
+    cpu.registers.flag = new_flag;
+
+    (Cycle(16), OpLength(1))
+}
+
 pub fn rlca(cpu: &mut Cpu) -> InstructionResult {
-    let (new_a, new_flag) = rlc(cpu.registers.a());
+    let (new_a, mut new_flag) = rlc(cpu.registers.a());
+    // Unlike the CB-prefixed version, zero flag is always reset.
+    new_flag.set_zero(false);
 
     cpu.registers.set_a(new_a);
     cpu.registers.flag = new_flag;
@@ -73,7 +133,8 @@ pub fn rlca(cpu: &mut Cpu) -> InstructionResult {
 }
 
 pub fn rla(cpu: &mut Cpu) -> InstructionResult {
-    let (new_a, new_flags) = rl(cpu.registers.flag.carry(), cpu.registers.a());
+    let (new_a, mut new_flags) = rl(cpu.registers.flag.carry(), cpu.registers.a());
+    new_flags.set_zero(false);
 
     cpu.registers.set_a(new_a);
     cpu.registers.flag = new_flags;
@@ -82,7 +143,8 @@ pub fn rla(cpu: &mut Cpu) -> InstructionResult {
 }
 
 pub fn rrca(cpu: &mut Cpu) -> InstructionResult {
-    let (new_a, new_flag) = rrc(cpu.registers.a());
+    let (new_a, mut new_flag) = rrc(cpu.registers.a());
+    new_flag.set_zero(false);
 
     cpu.registers.set_a(new_a);
     cpu.registers.flag = new_flag;
@@ -91,7 +153,8 @@ pub fn rrca(cpu: &mut Cpu) -> InstructionResult {
 }
 
 pub fn rra(cpu: &mut Cpu) -> InstructionResult {
-    let (new_a, new_flags) = rr(cpu.registers.flag.carry(), cpu.registers.a());
+    let (new_a, mut new_flags) = rr(cpu.registers.flag.carry(), cpu.registers.a());
+    new_flags.set_zero(false);
 
     cpu.registers.set_a(new_a);
     cpu.registers.flag = new_flags;
@@ -199,6 +262,34 @@ mod test {
         );
     }
 
+    #[test]
+    fn run_cb_rr_hl_dref() {
+        let mut actual_cpu = Cpu::default();
+        actual_cpu.registers.set_hl(0xC000);
+        actual_cpu.registers.flag.set_carry(true);
+        actual_cpu.mmu.write_byte(0xC000, 0b0000_0011).unwrap();
+
+        let mut expected_cpu = actual_cpu.clone();
+        expected_cpu.mmu.write_byte(0xC000, 0b1000_0001).unwrap();
+        expected_cpu.registers.flag = Flag::new(false, false, false, true);
+
+        let (Cycle(cycle), _) = cb_rr_hl_dref(&mut actual_cpu);
+
+        assert_eq!(cycle, 16);
+        assert_eq!(actual_cpu, expected_cpu);
+    }
+
+    #[test]
+    fn run_rlca_with_zero_result() {
+        let mut cpu = Cpu::default();
+        cpu.registers.set_a(0);
+        cpu.registers.flag.set_zero(true);
+
+        rlca(&mut cpu);
+
+        assert_eq!(cpu.registers.flag, Flag::new(false, false, false, false));
+    }
+
     #[test]
     fn run_rr() {
         assert_eq!(
@@ -0,0 +1,199 @@
+use crate::cpu::Cpu;
+use crate::opcode::table::{Cycle, OpLength};
+use crate::opcode::types::InstructionResult;
+use crate::registers::Flag;
+
+macro_rules! cb {
+    ($op:ident => $fn_name:ident, hl) => {
+        pub fn $fn_name(cpu: &mut Cpu) -> InstructionResult {
+            let hl = cpu.registers.hl();
+            let (new_value, new_flag) = $op(cpu.read_hl_dref());
+
+            cpu.mmu.write_byte(hl, new_value).unwrap();
+            cpu.registers.flag = new_flag;
+
+            (Cycle(16), OpLength(1))
+        }
+    };
+
+    ($op:ident => $fn_name:ident, $getter:ident, $setter:ident) => {
+        pub fn $fn_name(cpu: &mut Cpu) -> InstructionResult {
+            let (new_reg, new_flag) = $op(cpu.registers.$getter());
+
+            cpu.registers.$setter(new_reg);
+            cpu.registers.flag = new_flag;
+
+            (Cycle(8), OpLength(1))
+        }
+    };
+}
+
+cb!(sla => cb_slaa, a, set_a);
+cb!(sla => cb_slab, b, set_b);
+cb!(sla => cb_slac, c, set_c);
+cb!(sla => cb_slad, d, set_d);
+cb!(sla => cb_slae, e, set_e);
+cb!(sla => cb_slah, h, set_h);
+cb!(sla => cb_slal, l, set_l);
+
+cb!(sla => cb_sla_hl_dref, hl);
+
+cb!(sra => cb_sraa, a, set_a);
+cb!(sra => cb_srab, b, set_b);
+cb!(sra => cb_srac, c, set_c);
+cb!(sra => cb_srad, d, set_d);
+cb!(sra => cb_srae, e, set_e);
+cb!(sra => cb_srah, h, set_h);
+cb!(sra => cb_sral, l, set_l);
+
+cb!(sra => cb_sra_hl_dref, hl);
+
+cb!(swap => cb_swapa, a, set_a);
+cb!(swap => cb_swapb, b, set_b);
+cb!(swap => cb_swapc, c, set_c);
+cb!(swap => cb_swapd, d, set_d);
+cb!(swap => cb_swape, e, set_e);
+cb!(swap => cb_swaph, h, set_h);
+cb!(swap => cb_swapl, l, set_l);
+
+cb!(swap => cb_swap_hl_dref, hl);
+
+cb!(srl => cb_srla, a, set_a);
+cb!(srl => cb_srlb, b, set_b);
+cb!(srl => cb_srlc, c, set_c);
+cb!(srl => cb_srld, d, set_d);
+cb!(srl => cb_srle, e, set_e);
+cb!(srl => cb_srlh, h, set_h);
+cb!(srl => cb_srll, l, set_l);
+
+cb!(srl => cb_srl_hl_dref, hl);
+
+// Z 0 0 C
+#[inline]
+fn sla(input: u8) -> (u8, Flag) {
+    let carry = 0b1000_0000 & input != 0;
+    let new_value = input << 1;
+
+    (new_value, Flag::new(new_value == 0, false, false, carry))
+}
+
+// Z 0 0 C, bit 7 is kept.
+#[inline]
+fn sra(input: u8) -> (u8, Flag) {
+    let carry = 0b0000_0001 & input != 0;
+    let new_value = (input >> 1) | (input & 0b1000_0000);
+
+    (new_value, Flag::new(new_value == 0, false, false, carry))
+}
+
+// Z 0 0 0
+#[inline]
+fn swap(input: u8) -> (u8, Flag) {
+    let new_value = input.rotate_left(4);
+
+    (new_value, Flag::new(new_value == 0, false, false, false))
+}
+
+// Z 0 0 C
+#[inline]
+fn srl(input: u8) -> (u8, Flag) {
+    let carry = 0b0000_0001 & input != 0;
+    let new_value = input >> 1;
+
+    (new_value, Flag::new(new_value == 0, false, false, carry))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::registers::Flag;
+
+    pub use super::*;
+
+    #[test]
+    fn run_sla() {
+        assert_eq!(
+            sla(0b00000000),
+            (0b00000000, Flag::new(true, false, false, false))
+        );
+        assert_eq!(
+            sla(0b10000000),
+            (0b00000000, Flag::new(true, false, false, true))
+        );
+        assert_eq!(
+            sla(0b11000001),
+            (0b10000010, Flag::new(false, false, false, true))
+        );
+    }
+
+    #[test]
+    fn run_sra() {
+        assert_eq!(
+            sra(0b00000001),
+            (0b00000000, Flag::new(true, false, false, true))
+        );
+        assert_eq!(
+            sra(0b10000000),
+            (0b11000000, Flag::new(false, false, false, false))
+        );
+        assert_eq!(
+            sra(0b01000011),
+            (0b00100001, Flag::new(false, false, false, true))
+        );
+    }
+
+    #[test]
+    fn run_swap() {
+        assert_eq!(
+            swap(0b00000000),
+            (0b00000000, Flag::new(true, false, false, false))
+        );
+        assert_eq!(
+            swap(0b10100101),
+            (0b01011010, Flag::new(false, false, false, false))
+        );
+    }
+
+    #[test]
+    fn run_srl() {
+        assert_eq!(
+            srl(0b00000001),
+            (0b00000000, Flag::new(true, false, false, true))
+        );
+        assert_eq!(
+            srl(0b10000000),
+            (0b01000000, Flag::new(false, false, false, false))
+        );
+    }
+
+    #[test]
+    fn run_cb_swap_hl_dref() {
+        let mut actual_cpu = Cpu::default();
+        actual_cpu.registers.set_hl(0xC000);
+        actual_cpu.registers.flag.set_carry(true);
+        actual_cpu.mmu.write_byte(0xC000, 0x12).unwrap();
+
+        let mut expected_cpu = actual_cpu.clone();
+        expected_cpu.mmu.write_byte(0xC000, 0x21).unwrap();
+        expected_cpu.registers.flag = Flag::new(false, false, false, false);
+
+        let (Cycle(cycle), _) = cb_swap_hl_dref(&mut actual_cpu);
+
+        assert_eq!(cycle, 16);
+        assert_eq!(actual_cpu, expected_cpu);
+    }
+
+    #[test]
+    fn run_cb_srla() {
+        let mut actual_cpu = Cpu::default();
+        actual_cpu.registers.set_a(0b0000_0011);
+
+        let mut expected_cpu = actual_cpu.clone();
+        expected_cpu.registers.set_a(0b0000_0001);
+        expected_cpu.registers.flag = Flag::new(false, false, false, true);
+
+        let (Cycle(cycle), _) = cb_srla(&mut actual_cpu);
+
+        assert_eq!(cycle, 8);
+        assert_eq!(actual_cpu, expected_cpu);
+    }
+}
@@ -1,4 +1,5 @@
 use crate::cpu::Cpu;
+use crate::opcode::bit;
 use crate::opcode::control;
 use crate::opcode::d16_arithmetic_logical;
 use crate::opcode::d8_arithmetic_logical;
@@ -11,19 +12,37 @@ use crate::opcode::ld_reg_dref;
 use crate::opcode::ld_reg_reg;
 use crate::opcode::load_16_bit;
 use crate::opcode::rotate;
+use crate::opcode::shift;
 
-pub type OpFn = Fn(&mut Cpu) -> (Cycle, OpLength);
+pub type OpFn = dyn Fn(&mut Cpu) -> (Cycle, OpLength);
 
-fn unimplement_op_fn(_: &mut Cpu) -> (Cycle, OpLength) {
-    unimplemented!("Op code is not implemented yet");
+/// Op codes the SM83 leaves undefined, `Cpu` decides how to deal with them.
+pub const ILLEGAL_OP_CODES: [u8; 11] = [
+    0xD3, 0xDB, 0xDD, 0xE3, 0xE4, 0xEB, 0xEC, 0xED, 0xF4, 0xFC, 0xFD,
+];
+
+pub fn is_illegal_op_code(op_code: u8) -> bool {
+    ILLEGAL_OP_CODES.contains(&op_code)
+}
+
+fn illegal_op_fn(_: &mut Cpu) -> (Cycle, OpLength) {
+    unreachable!("Illegal op code should be handled by Cpu");
 }
 
+/// T-cycles spent by an instruction.
 pub struct Cycle(pub u8);
+
+/// Bytes PC should advance by once an instruction finishes. Instructions
+/// which write PC themselves (taken jumps, calls, returns, rst) report zero.
 pub struct OpLength(pub u8);
 
 pub fn op_table(op_code: u8) -> &'static OpFn {
     match op_code {
         0x00 => &control::nop,
+        0x10 => &control::stop,
+        0x76 => &control::halt,
+        0xF3 => &control::di,
+        0xFB => &control::ei,
 
         // ld reg d8.
         0x06 => &ld_reg_d8::ld_b_d8,
@@ -128,6 +147,10 @@ pub fn op_table(op_code: u8) -> &'static OpFn {
 
         0xEA => &ld_dref_reg::ld_a16_dref_a,
 
+        0xE0 => &ld_dref_reg::ldh_a8_dref_a,
+        0xE2 => &ld_dref_reg::ld_c_dref_a,
+        0x36 => &ld_dref_reg::ld_hl_dref_d8,
+
         // d16 arithmetic/logical
         0x03 => &d16_arithmetic_logical::inc_bc,
         0x13 => &d16_arithmetic_logical::inc_de,
@@ -251,6 +274,8 @@ pub fn op_table(op_code: u8) -> &'static OpFn {
 
         0x3F => &d8_arithmetic_logical::ccf,
 
+        0x27 => &d8_arithmetic_logical::daa,
+
         // ld reg d16.
         0x01 => &ld_reg_d16::ld_bc_d16,
         0x11 => &ld_reg_d16::ld_de_d16,
@@ -322,17 +347,14 @@ pub fn op_table(op_code: u8) -> &'static OpFn {
         // CB-prefix
         0xCB => &cb_prefix,
 
-        _ => &unimplement_op_fn,
+        _ => &illegal_op_fn,
     }
 }
 
 fn cb_prefix(cpu: &mut Cpu) -> (Cycle, OpLength) {
     let cb_argument = cpu.read_byte_argument(1);
 
-    cpu.registers.set_pc(cpu.registers.pc() + 1);
-
     let cb_fn: &'static OpFn = match cb_argument {
-        0x07 => &rotate::cb_rlca,
         0x00 => &rotate::cb_rlcb,
         0x01 => &rotate::cb_rlcc,
         0x02 => &rotate::cb_rlcd,
@@ -340,8 +362,17 @@ fn cb_prefix(cpu: &mut Cpu) -> (Cycle, OpLength) {
         0x04 => &rotate::cb_rlch,
         0x05 => &rotate::cb_rlcl,
         0x06 => &rotate::cb_rlc_hl_dref,
+        0x07 => &rotate::cb_rlca,
+
+        0x08 => &rotate::cb_rrcb,
+        0x09 => &rotate::cb_rrcc,
+        0x0A => &rotate::cb_rrcd,
+        0x0B => &rotate::cb_rrce,
+        0x0C => &rotate::cb_rrch,
+        0x0D => &rotate::cb_rrcl,
+        0x0E => &rotate::cb_rrc_hl_dref,
+        0x0F => &rotate::cb_rrca,
 
-        0x17 => &rotate::cb_rla,
         0x10 => &rotate::cb_rlb,
         0x11 => &rotate::cb_rlc,
         0x12 => &rotate::cb_rld,
@@ -349,9 +380,293 @@ fn cb_prefix(cpu: &mut Cpu) -> (Cycle, OpLength) {
         0x14 => &rotate::cb_rlh,
         0x15 => &rotate::cb_rll,
         0x16 => &rotate::cb_rl_hl_dref,
+        0x17 => &rotate::cb_rla,
 
-        _ => unimplemented!(),
+        0x18 => &rotate::cb_rrb,
+        0x19 => &rotate::cb_rrc,
+        0x1A => &rotate::cb_rrd,
+        0x1B => &rotate::cb_rre,
+        0x1C => &rotate::cb_rrh,
+        0x1D => &rotate::cb_rrl,
+        0x1E => &rotate::cb_rr_hl_dref,
+        0x1F => &rotate::cb_rra,
+
+        0x20 => &shift::cb_slab,
+        0x21 => &shift::cb_slac,
+        0x22 => &shift::cb_slad,
+        0x23 => &shift::cb_slae,
+        0x24 => &shift::cb_slah,
+        0x25 => &shift::cb_slal,
+        0x26 => &shift::cb_sla_hl_dref,
+        0x27 => &shift::cb_slaa,
+
+        0x28 => &shift::cb_srab,
+        0x29 => &shift::cb_srac,
+        0x2A => &shift::cb_srad,
+        0x2B => &shift::cb_srae,
+        0x2C => &shift::cb_srah,
+        0x2D => &shift::cb_sral,
+        0x2E => &shift::cb_sra_hl_dref,
+        0x2F => &shift::cb_sraa,
+
+        0x30 => &shift::cb_swapb,
+        0x31 => &shift::cb_swapc,
+        0x32 => &shift::cb_swapd,
+        0x33 => &shift::cb_swape,
+        0x34 => &shift::cb_swaph,
+        0x35 => &shift::cb_swapl,
+        0x36 => &shift::cb_swap_hl_dref,
+        0x37 => &shift::cb_swapa,
+
+        0x38 => &shift::cb_srlb,
+        0x39 => &shift::cb_srlc,
+        0x3A => &shift::cb_srld,
+        0x3B => &shift::cb_srle,
+        0x3C => &shift::cb_srlh,
+        0x3D => &shift::cb_srll,
+        0x3E => &shift::cb_srl_hl_dref,
+        0x3F => &shift::cb_srla,
+
+        0x40 => &bit::cb_bit_0_b,
+        0x41 => &bit::cb_bit_0_c,
+        0x42 => &bit::cb_bit_0_d,
+        0x43 => &bit::cb_bit_0_e,
+        0x44 => &bit::cb_bit_0_h,
+        0x45 => &bit::cb_bit_0_l,
+        0x46 => &bit::cb_bit_0_hl_dref,
+        0x47 => &bit::cb_bit_0_a,
+
+        0x48 => &bit::cb_bit_1_b,
+        0x49 => &bit::cb_bit_1_c,
+        0x4A => &bit::cb_bit_1_d,
+        0x4B => &bit::cb_bit_1_e,
+        0x4C => &bit::cb_bit_1_h,
+        0x4D => &bit::cb_bit_1_l,
+        0x4E => &bit::cb_bit_1_hl_dref,
+        0x4F => &bit::cb_bit_1_a,
+
+        0x50 => &bit::cb_bit_2_b,
+        0x51 => &bit::cb_bit_2_c,
+        0x52 => &bit::cb_bit_2_d,
+        0x53 => &bit::cb_bit_2_e,
+        0x54 => &bit::cb_bit_2_h,
+        0x55 => &bit::cb_bit_2_l,
+        0x56 => &bit::cb_bit_2_hl_dref,
+        0x57 => &bit::cb_bit_2_a,
+
+        0x58 => &bit::cb_bit_3_b,
+        0x59 => &bit::cb_bit_3_c,
+        0x5A => &bit::cb_bit_3_d,
+        0x5B => &bit::cb_bit_3_e,
+        0x5C => &bit::cb_bit_3_h,
+        0x5D => &bit::cb_bit_3_l,
+        0x5E => &bit::cb_bit_3_hl_dref,
+        0x5F => &bit::cb_bit_3_a,
+
+        0x60 => &bit::cb_bit_4_b,
+        0x61 => &bit::cb_bit_4_c,
+        0x62 => &bit::cb_bit_4_d,
+        0x63 => &bit::cb_bit_4_e,
+        0x64 => &bit::cb_bit_4_h,
+        0x65 => &bit::cb_bit_4_l,
+        0x66 => &bit::cb_bit_4_hl_dref,
+        0x67 => &bit::cb_bit_4_a,
+
+        0x68 => &bit::cb_bit_5_b,
+        0x69 => &bit::cb_bit_5_c,
+        0x6A => &bit::cb_bit_5_d,
+        0x6B => &bit::cb_bit_5_e,
+        0x6C => &bit::cb_bit_5_h,
+        0x6D => &bit::cb_bit_5_l,
+        0x6E => &bit::cb_bit_5_hl_dref,
+        0x6F => &bit::cb_bit_5_a,
+
+        0x70 => &bit::cb_bit_6_b,
+        0x71 => &bit::cb_bit_6_c,
+        0x72 => &bit::cb_bit_6_d,
+        0x73 => &bit::cb_bit_6_e,
+        0x74 => &bit::cb_bit_6_h,
+        0x75 => &bit::cb_bit_6_l,
+        0x76 => &bit::cb_bit_6_hl_dref,
+        0x77 => &bit::cb_bit_6_a,
+
+        0x78 => &bit::cb_bit_7_b,
+        0x79 => &bit::cb_bit_7_c,
+        0x7A => &bit::cb_bit_7_d,
+        0x7B => &bit::cb_bit_7_e,
+        0x7C => &bit::cb_bit_7_h,
+        0x7D => &bit::cb_bit_7_l,
+        0x7E => &bit::cb_bit_7_hl_dref,
+        0x7F => &bit::cb_bit_7_a,
+
+        0x80 => &bit::cb_res_0_b,
+        0x81 => &bit::cb_res_0_c,
+        0x82 => &bit::cb_res_0_d,
+        0x83 => &bit::cb_res_0_e,
+        0x84 => &bit::cb_res_0_h,
+        0x85 => &bit::cb_res_0_l,
+        0x86 => &bit::cb_res_0_hl_dref,
+        0x87 => &bit::cb_res_0_a,
+
+        0x88 => &bit::cb_res_1_b,
+        0x89 => &bit::cb_res_1_c,
+        0x8A => &bit::cb_res_1_d,
+        0x8B => &bit::cb_res_1_e,
+        0x8C => &bit::cb_res_1_h,
+        0x8D => &bit::cb_res_1_l,
+        0x8E => &bit::cb_res_1_hl_dref,
+        0x8F => &bit::cb_res_1_a,
+
+        0x90 => &bit::cb_res_2_b,
+        0x91 => &bit::cb_res_2_c,
+        0x92 => &bit::cb_res_2_d,
+        0x93 => &bit::cb_res_2_e,
+        0x94 => &bit::cb_res_2_h,
+        0x95 => &bit::cb_res_2_l,
+        0x96 => &bit::cb_res_2_hl_dref,
+        0x97 => &bit::cb_res_2_a,
+
+        0x98 => &bit::cb_res_3_b,
+        0x99 => &bit::cb_res_3_c,
+        0x9A => &bit::cb_res_3_d,
+        0x9B => &bit::cb_res_3_e,
+        0x9C => &bit::cb_res_3_h,
+        0x9D => &bit::cb_res_3_l,
+        0x9E => &bit::cb_res_3_hl_dref,
+        0x9F => &bit::cb_res_3_a,
+
+        0xA0 => &bit::cb_res_4_b,
+        0xA1 => &bit::cb_res_4_c,
+        0xA2 => &bit::cb_res_4_d,
+        0xA3 => &bit::cb_res_4_e,
+        0xA4 => &bit::cb_res_4_h,
+        0xA5 => &bit::cb_res_4_l,
+        0xA6 => &bit::cb_res_4_hl_dref,
+        0xA7 => &bit::cb_res_4_a,
+
+        0xA8 => &bit::cb_res_5_b,
+        0xA9 => &bit::cb_res_5_c,
+        0xAA => &bit::cb_res_5_d,
+        0xAB => &bit::cb_res_5_e,
+        0xAC => &bit::cb_res_5_h,
+        0xAD => &bit::cb_res_5_l,
+        0xAE => &bit::cb_res_5_hl_dref,
+        0xAF => &bit::cb_res_5_a,
+
+        0xB0 => &bit::cb_res_6_b,
+        0xB1 => &bit::cb_res_6_c,
+        0xB2 => &bit::cb_res_6_d,
+        0xB3 => &bit::cb_res_6_e,
+        0xB4 => &bit::cb_res_6_h,
+        0xB5 => &bit::cb_res_6_l,
+        0xB6 => &bit::cb_res_6_hl_dref,
+        0xB7 => &bit::cb_res_6_a,
+
+        0xB8 => &bit::cb_res_7_b,
+        0xB9 => &bit::cb_res_7_c,
+        0xBA => &bit::cb_res_7_d,
+        0xBB => &bit::cb_res_7_e,
+        0xBC => &bit::cb_res_7_h,
+        0xBD => &bit::cb_res_7_l,
+        0xBE => &bit::cb_res_7_hl_dref,
+        0xBF => &bit::cb_res_7_a,
+
+        0xC0 => &bit::cb_set_0_b,
+        0xC1 => &bit::cb_set_0_c,
+        0xC2 => &bit::cb_set_0_d,
+        0xC3 => &bit::cb_set_0_e,
+        0xC4 => &bit::cb_set_0_h,
+        0xC5 => &bit::cb_set_0_l,
+        0xC6 => &bit::cb_set_0_hl_dref,
+        0xC7 => &bit::cb_set_0_a,
+
+        0xC8 => &bit::cb_set_1_b,
+        0xC9 => &bit::cb_set_1_c,
+        0xCA => &bit::cb_set_1_d,
+        0xCB => &bit::cb_set_1_e,
+        0xCC => &bit::cb_set_1_h,
+        0xCD => &bit::cb_set_1_l,
+        0xCE => &bit::cb_set_1_hl_dref,
+        0xCF => &bit::cb_set_1_a,
+
+        0xD0 => &bit::cb_set_2_b,
+        0xD1 => &bit::cb_set_2_c,
+        0xD2 => &bit::cb_set_2_d,
+        0xD3 => &bit::cb_set_2_e,
+        0xD4 => &bit::cb_set_2_h,
+        0xD5 => &bit::cb_set_2_l,
+        0xD6 => &bit::cb_set_2_hl_dref,
+        0xD7 => &bit::cb_set_2_a,
+
+        0xD8 => &bit::cb_set_3_b,
+        0xD9 => &bit::cb_set_3_c,
+        0xDA => &bit::cb_set_3_d,
+        0xDB => &bit::cb_set_3_e,
+        0xDC => &bit::cb_set_3_h,
+        0xDD => &bit::cb_set_3_l,
+        0xDE => &bit::cb_set_3_hl_dref,
+        0xDF => &bit::cb_set_3_a,
+
+        0xE0 => &bit::cb_set_4_b,
+        0xE1 => &bit::cb_set_4_c,
+        0xE2 => &bit::cb_set_4_d,
+        0xE3 => &bit::cb_set_4_e,
+        0xE4 => &bit::cb_set_4_h,
+        0xE5 => &bit::cb_set_4_l,
+        0xE6 => &bit::cb_set_4_hl_dref,
+        0xE7 => &bit::cb_set_4_a,
+
+        0xE8 => &bit::cb_set_5_b,
+        0xE9 => &bit::cb_set_5_c,
+        0xEA => &bit::cb_set_5_d,
+        0xEB => &bit::cb_set_5_e,
+        0xEC => &bit::cb_set_5_h,
+        0xED => &bit::cb_set_5_l,
+        0xEE => &bit::cb_set_5_hl_dref,
+        0xEF => &bit::cb_set_5_a,
+
+        0xF0 => &bit::cb_set_6_b,
+        0xF1 => &bit::cb_set_6_c,
+        0xF2 => &bit::cb_set_6_d,
+        0xF3 => &bit::cb_set_6_e,
+        0xF4 => &bit::cb_set_6_h,
+        0xF5 => &bit::cb_set_6_l,
+        0xF6 => &bit::cb_set_6_hl_dref,
+        0xF7 => &bit::cb_set_6_a,
+
+        0xF8 => &bit::cb_set_7_b,
+        0xF9 => &bit::cb_set_7_c,
+        0xFA => &bit::cb_set_7_d,
+        0xFB => &bit::cb_set_7_e,
+        0xFC => &bit::cb_set_7_h,
+        0xFD => &bit::cb_set_7_l,
+        0xFE => &bit::cb_set_7_hl_dref,
+        0xFF => &bit::cb_set_7_a,
     };
 
-    cb_fn(cpu)
+    // The prefix byte itself is part of the instruction length.
+    let (cycle, OpLength(len)) = cb_fn(cpu);
+
+    (cycle, OpLength(len + 1))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{is_illegal_op_code, op_table};
+
+    use crate::cpu::Cpu;
+
+    #[test]
+    fn run_every_legal_op_code() {
+        let legal_op_codes = (0x00..=0xFF).filter(|op| !is_illegal_op_code(*op));
+
+        for op_code in legal_op_codes {
+            let mut cpu = Cpu::default();
+            cpu.registers.set_pc(0x0100);
+            cpu.registers.set_sp(0xDFF0);
+            cpu.registers.set_hl(0xC000);
+
+            op_table(op_code)(&mut cpu);
+        }
+    }
 }
@@ -4,13 +4,13 @@ use crate::opcode::table::{Cycle, OpLength};
 use crate::registers::Registers;
 
 pub type InstructionResult = (Cycle, OpLength);
-pub type Instruction = Fn(&mut Cpu) -> InstructionResult;
+pub type Instruction = dyn Fn(&mut Cpu) -> InstructionResult;
 
-pub type LoadFromFn<S> = Fn(&Cpu) -> mmu::Result<S>;
-pub type StoreToFn<S> = Fn(&mut Cpu, S) -> mmu::Result<()>;
+pub type LoadFromFn<S> = dyn Fn(&Cpu) -> mmu::Result<S>;
+pub type StoreToFn<S> = dyn Fn(&mut Cpu, S) -> mmu::Result<()>;
 
-pub type LoadFromRegFn<S> = Fn(&Registers) -> S;
-pub type StoreToRegFn<S> = Fn(&mut Registers, S);
+pub type LoadFromRegFn<S> = dyn Fn(&Registers) -> S;
+pub type StoreToRegFn<S> = dyn Fn(&mut Registers, S);
 
 pub type LoadByteFromRegFn = LoadFromRegFn<u8>;
 pub type StoreByteToRegFn = StoreToRegFn<u8>;
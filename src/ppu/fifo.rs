@@ -0,0 +1,440 @@
+//! Pixel FIFO renderer, stepping the background fetcher, the sprite fetcher
+//! and both FIFOs one dot at a time so writes made during mode 3 land on
+//! the pixels they affect on hardware.
+
+use std::collections::VecDeque;
+
+use super::scanline::{OBJ_BEHIND_BG, OBJ_PALETTE};
+use super::*;
+
+/// Dots of the first background fetch of a line, which is thrown away.
+const DISCARDED_FETCH_DOTS: u8 = 6;
+/// Dots the sprite fetcher takes once the background fetcher let it in.
+const SPRITE_FETCH_DOTS: u8 = 6;
+/// Background fetcher dot from which a pending sprite may take over.
+const SPRITE_FETCH_READY_DOT: u8 = 4;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct ObjPixel {
+    color: u8,
+    obp1: bool,
+    behind_bg: bool,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct PixelFifo {
+    bg: VecDeque<u8>,
+    obj: VecDeque<ObjPixel>,
+
+    /// Dots into the current background fetch, 1 to 6 fetching, then
+    /// waiting for room to push.
+    fetcher_dot: u8,
+    /// Tile column the background fetcher works on, relative to the
+    /// scroll or to the window's left edge.
+    fetcher_x: u8,
+    tile: u8,
+    low: u8,
+    high: u8,
+
+    /// Dots left before the fetcher starts for real.
+    stall: u8,
+    /// Pixels the fine scroll still throws away.
+    discard: u8,
+    /// Pixels shifted out to the LCD so far.
+    lcd_x: u8,
+
+    /// Sprites picked by the OAM scan, in fetch order.
+    sprites: Vec<Sprite>,
+    /// The sprite being fetched and the dots spent on it.
+    sprite_fetch: Option<(Sprite, u8)>,
+
+    /// LY matched WY at some line this frame.
+    wy_triggered: bool,
+    window_active: bool,
+    /// LCD column the window last started at, it only re-triggers at a
+    /// different one.
+    window_start: Option<u8>,
+    window_drawn: bool,
+}
+
+impl Ppu {
+    /// Resets the FIFO renderer for mode 3 of the current line.
+    pub(super) fn start_fifo_line(&mut self) {
+        let sprites = self.select_sprites();
+        let fifo = &mut self.fifo;
+
+        if self.ly == 0 {
+            fifo.wy_triggered = false;
+        }
+        fifo.wy_triggered |= self.ly == self.wy;
+
+        fifo.bg.clear();
+        fifo.obj.clear();
+        fifo.fetcher_dot = 0;
+        fifo.fetcher_x = 0;
+        fifo.stall = DISCARDED_FETCH_DOTS;
+        fifo.discard = self.scx % 8;
+        fifo.lcd_x = 0;
+        fifo.sprites = sprites;
+        fifo.sprite_fetch = None;
+        fifo.window_active = false;
+        fifo.window_start = None;
+        fifo.window_drawn = false;
+    }
+
+    /// Runs mode 3 for one dot. Returns whether the line is complete.
+    pub(super) fn fifo_dot(&mut self) -> bool {
+        if self.fifo.stall > 0 {
+            self.fifo.stall -= 1;
+            return false;
+        }
+
+        if let Some((sprite, dots)) = self.fifo.sprite_fetch {
+            if dots + 1 < SPRITE_FETCH_DOTS {
+                self.fifo.sprite_fetch = Some((sprite, dots + 1));
+            } else {
+                self.fifo.sprite_fetch = None;
+                self.merge_sprite(&sprite);
+            }
+            return false;
+        }
+
+        if self.fifo.discard == 0 && self.pending_sprite().is_some() {
+            // The sprite fetch waits for the background fetcher to get far
+            // enough into its fetch, and for pixels to mix the sprite with.
+            if self.fifo.fetcher_dot >= SPRITE_FETCH_READY_DOT && !self.fifo.bg.is_empty() {
+                let sprite = self.fifo.sprites.remove(0);
+                self.fifo.sprite_fetch = Some((sprite, 1));
+            } else {
+                self.step_fetcher();
+            }
+            return false;
+        }
+
+        if self.fifo.discard == 0 && self.window_triggers() {
+            let fifo = &mut self.fifo;
+            fifo.window_active = true;
+            fifo.window_drawn = true;
+            fifo.window_start = Some(fifo.lcd_x);
+            fifo.bg.clear();
+            fifo.fetcher_dot = 0;
+            fifo.fetcher_x = 0;
+        }
+
+        self.step_fetcher();
+        self.shift_pixel();
+
+        usize::from(self.fifo.lcd_x) == SCREEN_WIDTH
+    }
+
+    /// Completes the line once mode 3 is over.
+    pub(super) fn finish_fifo_line(&mut self) {
+        if self.fifo.window_drawn {
+            self.window_line += 1;
+        }
+    }
+
+    /// The next sprite to fetch, once the LCD reached its left edge.
+    fn pending_sprite(&self) -> Option<&Sprite> {
+        if self.lcdc & LCDC_OBJ_ENABLE == 0 {
+            return None;
+        }
+
+        self.fifo
+            .sprites
+            .first()
+            .filter(|sprite| u16::from(sprite.x) <= u16::from(self.fifo.lcd_x) + 8)
+    }
+
+    fn window_triggers(&self) -> bool {
+        let fifo = &self.fifo;
+        if self.lcdc & LCDC_WINDOW_ENABLE == 0 || !fifo.wy_triggered {
+            return false;
+        }
+        if fifo.window_start == Some(fifo.lcd_x) {
+            return false;
+        }
+
+        // WX below 7 starts the window on the first pixel.
+        u16::from(fifo.lcd_x) + 7 == u16::from(self.wx.max(7))
+    }
+
+    fn step_fetcher(&mut self) {
+        self.fifo.fetcher_dot += 1;
+
+        match self.fifo.fetcher_dot {
+            2 => self.fifo.tile = self.fetch_tile_number(),
+            4 => self.fifo.low = self.fetch_tile_data(0),
+            6 => self.fifo.high = self.fetch_tile_data(1),
+            dot if dot >= 7 && self.fifo.bg.is_empty() => {
+                let fifo = &mut self.fifo;
+                for bit in (0..8).rev() {
+                    let color = (((fifo.high >> bit) & 1) << 1) | ((fifo.low >> bit) & 1);
+                    fifo.bg.push_back(color);
+                }
+                fifo.fetcher_dot = 0;
+                fifo.fetcher_x = fifo.fetcher_x.wrapping_add(1);
+            }
+            _ => {}
+        }
+    }
+
+    fn fetch_tile_number(&mut self) -> u8 {
+        // Turning the window off mid-line sends the fetcher back to the
+        // background.
+        if self.lcdc & LCDC_WINDOW_ENABLE == 0 {
+            self.fifo.window_active = false;
+        }
+
+        let (map, x, y) = if self.fifo.window_active {
+            (self.window_map(), self.fifo.fetcher_x, self.window_line)
+        } else {
+            let x = (self.scx / 8).wrapping_add(self.fifo.fetcher_x);
+            (self.bg_map(), x, self.ly.wrapping_add(self.scy))
+        };
+
+        self.vram[map + usize::from(y / 8) * 32 + usize::from(x & 0x1F)]
+    }
+
+    fn fetch_tile_data(&self, plane: usize) -> u8 {
+        let row = if self.fifo.window_active {
+            self.window_line % 8
+        } else {
+            self.ly.wrapping_add(self.scy) % 8
+        };
+
+        self.vram[self.bg_tile_addr(self.fifo.tile) + usize::from(row) * 2 + plane]
+    }
+
+    fn merge_sprite(&mut self, sprite: &Sprite) {
+        // Columns left of the screen are never shifted out.
+        let hidden = 8u8.saturating_sub(sprite.x);
+        let pixels: Vec<ObjPixel> = (hidden..8)
+            .map(|column| ObjPixel {
+                color: self
+                    .sprite_pixel(sprite, sprite.x + column - 8)
+                    .unwrap_or(0),
+                obp1: sprite.attributes & OBJ_PALETTE != 0,
+                behind_bg: sprite.attributes & OBJ_BEHIND_BG != 0,
+            })
+            .collect();
+
+        let fifo = &mut self.fifo;
+        for (index, pixel) in pixels.into_iter().enumerate() {
+            match fifo.obj.get_mut(index) {
+                // Earlier sprites keep their opaque pixels.
+                Some(existing) if existing.color != 0 => {}
+                Some(existing) => *existing = pixel,
+                None => fifo.obj.push_back(pixel),
+            }
+        }
+    }
+
+    fn shift_pixel(&mut self) {
+        let bg_color = match self.fifo.bg.pop_front() {
+            Some(color) => color,
+            None => return,
+        };
+
+        if self.fifo.discard > 0 {
+            self.fifo.discard -= 1;
+            return;
+        }
+
+        // Enables and palettes are read as the pixel leaves, so writes
+        // during mode 3 only affect the pixels after them.
+        let bg_color = if self.lcdc & LCDC_BG_ENABLE != 0 {
+            bg_color
+        } else {
+            0
+        };
+        let mut pixel_shade = shade(self.bgp, bg_color);
+
+        if let Some(obj) = self.fifo.obj.pop_front() {
+            let visible = self.lcdc & LCDC_OBJ_ENABLE != 0 && obj.color != 0;
+            if visible && (!obj.behind_bg || bg_color == 0) {
+                let palette = if obj.obp1 { self.obp1 } else { self.obp0 };
+                pixel_shade = shade(palette, obj.color);
+            }
+        }
+
+        let row = usize::from(self.ly) * SCREEN_WIDTH;
+        self.framebuffer[row + usize::from(self.fifo.lcd_x)] = pixel_shade;
+        self.fifo.lcd_x += 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::scanline::{BG_MAP_HIGH, BG_MAP_LOW, OBJ_FLIP_X, OBJ_FLIP_Y};
+    use super::*;
+
+    const IDENTITY_PALETTE: u8 = 0b1110_0100;
+
+    fn ppu_with(renderer: Renderer, lcdc: u8) -> Ppu {
+        let mut ppu = Ppu::new(renderer);
+        ppu.write_register(BGP_ADDR, IDENTITY_PALETTE);
+        ppu.write_register(OBP0_ADDR, IDENTITY_PALETTE);
+        ppu.write_register(OBP1_ADDR, 0b0001_1011);
+        ppu.write_register(LCDC_ADDR, LCDC_LCD_ENABLE | lcdc);
+
+        ppu
+    }
+
+    /// Fills tile `tile` of the 0x8000 area with colour `color`.
+    fn fill_tile(ppu: &mut Ppu, tile: usize, color: u8) {
+        let low = if color & 1 != 0 { 0xFF } else { 0 };
+        let high = if color & 2 != 0 { 0xFF } else { 0 };
+        for row in 0..8 {
+            ppu.vram[tile * 16 + row * 2] = low;
+            ppu.vram[tile * 16 + row * 2 + 1] = high;
+        }
+    }
+
+    fn set_sprite(ppu: &mut Ppu, index: usize, y: u8, x: u8, tile: u8, attributes: u8) {
+        ppu.oam[index * 4..index * 4 + 4].copy_from_slice(&[y, x, tile, attributes]);
+    }
+
+    fn pixel(ppu: &Ppu, x: usize) -> u8 {
+        ppu.framebuffer()[x]
+    }
+
+    /// Dots mode 3 of the first line lasts.
+    fn first_drawing_dots(ppu: &mut Ppu) -> u32 {
+        ppu.tick(OAM_SCAN_DOTS);
+
+        let mut dots = 0;
+        while ppu.mode() == Mode::Drawing {
+            ppu.tick(1);
+            dots += 1;
+        }
+
+        dots
+    }
+
+    #[test]
+    fn matches_scanline_renderer() {
+        let lcdc = LCDC_BG_ENABLE
+            | LCDC_OBJ_ENABLE
+            | LCDC_WINDOW_ENABLE
+            | LCDC_WINDOW_MAP
+            | LCDC_TILE_DATA;
+        let mut scanline = ppu_with(Renderer::Scanline, lcdc);
+        for (index, byte) in scanline.vram.iter_mut().enumerate() {
+            *byte = (index * 37 + index / 97) as u8;
+        }
+        scanline.write_register(SCX_ADDR, 0x2B);
+        scanline.write_register(SCY_ADDR, 0x13);
+        scanline.write_register(WX_ADDR, 7 + 100);
+        scanline.write_register(WY_ADDR, 60);
+        set_sprite(&mut scanline, 0, 20, 4, 3, 0);
+        set_sprite(&mut scanline, 1, 30, 50, 7, OBJ_PALETTE | OBJ_FLIP_X);
+        set_sprite(&mut scanline, 2, 30, 54, 9, OBJ_BEHIND_BG);
+        set_sprite(&mut scanline, 3, 100, 120, 11, OBJ_FLIP_Y);
+
+        let mut fifo = Ppu {
+            renderer: Renderer::PixelFifo,
+            ..scanline.clone()
+        };
+
+        scanline.tick(DOTS_PER_LINE * u32::from(LINES_PER_FRAME));
+        fifo.tick(DOTS_PER_LINE * u32::from(LINES_PER_FRAME));
+
+        assert_eq!(fifo.framebuffer(), scanline.framebuffer());
+        assert_eq!(fifo.ly(), scanline.ly());
+    }
+
+    #[test]
+    fn mode_3_length_matches_timing_model() {
+        let configure: [fn(&mut Ppu); 5] = [
+            |_| {},
+            |ppu| {
+                ppu.write_register(SCX_ADDR, 5);
+            },
+            |ppu| {
+                ppu.write_register(LCDC_ADDR, ppu.lcdc | LCDC_WINDOW_ENABLE);
+                ppu.write_register(WX_ADDR, 7 + 50);
+            },
+            |ppu| {
+                set_sprite(ppu, 0, 16, 8, 0, 0);
+                set_sprite(ppu, 1, 16, 10, 0, 0);
+                set_sprite(ppu, 2, 16, 30, 0, 0);
+            },
+            |ppu| {
+                ppu.write_register(SCX_ADDR, 3);
+                set_sprite(ppu, 0, 16, 64, 0, 0);
+                set_sprite(ppu, 1, 16, 100, 0, 0);
+            },
+        ];
+
+        for configure in configure.iter() {
+            let mut ppu = ppu_with(Renderer::PixelFifo, LCDC_BG_ENABLE | LCDC_OBJ_ENABLE);
+            configure(&mut ppu);
+            let expected = ppu.drawing_dots();
+
+            assert_eq!(first_drawing_dots(&mut ppu), expected);
+        }
+    }
+
+    #[test]
+    fn mid_scanline_palette_write() {
+        let mut ppu = ppu_with(Renderer::PixelFifo, LCDC_BG_ENABLE | LCDC_TILE_DATA);
+        fill_tile(&mut ppu, 0, 3);
+
+        // 12 dots of fetching, then a pixel per dot.
+        ppu.tick(OAM_SCAN_DOTS + 12 + 50);
+        ppu.write_register(BGP_ADDR, 0);
+        ppu.tick(DOTS_PER_LINE);
+
+        assert_eq!(pixel(&ppu, 0), 3);
+        assert_eq!(pixel(&ppu, 49), 3);
+        assert_eq!(pixel(&ppu, 50), 0);
+        assert_eq!(pixel(&ppu, 159), 0);
+    }
+
+    #[test]
+    fn mid_scanline_scx_write() {
+        let mut ppu = ppu_with(Renderer::PixelFifo, LCDC_BG_ENABLE | LCDC_TILE_DATA);
+        fill_tile(&mut ppu, 1, 3);
+        for column in 12..32 {
+            ppu.vram[BG_MAP_LOW + column] = 1;
+        }
+
+        ppu.tick(OAM_SCAN_DOTS + 12 + 40);
+        ppu.write_register(SCX_ADDR, 8 * 8);
+        ppu.tick(DOTS_PER_LINE);
+
+        assert_eq!(pixel(&ppu, 0), 0);
+        assert_eq!(pixel(&ppu, 39), 0);
+        assert_eq!(pixel(&ppu, 159), 3);
+    }
+
+    #[test]
+    fn window_retrigger() {
+        let mut ppu = ppu_with(
+            Renderer::PixelFifo,
+            LCDC_BG_ENABLE | LCDC_TILE_DATA | LCDC_WINDOW_ENABLE | LCDC_WINDOW_MAP,
+        );
+        fill_tile(&mut ppu, 1, 3);
+        fill_tile(&mut ppu, 2, 1);
+        ppu.vram[BG_MAP_HIGH] = 1;
+        for column in 1..32 {
+            ppu.vram[BG_MAP_HIGH + column] = 2;
+        }
+        ppu.write_register(WX_ADDR, 7 + 40);
+
+        ppu.tick(OAM_SCAN_DOTS + 12 + 60 + WINDOW_PENALTY);
+        ppu.write_register(WX_ADDR, 7 + 100);
+        ppu.tick(DOTS_PER_LINE);
+
+        assert_eq!(pixel(&ppu, 39), 0);
+        assert_eq!(pixel(&ppu, 40), 3);
+        assert_eq!(pixel(&ppu, 47), 3);
+        assert_eq!(pixel(&ppu, 48), 1);
+        assert_eq!(pixel(&ppu, 99), 1);
+        assert_eq!(pixel(&ppu, 100), 3);
+        assert_eq!(pixel(&ppu, 107), 3);
+        assert_eq!(pixel(&ppu, 108), 1);
+        assert_eq!(ppu.window_line, 1);
+    }
+}
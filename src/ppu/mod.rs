@@ -0,0 +1,550 @@
+//! Picture processing unit, owning VRAM, OAM and the LCD registers and
+//! drawing into a 160x144 framebuffer.
+
+mod fifo;
+mod scanline;
+mod timing;
+
+pub use self::scanline::{shade, Sprite, SPRITES_PER_LINE};
+pub use self::timing::{SPRITE_PENALTY, WINDOW_PENALTY};
+
+use self::fifo::PixelFifo;
+use crate::interrupt::Interrupt;
+use crate::mmu::{Addr, OAM_SIZE, VRAM_SIZE};
+
+pub const SCREEN_WIDTH: usize = 160;
+pub const SCREEN_HEIGHT: usize = 144;
+
+pub const LCDC_ADDR: Addr = 0xFF40;
+pub const STAT_ADDR: Addr = 0xFF41;
+pub const SCY_ADDR: Addr = 0xFF42;
+pub const SCX_ADDR: Addr = 0xFF43;
+pub const LY_ADDR: Addr = 0xFF44;
+pub const LYC_ADDR: Addr = 0xFF45;
+pub const BGP_ADDR: Addr = 0xFF47;
+pub const OBP0_ADDR: Addr = 0xFF48;
+pub const OBP1_ADDR: Addr = 0xFF49;
+pub const WY_ADDR: Addr = 0xFF4A;
+pub const WX_ADDR: Addr = 0xFF4B;
+
+pub const LCDC_BG_ENABLE: u8 = 0b0000_0001;
+pub const LCDC_OBJ_ENABLE: u8 = 0b0000_0010;
+pub const LCDC_OBJ_SIZE: u8 = 0b0000_0100;
+pub const LCDC_BG_MAP: u8 = 0b0000_1000;
+pub const LCDC_TILE_DATA: u8 = 0b0001_0000;
+pub const LCDC_WINDOW_ENABLE: u8 = 0b0010_0000;
+pub const LCDC_WINDOW_MAP: u8 = 0b0100_0000;
+pub const LCDC_LCD_ENABLE: u8 = 0b1000_0000;
+
+pub const STAT_LYC_EQUAL: u8 = 0b0000_0100;
+pub const STAT_HBLANK_INTERRUPT: u8 = 0b0000_1000;
+pub const STAT_VBLANK_INTERRUPT: u8 = 0b0001_0000;
+pub const STAT_OAM_INTERRUPT: u8 = 0b0010_0000;
+pub const STAT_LYC_INTERRUPT: u8 = 0b0100_0000;
+/// The interrupt enable bits, the only ones the CPU can write.
+const STAT_WRITABLE: u8 = 0b0111_1000;
+
+pub const DOTS_PER_LINE: u32 = 456;
+pub const OAM_SCAN_DOTS: u32 = 80;
+/// Mode 3 length without fine scroll, window or sprites.
+pub const DRAWING_DOTS: u32 = 172;
+pub const LINES_PER_FRAME: u8 = 154;
+
+/// The DMG shades as RGBA, from lightest to darkest.
+pub const DMG_SHADES: [[u8; 4]; 4] = [
+    [0xFF, 0xFF, 0xFF, 0xFF],
+    [0xAA, 0xAA, 0xAA, 0xFF],
+    [0x55, 0x55, 0x55, 0xFF],
+    [0x00, 0x00, 0x00, 0xFF],
+];
+
+/// How mode 3 turns VRAM into pixels.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Renderer {
+    /// Draws each line in one go at the end of mode 3, with the mode 3
+    /// length computed up front. Writes made during mode 3 are not seen.
+    #[default]
+    Scanline,
+    /// Steps the fetchers and pixel FIFOs dot by dot, slower but exact for
+    /// raster effects within a line.
+    PixelFifo,
+}
+
+/// The STAT mode, numbered as the low 2 bits of STAT read.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Mode {
+    HBlank = 0,
+    VBlank = 1,
+    OamScan = 2,
+    Drawing = 3,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Ppu {
+    renderer: Renderer,
+    fifo: PixelFifo,
+
+    vram: [u8; VRAM_SIZE],
+    oam: [u8; OAM_SIZE],
+
+    lcdc: u8,
+    /// Only the interrupt enable bits, the rest is computed on read.
+    stat: u8,
+    scy: u8,
+    scx: u8,
+    ly: u8,
+    lyc: u8,
+    bgp: u8,
+    obp0: u8,
+    obp1: u8,
+    wy: u8,
+    wx: u8,
+
+    mode: Mode,
+    /// Dots spent in the current line.
+    dot: u32,
+    /// Length of mode 3 on the current line, known once OAM scan ends.
+    drawing_dots: u32,
+    /// The OR of every enabled STAT source, the interrupt is requested on
+    /// its rising edge only.
+    stat_line: bool,
+    /// Lines of the window drawn so far this frame.
+    window_line: u8,
+
+    /// One shade, 0 to 3, per pixel.
+    framebuffer: Vec<u8>,
+    frame_ready: bool,
+}
+
+impl Default for Ppu {
+    fn default() -> Self {
+        Self {
+            renderer: Renderer::default(),
+            fifo: PixelFifo::default(),
+            vram: [0; VRAM_SIZE],
+            oam: [0; OAM_SIZE],
+            lcdc: 0,
+            stat: 0,
+            scy: 0,
+            scx: 0,
+            ly: 0,
+            lyc: 0,
+            bgp: 0,
+            obp0: 0,
+            obp1: 0,
+            wy: 0,
+            wx: 0,
+            mode: Mode::HBlank,
+            dot: 0,
+            drawing_dots: DRAWING_DOTS,
+            stat_line: false,
+            window_line: 0,
+            framebuffer: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            frame_ready: false,
+        }
+    }
+}
+
+impl Ppu {
+    pub fn new(renderer: Renderer) -> Self {
+        Self {
+            renderer,
+            ..Self::default()
+        }
+    }
+
+    pub fn renderer(&self) -> Renderer {
+        self.renderer
+    }
+
+    /// Whether `addr` is one of the LCD registers, DMA excluded.
+    pub fn is_register(addr: Addr) -> bool {
+        matches!(addr, LCDC_ADDR..=LYC_ADDR | BGP_ADDR..=WX_ADDR)
+    }
+
+    pub fn read_vram(&self, offset: Addr) -> u8 {
+        self.vram[usize::from(offset)]
+    }
+
+    pub fn write_vram(&mut self, offset: Addr, value: u8) {
+        self.vram[usize::from(offset)] = value;
+    }
+
+    pub fn read_oam(&self, offset: Addr) -> u8 {
+        self.oam[usize::from(offset)]
+    }
+
+    pub fn write_oam(&mut self, offset: Addr, value: u8) {
+        self.oam[usize::from(offset)] = value;
+    }
+
+    pub fn read_register(&self, addr: Addr) -> u8 {
+        match addr {
+            LCDC_ADDR => self.lcdc,
+            STAT_ADDR => self.read_stat(),
+            SCY_ADDR => self.scy,
+            SCX_ADDR => self.scx,
+            LY_ADDR => self.ly,
+            LYC_ADDR => self.lyc,
+            BGP_ADDR => self.bgp,
+            OBP0_ADDR => self.obp0,
+            OBP1_ADDR => self.obp1,
+            WY_ADDR => self.wy,
+            WX_ADDR => self.wx,
+            _ => unreachable!("{:#06X} is not an LCD register", addr),
+        }
+    }
+
+    /// Returns the interrupts to raise in IF.
+    pub fn write_register(&mut self, addr: Addr, value: u8) -> u8 {
+        match addr {
+            LCDC_ADDR => return self.write_lcdc(value),
+            STAT_ADDR => {
+                self.stat = value & STAT_WRITABLE;
+                return self.update_stat_line();
+            }
+            SCY_ADDR => self.scy = value,
+            SCX_ADDR => self.scx = value,
+            // LY is read-only.
+            LY_ADDR => {}
+            LYC_ADDR => {
+                self.lyc = value;
+                return self.update_stat_line();
+            }
+            BGP_ADDR => self.bgp = value,
+            OBP0_ADDR => self.obp0 = value,
+            OBP1_ADDR => self.obp1 = value,
+            WY_ADDR => self.wy = value,
+            WX_ADDR => self.wx = value,
+            _ => unreachable!("{:#06X} is not an LCD register", addr),
+        }
+
+        0
+    }
+
+    fn read_stat(&self) -> u8 {
+        let lyc_equal = if self.ly == self.lyc {
+            STAT_LYC_EQUAL
+        } else {
+            0
+        };
+
+        0b1000_0000 | self.stat | lyc_equal | self.mode as u8
+    }
+
+    fn write_lcdc(&mut self, value: u8) -> u8 {
+        let was_enabled = self.lcd_enabled();
+        self.lcdc = value;
+
+        match (was_enabled, self.lcd_enabled()) {
+            (true, false) => {
+                self.ly = 0;
+                self.dot = 0;
+                self.window_line = 0;
+                self.mode = Mode::HBlank;
+                self.stat_line = false;
+                0
+            }
+            (false, true) => {
+                self.mode = Mode::OamScan;
+                self.update_stat_line()
+            }
+            _ => 0,
+        }
+    }
+
+    pub fn lcd_enabled(&self) -> bool {
+        self.lcdc & LCDC_LCD_ENABLE != 0
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    pub fn ly(&self) -> u8 {
+        self.ly
+    }
+
+    /// The CPU reads 0xFF from VRAM and can not write it while the PPU is
+    /// drawing.
+    pub fn vram_locked(&self) -> bool {
+        self.mode == Mode::Drawing
+    }
+
+    /// The CPU reads 0xFF from OAM and can not write it while the PPU is
+    /// scanning or drawing.
+    pub fn oam_locked(&self) -> bool {
+        self.mode == Mode::OamScan || self.mode == Mode::Drawing
+    }
+
+    /// The last frame as one shade, 0 to 3, per pixel, row by row.
+    pub fn framebuffer(&self) -> &[u8] {
+        &self.framebuffer
+    }
+
+    /// The last frame as RGBA bytes using `DMG_SHADES`.
+    pub fn rgba_framebuffer(&self) -> Vec<u8> {
+        self.framebuffer
+            .iter()
+            .flat_map(|shade| DMG_SHADES[usize::from(*shade)].iter().cloned())
+            .collect()
+    }
+
+    /// Whether a frame was completed since the last call.
+    pub fn take_frame_ready(&mut self) -> bool {
+        let ready = self.frame_ready;
+        self.frame_ready = false;
+
+        ready
+    }
+
+    /// Advances by `dots`, the PPU clock which does not follow the CPU into
+    /// double speed. Returns the interrupts to raise in IF.
+    pub fn tick(&mut self, dots: u32) -> u8 {
+        if !self.lcd_enabled() {
+            return 0;
+        }
+
+        let mut interrupts = 0;
+        for _ in 0..dots {
+            interrupts |= self.tick_dot();
+        }
+
+        interrupts
+    }
+
+    fn tick_dot(&mut self) -> u8 {
+        self.dot += 1;
+
+        let interrupts = match self.mode {
+            Mode::OamScan if self.dot == OAM_SCAN_DOTS => {
+                match self.renderer {
+                    Renderer::Scanline => self.drawing_dots = self.drawing_dots(),
+                    Renderer::PixelFifo => self.start_fifo_line(),
+                }
+                self.mode = Mode::Drawing;
+                0
+            }
+            Mode::Drawing if self.renderer == Renderer::PixelFifo => {
+                if self.fifo_dot() {
+                    self.finish_fifo_line();
+                    self.mode = Mode::HBlank;
+                }
+                0
+            }
+            Mode::Drawing if self.dot == OAM_SCAN_DOTS + self.drawing_dots => {
+                self.render_scanline();
+                self.mode = Mode::HBlank;
+                0
+            }
+            Mode::HBlank | Mode::VBlank if self.dot == DOTS_PER_LINE => self.next_line(),
+            _ => 0,
+        };
+
+        interrupts | self.update_stat_line()
+    }
+
+    fn next_line(&mut self) -> u8 {
+        self.dot = 0;
+        self.ly += 1;
+
+        match self.ly {
+            144 => {
+                self.frame_ready = true;
+                self.mode = Mode::VBlank;
+                return Interrupt::VBlank.bit();
+            }
+            LINES_PER_FRAME => {
+                self.ly = 0;
+                self.window_line = 0;
+                self.mode = Mode::OamScan;
+            }
+            ly if ly < 144 => self.mode = Mode::OamScan,
+            _ => {}
+        }
+
+        0
+    }
+
+    fn stat_sources(&self) -> u8 {
+        let mode_source = match self.mode {
+            Mode::HBlank => STAT_HBLANK_INTERRUPT,
+            // Entering VBlank also triggers the OAM source, as line 144
+            // starts like any other line.
+            Mode::VBlank if self.ly == 144 && self.dot == 0 => {
+                STAT_VBLANK_INTERRUPT | STAT_OAM_INTERRUPT
+            }
+            Mode::VBlank => STAT_VBLANK_INTERRUPT,
+            Mode::OamScan => STAT_OAM_INTERRUPT,
+            Mode::Drawing => 0,
+        };
+        let lyc_source = if self.ly == self.lyc {
+            STAT_LYC_INTERRUPT
+        } else {
+            0
+        };
+
+        mode_source | lyc_source
+    }
+
+    /// Re-evaluates the STAT line, returning LcdStat on a rising edge. A
+    /// source becoming true while another one already holds the line high
+    /// is blocked.
+    fn update_stat_line(&mut self) -> u8 {
+        let line = self.lcd_enabled() && self.stat & self.stat_sources() != 0;
+        let rising = line && !self.stat_line;
+        self.stat_line = line;
+
+        if rising {
+            Interrupt::LcdStat.bit()
+        } else {
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn enabled_ppu() -> Ppu {
+        let mut ppu = Ppu::default();
+        ppu.write_register(LCDC_ADDR, LCDC_LCD_ENABLE | LCDC_BG_ENABLE);
+
+        ppu
+    }
+
+    #[test]
+    fn mode_sequence_of_a_line() {
+        let mut ppu = enabled_ppu();
+        assert_eq!(ppu.mode(), Mode::OamScan);
+
+        ppu.tick(OAM_SCAN_DOTS);
+        assert_eq!(ppu.mode(), Mode::Drawing);
+
+        ppu.tick(DRAWING_DOTS);
+        assert_eq!(ppu.mode(), Mode::HBlank);
+
+        ppu.tick(DOTS_PER_LINE - OAM_SCAN_DOTS - DRAWING_DOTS);
+        assert_eq!(ppu.mode(), Mode::OamScan);
+        assert_eq!(ppu.ly(), 1);
+    }
+
+    #[test]
+    fn vblank_after_144_lines() {
+        let mut ppu = enabled_ppu();
+
+        let interrupts = ppu.tick(DOTS_PER_LINE * 144);
+
+        assert_eq!(ppu.ly(), 144);
+        assert_eq!(ppu.mode(), Mode::VBlank);
+        assert_eq!(interrupts, Interrupt::VBlank.bit());
+        assert!(ppu.take_frame_ready());
+        assert!(!ppu.take_frame_ready());
+
+        ppu.tick(DOTS_PER_LINE * 10);
+        assert_eq!(ppu.ly(), 0);
+        assert_eq!(ppu.mode(), Mode::OamScan);
+    }
+
+    #[test]
+    fn lcd_off_resets_ly() {
+        let mut ppu = enabled_ppu();
+        ppu.tick(DOTS_PER_LINE * 3);
+
+        ppu.write_register(LCDC_ADDR, 0);
+        ppu.tick(DOTS_PER_LINE);
+
+        assert_eq!(ppu.read_register(LY_ADDR), 0);
+        assert_eq!(ppu.read_register(STAT_ADDR) & 0b11, Mode::HBlank as u8);
+    }
+
+    #[test]
+    fn stat_read_and_write() {
+        let mut ppu = enabled_ppu();
+
+        ppu.write_register(STAT_ADDR, 0xFF);
+        ppu.write_register(LYC_ADDR, 1);
+
+        assert_eq!(ppu.read_register(STAT_ADDR), 0b1111_1010);
+
+        ppu.write_register(LY_ADDR, 0x42);
+        assert_eq!(ppu.read_register(LY_ADDR), 0);
+    }
+
+    #[test]
+    fn stat_interrupt_sources() {
+        let mut ppu = enabled_ppu();
+        ppu.write_register(STAT_ADDR, STAT_HBLANK_INTERRUPT);
+
+        assert_eq!(ppu.tick(OAM_SCAN_DOTS), 0);
+        assert_eq!(ppu.tick(DRAWING_DOTS), Interrupt::LcdStat.bit());
+
+        ppu.write_register(STAT_ADDR, STAT_LYC_INTERRUPT);
+        ppu.write_register(LYC_ADDR, 2);
+        assert_eq!(ppu.tick(DOTS_PER_LINE - OAM_SCAN_DOTS - DRAWING_DOTS), 0);
+        assert_eq!(ppu.tick(DOTS_PER_LINE), Interrupt::LcdStat.bit());
+        assert_ne!(ppu.read_register(STAT_ADDR) & STAT_LYC_EQUAL, 0);
+    }
+
+    #[test]
+    fn stat_blocking() {
+        let mut ppu = enabled_ppu();
+        ppu.write_register(LYC_ADDR, 0);
+
+        assert_eq!(
+            ppu.write_register(STAT_ADDR, STAT_LYC_INTERRUPT | STAT_HBLANK_INTERRUPT),
+            Interrupt::LcdStat.bit()
+        );
+        // LYC still holds the line high when HBlank starts.
+        assert_eq!(ppu.tick(OAM_SCAN_DOTS + DRAWING_DOTS), 0);
+        assert_eq!(ppu.mode(), Mode::HBlank);
+
+        // Line 1 HBlank after a low OAM scan and drawing.
+        ppu.tick(DOTS_PER_LINE - OAM_SCAN_DOTS - DRAWING_DOTS);
+        assert_eq!(
+            ppu.tick(OAM_SCAN_DOTS + DRAWING_DOTS),
+            Interrupt::LcdStat.bit()
+        );
+    }
+
+    #[test]
+    fn vblank_entry_triggers_oam_source() {
+        let mut ppu = enabled_ppu();
+        ppu.write_register(STAT_ADDR, STAT_OAM_INTERRUPT);
+        ppu.tick(DOTS_PER_LINE * 143 + OAM_SCAN_DOTS);
+
+        assert_eq!(
+            ppu.tick(DOTS_PER_LINE - OAM_SCAN_DOTS),
+            Interrupt::VBlank.bit() | Interrupt::LcdStat.bit()
+        );
+    }
+
+    #[test]
+    fn vram_and_oam_locking_follows_mode() {
+        let mut ppu = enabled_ppu();
+
+        assert!(ppu.oam_locked());
+        assert!(!ppu.vram_locked());
+
+        ppu.tick(OAM_SCAN_DOTS);
+        assert!(ppu.oam_locked());
+        assert!(ppu.vram_locked());
+
+        ppu.tick(DRAWING_DOTS);
+        assert!(!ppu.oam_locked());
+        assert!(!ppu.vram_locked());
+    }
+
+    #[test]
+    fn rgba_framebuffer_uses_dmg_shades() {
+        let mut ppu = Ppu::default();
+        ppu.framebuffer[1] = 3;
+
+        let rgba = ppu.rgba_framebuffer();
+
+        assert_eq!(rgba.len(), SCREEN_WIDTH * SCREEN_HEIGHT * 4);
+        assert_eq!(&rgba[0..4], &DMG_SHADES[0]);
+        assert_eq!(&rgba[4..8], &DMG_SHADES[3]);
+    }
+}
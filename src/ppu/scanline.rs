@@ -0,0 +1,380 @@
+//! Whole-line renderer, drawing the current line in one go at the end of
+//! mode 3.
+
+use super::*;
+
+/// Sprites the hardware selects per line.
+pub const SPRITES_PER_LINE: usize = 10;
+
+pub(super) const BG_MAP_LOW: usize = 0x1800;
+pub(super) const BG_MAP_HIGH: usize = 0x1C00;
+
+pub(super) const OBJ_PALETTE: u8 = 0b0001_0000;
+pub(super) const OBJ_FLIP_X: u8 = 0b0010_0000;
+pub(super) const OBJ_FLIP_Y: u8 = 0b0100_0000;
+pub(super) const OBJ_BEHIND_BG: u8 = 0b1000_0000;
+
+/// One OAM entry, with coordinates kept in the hardware's offset form.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Sprite {
+    pub index: u8,
+    pub y: u8,
+    pub x: u8,
+    pub tile: u8,
+    pub attributes: u8,
+}
+
+impl Ppu {
+    pub(super) fn sprite_height(&self) -> u8 {
+        if self.lcdc & LCDC_OBJ_SIZE != 0 {
+            16
+        } else {
+            8
+        }
+    }
+
+    /// The first ten sprites in OAM order covering the current line,
+    /// sorted by drawing priority: smallest X first, then OAM index.
+    pub(super) fn select_sprites(&self) -> Vec<Sprite> {
+        let height = self.sprite_height();
+        let line = u16::from(self.ly) + 16;
+
+        let mut sprites: Vec<Sprite> = self
+            .oam
+            .chunks(4)
+            .enumerate()
+            .map(|(index, entry)| Sprite {
+                index: index as u8,
+                y: entry[0],
+                x: entry[1],
+                tile: entry[2],
+                attributes: entry[3],
+            })
+            .filter(|sprite| {
+                let top = u16::from(sprite.y);
+                line >= top && line < top + u16::from(height)
+            })
+            .take(SPRITES_PER_LINE)
+            .collect();
+
+        sprites.sort_by_key(|sprite| (sprite.x, sprite.index));
+
+        sprites
+    }
+
+    /// Colour number, 0 to 3, of a pixel of a tile in VRAM.
+    pub(super) fn tile_pixel(&self, tile_addr: usize, row: u8, column: u8) -> u8 {
+        let low = self.vram[tile_addr + usize::from(row) * 2];
+        let high = self.vram[tile_addr + usize::from(row) * 2 + 1];
+        let bit = 7 - column;
+
+        (((high >> bit) & 1) << 1) | ((low >> bit) & 1)
+    }
+
+    /// VRAM offset of a background or window tile, honouring the signed
+    /// 0x9000 addressing mode.
+    pub(super) fn bg_tile_addr(&self, tile: u8) -> usize {
+        if self.lcdc & LCDC_TILE_DATA != 0 {
+            usize::from(tile) * 16
+        } else {
+            (0x1000 + i32::from(tile as i8) * 16) as usize
+        }
+    }
+
+    /// Colour number of the background or window at map coordinates.
+    pub(super) fn map_pixel(&self, map: usize, x: u8, y: u8) -> u8 {
+        let tile = self.vram[map + usize::from(y / 8) * 32 + usize::from(x / 8)];
+
+        self.tile_pixel(self.bg_tile_addr(tile), y % 8, x % 8)
+    }
+
+    pub(super) fn bg_map(&self) -> usize {
+        if self.lcdc & LCDC_BG_MAP != 0 {
+            BG_MAP_HIGH
+        } else {
+            BG_MAP_LOW
+        }
+    }
+
+    pub(super) fn window_map(&self) -> usize {
+        if self.lcdc & LCDC_WINDOW_MAP != 0 {
+            BG_MAP_HIGH
+        } else {
+            BG_MAP_LOW
+        }
+    }
+
+    pub(super) fn window_visible(&self) -> bool {
+        self.lcdc & LCDC_WINDOW_ENABLE != 0 && self.ly >= self.wy && self.wx <= 166
+    }
+
+    /// Colour number of `sprite` at screen column `x`, if it covers it.
+    pub(super) fn sprite_pixel(&self, sprite: &Sprite, x: u8) -> Option<u8> {
+        let left = i16::from(sprite.x) - 8;
+        let column = i16::from(x) - left;
+        if !(0..8).contains(&column) {
+            return None;
+        }
+
+        let height = self.sprite_height();
+        let mut row = self.ly + 16 - sprite.y;
+        if sprite.attributes & OBJ_FLIP_Y != 0 {
+            row = height - 1 - row;
+        }
+        let mut column = column as u8;
+        if sprite.attributes & OBJ_FLIP_X != 0 {
+            column = 7 - column;
+        }
+
+        let tile = if height == 16 {
+            sprite.tile & 0xFE
+        } else {
+            sprite.tile
+        };
+        let tile_addr = usize::from(tile) * 16;
+
+        Some(self.tile_pixel(tile_addr, row, column))
+    }
+
+    pub(super) fn sprite_palette(&self, sprite: &Sprite) -> u8 {
+        if sprite.attributes & OBJ_PALETTE != 0 {
+            self.obp1
+        } else {
+            self.obp0
+        }
+    }
+
+    /// Draws the current line into the framebuffer.
+    pub(super) fn render_scanline(&mut self) {
+        let window = self.window_visible();
+        let mut window_drawn = false;
+        let sprites = if self.lcdc & LCDC_OBJ_ENABLE != 0 {
+            self.select_sprites()
+        } else {
+            Vec::new()
+        };
+
+        let row = usize::from(self.ly) * SCREEN_WIDTH;
+        for x in 0..SCREEN_WIDTH as u8 {
+            let bg_color = if self.lcdc & LCDC_BG_ENABLE == 0 {
+                0
+            } else if window && u16::from(x) + 7 >= u16::from(self.wx) {
+                window_drawn = true;
+                let window_x = (u16::from(x) + 7 - u16::from(self.wx)) as u8;
+                self.map_pixel(self.window_map(), window_x, self.window_line)
+            } else {
+                self.map_pixel(
+                    self.bg_map(),
+                    x.wrapping_add(self.scx),
+                    self.ly.wrapping_add(self.scy),
+                )
+            };
+
+            let mut pixel_shade = shade(self.bgp, bg_color);
+            let sprite = sprites.iter().find_map(|sprite| {
+                self.sprite_pixel(sprite, x)
+                    .filter(|color| *color != 0)
+                    .map(|color| (sprite, color))
+            });
+            if let Some((sprite, color)) = sprite {
+                if sprite.attributes & OBJ_BEHIND_BG == 0 || bg_color == 0 {
+                    pixel_shade = shade(self.sprite_palette(sprite), color);
+                }
+            }
+
+            self.framebuffer[row + usize::from(x)] = pixel_shade;
+        }
+
+        if window_drawn {
+            self.window_line += 1;
+        }
+    }
+}
+
+/// The shade a palette register gives to colour number `color`.
+pub fn shade(palette: u8, color: u8) -> u8 {
+    (palette >> (color * 2)) & 0b11
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ppu_with(lcdc: u8) -> Ppu {
+        let mut ppu = Ppu::default();
+        ppu.write_register(BGP_ADDR, 0b1110_0100);
+        ppu.write_register(OBP0_ADDR, 0b1110_0100);
+        ppu.write_register(OBP1_ADDR, 0b0001_1011);
+        ppu.write_register(LCDC_ADDR, LCDC_LCD_ENABLE | lcdc);
+
+        ppu
+    }
+
+    /// Fills tile `tile` of the 0x8000 area with colour `color`.
+    fn fill_tile(ppu: &mut Ppu, tile: usize, color: u8) {
+        let low = if color & 1 != 0 { 0xFF } else { 0 };
+        let high = if color & 2 != 0 { 0xFF } else { 0 };
+        for row in 0..8 {
+            ppu.vram[tile * 16 + row * 2] = low;
+            ppu.vram[tile * 16 + row * 2 + 1] = high;
+        }
+    }
+
+    fn set_sprite(ppu: &mut Ppu, index: usize, y: u8, x: u8, tile: u8, attributes: u8) {
+        ppu.oam[index * 4..index * 4 + 4].copy_from_slice(&[y, x, tile, attributes]);
+    }
+
+    fn pixel(ppu: &Ppu, x: usize, y: usize) -> u8 {
+        ppu.framebuffer()[y * SCREEN_WIDTH + x]
+    }
+
+    fn run_frame(ppu: &mut Ppu) {
+        ppu.tick(DOTS_PER_LINE * u32::from(LINES_PER_FRAME));
+    }
+
+    #[test]
+    fn tile_pixel_combines_bit_planes() {
+        let mut ppu = Ppu::default();
+        ppu.vram[0] = 0b1010_0000;
+        ppu.vram[1] = 0b1100_0000;
+
+        assert_eq!(ppu.tile_pixel(0, 0, 0), 3);
+        assert_eq!(ppu.tile_pixel(0, 0, 1), 2);
+        assert_eq!(ppu.tile_pixel(0, 0, 2), 1);
+        assert_eq!(ppu.tile_pixel(0, 0, 3), 0);
+    }
+
+    #[test]
+    fn signed_tile_addressing() {
+        let mut ppu = ppu_with(0);
+
+        assert_eq!(ppu.bg_tile_addr(0), 0x1000);
+        assert_eq!(ppu.bg_tile_addr(0x80), 0x0800);
+
+        ppu.write_register(LCDC_ADDR, LCDC_LCD_ENABLE | LCDC_TILE_DATA);
+        assert_eq!(ppu.bg_tile_addr(0x80), 0x0800);
+        assert_eq!(ppu.bg_tile_addr(1), 0x0010);
+    }
+
+    #[test]
+    fn background_scrolls() {
+        let mut ppu = ppu_with(LCDC_BG_ENABLE | LCDC_TILE_DATA);
+        fill_tile(&mut ppu, 1, 3);
+        // Tile (1, 1) of the map.
+        ppu.vram[BG_MAP_LOW + 32 + 1] = 1;
+        ppu.write_register(SCX_ADDR, 4);
+        ppu.write_register(SCY_ADDR, 2);
+
+        run_frame(&mut ppu);
+
+        assert_eq!(pixel(&ppu, 3, 5), 0);
+        assert_eq!(pixel(&ppu, 4, 6), 3);
+        assert_eq!(pixel(&ppu, 11, 13), 3);
+        assert_eq!(pixel(&ppu, 12, 14), 0);
+    }
+
+    #[test]
+    fn background_disabled_is_white() {
+        let mut ppu = ppu_with(LCDC_TILE_DATA);
+        fill_tile(&mut ppu, 0, 3);
+
+        run_frame(&mut ppu);
+
+        assert!(ppu.framebuffer().iter().all(|shade| *shade == 0));
+    }
+
+    #[test]
+    fn window_covers_background() {
+        let mut ppu =
+            ppu_with(LCDC_BG_ENABLE | LCDC_TILE_DATA | LCDC_WINDOW_ENABLE | LCDC_WINDOW_MAP);
+        fill_tile(&mut ppu, 1, 2);
+        for tile in 0..0x400 {
+            ppu.vram[BG_MAP_HIGH + tile] = 1;
+        }
+        ppu.write_register(WX_ADDR, 7 + 80);
+        ppu.write_register(WY_ADDR, 100);
+
+        run_frame(&mut ppu);
+
+        assert_eq!(pixel(&ppu, 79, 100), 0);
+        assert_eq!(pixel(&ppu, 80, 100), 2);
+        assert_eq!(pixel(&ppu, 80, 99), 0);
+        assert_eq!(pixel(&ppu, 159, 143), 2);
+    }
+
+    #[test]
+    fn window_line_counter_only_advances_when_drawn() {
+        let mut ppu = ppu_with(LCDC_BG_ENABLE | LCDC_TILE_DATA | LCDC_WINDOW_ENABLE);
+        ppu.write_register(WX_ADDR, 200);
+
+        ppu.tick(DOTS_PER_LINE * 10);
+        assert_eq!(ppu.window_line, 0);
+
+        ppu.write_register(WX_ADDR, 7);
+        ppu.tick(DOTS_PER_LINE * 10);
+        assert_eq!(ppu.window_line, 10);
+    }
+
+    #[test]
+    fn ten_sprites_per_line() {
+        let mut ppu = ppu_with(LCDC_OBJ_ENABLE);
+        for index in 0..12 {
+            set_sprite(&mut ppu, index, 16, 8 + index as u8 * 8, 0, 0);
+        }
+
+        let sprites = ppu.select_sprites();
+
+        assert_eq!(sprites.len(), SPRITES_PER_LINE);
+        assert_eq!(sprites.last().unwrap().index, 9);
+    }
+
+    #[test]
+    fn sprite_priority_by_x_then_oam_index() {
+        let mut ppu = ppu_with(LCDC_OBJ_ENABLE);
+        fill_tile(&mut ppu, 1, 1);
+        fill_tile(&mut ppu, 2, 2);
+        fill_tile(&mut ppu, 3, 3);
+        set_sprite(&mut ppu, 0, 16, 12, 1, 0);
+        set_sprite(&mut ppu, 1, 16, 10, 2, 0);
+        set_sprite(&mut ppu, 2, 16, 10, 3, 0);
+
+        run_frame(&mut ppu);
+
+        assert_eq!(pixel(&ppu, 1, 0), 0);
+        assert_eq!(pixel(&ppu, 2, 0), 2);
+        assert_eq!(pixel(&ppu, 9, 0), 2);
+        assert_eq!(pixel(&ppu, 10, 0), 1);
+        assert_eq!(pixel(&ppu, 12, 0), 0);
+    }
+
+    #[test]
+    fn sprite_behind_background_and_palettes() {
+        let mut ppu = ppu_with(LCDC_BG_ENABLE | LCDC_OBJ_ENABLE | LCDC_TILE_DATA);
+        fill_tile(&mut ppu, 1, 1);
+        fill_tile(&mut ppu, 2, 1);
+        ppu.vram[BG_MAP_LOW] = 1;
+        set_sprite(&mut ppu, 0, 16, 8, 2, OBJ_BEHIND_BG);
+        set_sprite(&mut ppu, 1, 16, 16, 2, OBJ_PALETTE);
+
+        run_frame(&mut ppu);
+
+        assert_eq!(pixel(&ppu, 0, 0), 1);
+        assert_eq!(pixel(&ppu, 8, 0), 2);
+    }
+
+    #[test]
+    fn tall_sprites_ignore_tile_low_bit_and_flip() {
+        let mut ppu = ppu_with(LCDC_OBJ_ENABLE | LCDC_OBJ_SIZE);
+        fill_tile(&mut ppu, 4, 1);
+        fill_tile(&mut ppu, 5, 3);
+        set_sprite(&mut ppu, 0, 16, 8, 5, 0);
+        set_sprite(&mut ppu, 1, 16, 16, 5, OBJ_FLIP_Y);
+
+        run_frame(&mut ppu);
+
+        assert_eq!(pixel(&ppu, 0, 0), 1);
+        assert_eq!(pixel(&ppu, 0, 15), 3);
+        assert_eq!(pixel(&ppu, 8, 0), 3);
+        assert_eq!(pixel(&ppu, 8, 15), 1);
+    }
+}
@@ -0,0 +1,147 @@
+//! Length of mode 3, which grows with fine scroll, the window and sprites
+//! while HBlank shrinks to keep lines at 456 dots.
+
+use super::*;
+
+/// Dots the fetcher loses restarting on the window.
+pub const WINDOW_PENALTY: u32 = 6;
+/// Dots every sprite fetch costs, before waiting on the background fetch.
+pub const SPRITE_PENALTY: u32 = 6;
+/// Dots a sprite at OAM X 0 always costs, whatever the scroll.
+const LEFT_EDGE_SPRITE_PENALTY: u32 = 11;
+/// Rightmost OAM X still fetched, sprites past it cost nothing.
+const LAST_FETCHED_SPRITE_X: u8 = 167;
+
+impl Ppu {
+    /// Mode 3 length of the current line.
+    pub(super) fn drawing_dots(&self) -> u32 {
+        // The fetcher throws away the pixels SCX scrolls past.
+        let mut dots = DRAWING_DOTS + u32::from(self.scx % 8);
+
+        if self.window_visible() && self.lcdc & LCDC_BG_ENABLE != 0 {
+            dots += WINDOW_PENALTY;
+        }
+
+        if self.lcdc & LCDC_OBJ_ENABLE != 0 {
+            dots += self.sprite_penalty(&self.select_sprites());
+        }
+
+        dots
+    }
+
+    /// Dots `sprites`, in drawing order, add to mode 3. Each costs a flat
+    /// penalty, plus the wait for the background fetch of its tile when no
+    /// earlier sprite already waited on the same tile.
+    pub(super) fn sprite_penalty(&self, sprites: &[Sprite]) -> u32 {
+        let mut waited_tiles: Vec<u16> = Vec::new();
+        let mut dots = 0;
+
+        for sprite in sprites {
+            if sprite.x > LAST_FETCHED_SPRITE_X {
+                continue;
+            }
+            if sprite.x == 0 {
+                dots += LEFT_EDGE_SPRITE_PENALTY;
+                continue;
+            }
+
+            let position = u16::from(sprite.x) + u16::from(self.scx % 8);
+            let tile = position / 8;
+            if !waited_tiles.contains(&tile) {
+                waited_tiles.push(tile);
+                // Pixels of the tile right of the sprite, less the two the
+                // fetcher has already overlapped.
+                let remaining = 7 - u32::from(position % 8);
+                dots += remaining.saturating_sub(2);
+            }
+
+            dots += SPRITE_PENALTY;
+        }
+
+        dots
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ppu_with(lcdc: u8) -> Ppu {
+        let mut ppu = Ppu::default();
+        ppu.write_register(LCDC_ADDR, LCDC_LCD_ENABLE | lcdc);
+
+        ppu
+    }
+
+    fn set_sprite(ppu: &mut Ppu, index: usize, y: u8, x: u8) {
+        ppu.write_oam((index * 4) as Addr, y);
+        ppu.write_oam((index * 4 + 1) as Addr, x);
+    }
+
+    #[test]
+    fn plain_line() {
+        let ppu = ppu_with(LCDC_BG_ENABLE);
+
+        assert_eq!(ppu.drawing_dots(), DRAWING_DOTS);
+    }
+
+    #[test]
+    fn fine_scroll_delays() {
+        let mut ppu = ppu_with(LCDC_BG_ENABLE);
+        ppu.write_register(SCX_ADDR, 0x0B);
+
+        assert_eq!(ppu.drawing_dots(), DRAWING_DOTS + 3);
+    }
+
+    #[test]
+    fn window_delays() {
+        let mut ppu = ppu_with(LCDC_BG_ENABLE | LCDC_WINDOW_ENABLE);
+        ppu.write_register(WX_ADDR, 7);
+        ppu.write_register(WY_ADDR, 1);
+
+        assert_eq!(ppu.drawing_dots(), DRAWING_DOTS);
+
+        ppu.write_register(WY_ADDR, 0);
+        assert_eq!(ppu.drawing_dots(), DRAWING_DOTS + WINDOW_PENALTY);
+    }
+
+    #[test]
+    fn sprites_delay() {
+        let mut ppu = ppu_with(LCDC_BG_ENABLE | LCDC_OBJ_ENABLE);
+        // Aligned on a tile: 5 dots of wait and the flat penalty.
+        set_sprite(&mut ppu, 0, 16, 8);
+        // Same tile, the wait is not paid again.
+        set_sprite(&mut ppu, 1, 16, 10);
+        // Last pixels of a tile, nothing to wait for.
+        set_sprite(&mut ppu, 2, 16, 30);
+        // Off screen to the right, not fetched.
+        set_sprite(&mut ppu, 3, 16, 170);
+
+        assert_eq!(ppu.drawing_dots(), DRAWING_DOTS + 11 + 6 + 6);
+    }
+
+    #[test]
+    fn left_edge_sprite_delay() {
+        let mut ppu = ppu_with(LCDC_BG_ENABLE | LCDC_OBJ_ENABLE);
+        ppu.write_register(SCX_ADDR, 5);
+        set_sprite(&mut ppu, 0, 16, 0);
+
+        assert_eq!(ppu.drawing_dots(), DRAWING_DOTS + 5 + 11);
+    }
+
+    #[test]
+    fn hblank_shrinks_with_drawing() {
+        let mut ppu = ppu_with(LCDC_BG_ENABLE);
+        ppu.write_register(SCX_ADDR, 7);
+
+        ppu.tick(OAM_SCAN_DOTS + DRAWING_DOTS);
+        assert_eq!(ppu.mode(), Mode::Drawing);
+
+        ppu.tick(7);
+        assert_eq!(ppu.mode(), Mode::HBlank);
+
+        ppu.tick(DOTS_PER_LINE - OAM_SCAN_DOTS - DRAWING_DOTS - 7);
+        assert_eq!(ppu.ly(), 1);
+        assert_eq!(ppu.mode(), Mode::OamScan);
+    }
+}
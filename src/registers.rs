@@ -1,6 +1,4 @@
 use std::default::Default;
-use std::ops::Deref;
-
 macro_rules! register_getter_and_setter {
   (8bits $([$reg:ident, $setter:ident]),*) => {
     $(
@@ -61,7 +59,7 @@ macro_rules! flag_getter_and_setter {
 }
 
 /// Magic flag
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Default, Debug, PartialEq, Clone)]
 pub struct Flag {
     zero: bool,
     sub: bool,
@@ -69,25 +67,22 @@ pub struct Flag {
     carry: bool,
 }
 
-impl Default for Flag {
-    fn default() -> Self {
-        Self {
-            zero: false,
-            sub: false,
-            half_carry: false,
-            carry: false,
-        }
+/// Packs flags into the F register layout, the low nibble is always zero.
+impl From<&Flag> for u8 {
+    fn from(flag: &Flag) -> u8 {
+        let bit = |set: bool, mask: u8| if set { mask } else { 0 };
+
+        bit(flag.zero, 128) | bit(flag.sub, 64) | bit(flag.half_carry, 32) | bit(flag.carry, 16)
     }
 }
 
-impl Deref for Flag {
-    type Target = u8;
-
-    fn deref(&self) -> &Self::Target {
-        &0
+impl From<Flag> for u8 {
+    fn from(flag: Flag) -> u8 {
+        u8::from(&flag)
     }
 }
 
+/// Unpacks the F register layout, the low nibble is ignored.
 impl From<u8> for Flag {
     fn from(val: u8) -> Flag {
         Flag {
@@ -117,7 +112,7 @@ impl Flag {
     ];
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Default, Debug, PartialEq, Clone)]
 pub struct Registers {
     a: u8,
 
@@ -134,26 +129,6 @@ pub struct Registers {
     pub flag: Flag,
 }
 
-impl Default for Registers {
-    fn default() -> Self {
-        Registers {
-            a: 0,
-
-            b: 0,
-            c: 0,
-            d: 0,
-            e: 0,
-            h: 0,
-            l: 0,
-
-            sp: 0,
-            pc: 0,
-
-            flag: Flag::default(),
-        }
-    }
-}
-
 impl Registers {
     register_getter_and_setter![
     8bits
@@ -183,7 +158,7 @@ impl Registers {
 impl Registers {
     pub fn af(&self) -> u16 {
         let h = u16::from(self.a) << 8;
-        let l = u16::from(*self.flag);
+        let l = u16::from(u8::from(&self.flag));
 
         h + l
     }
@@ -201,10 +176,10 @@ impl Registers {
 mod test {
     use super::*;
 
-    type RegisterSetter8 = &'static Fn(&mut Registers, u8);
-    type RegisterGetter8 = &'static Fn(&Registers) -> u8;
-    type RegisterSetter16 = &'static Fn(&mut Registers, u16);
-    type RegisterGetter16 = &'static Fn(&Registers) -> u16;
+    type RegisterSetter8 = &'static dyn Fn(&mut Registers, u8);
+    type RegisterGetter8 = &'static dyn Fn(&Registers) -> u8;
+    type RegisterSetter16 = &'static dyn Fn(&mut Registers, u16);
+    type RegisterGetter16 = &'static dyn Fn(&Registers) -> u16;
 
     fn test_u16_read(
         set_fn: RegisterSetter16,
@@ -219,7 +194,7 @@ mod test {
         set_fn(&mut registers, h + l);
 
         assert_eq!(get_h_fn(&registers), (h >> 8) as u8);
-        assert_eq!(get_l_fn(&registers), (l >> 0) as u8);
+        assert_eq!(get_l_fn(&registers), l as u8);
     }
 
     fn test_u16_write(
@@ -269,4 +244,34 @@ mod test {
     fn hl_write() {
         test_u16_write(&Registers::set_h, &Registers::set_l, &Registers::hl)
     }
+
+    #[test]
+    fn flag_round_trip() {
+        for val in 0..=0xFF_u8 {
+            let flag = Flag::from(val);
+
+            assert_eq!(u8::from(&flag), val & 0xF0);
+            assert_eq!(Flag::from(u8::from(&flag)), flag);
+        }
+    }
+
+    #[test]
+    fn flag_to_u8() {
+        assert_eq!(u8::from(Flag::new(true, false, false, false)), 0b1000_0000);
+        assert_eq!(u8::from(Flag::new(false, true, false, false)), 0b0100_0000);
+        assert_eq!(u8::from(Flag::new(false, false, true, false)), 0b0010_0000);
+        assert_eq!(u8::from(Flag::new(false, false, false, true)), 0b0001_0000);
+    }
+
+    #[test]
+    fn af_round_trip() {
+        let mut registers = Registers::default();
+
+        for val in 0..=0xFFFF_u16 {
+            registers.set_af(val);
+
+            assert_eq!(registers.af(), val & 0xFFF0);
+            assert_eq!(registers.a(), (val >> 8) as u8);
+        }
+    }
 }
@@ -0,0 +1,268 @@
+//! Battery-backed cartridge RAM in the `.sav` layout other emulators use:
+//! the raw external RAM, followed by the 48-byte RTC trailer on cartridges
+//! with a clock.
+
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::mmu::Mmu;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// The cartridge forgets its RAM on power off.
+    NoBattery,
+    /// The save does not fit the cartridge's RAM.
+    SizeMismatch {
+        expected: usize,
+        actual: usize,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "failed to access save: {}", err),
+            Error::NoBattery => write!(f, "cartridge has no battery-backed RAM"),
+            Error::SizeMismatch { expected, actual } => write!(
+                f,
+                "save of {} bytes does not fit cartridge expecting {}",
+                actual, expected
+            ),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Loads the save at `path`, returns false and leaves RAM untouched when
+/// there is none yet.
+pub fn load_from_path<P: AsRef<Path>>(mmu: &mut Mmu, path: P) -> Result<bool> {
+    let save = match fs::read(path) {
+        Ok(save) => save,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(err) => return Err(err.into()),
+    };
+
+    mmu.import_save(&save)?;
+
+    Ok(true)
+}
+
+pub fn write_to_path<P: AsRef<Path>>(mmu: &mut Mmu, path: P) -> Result<()> {
+    if !mmu.has_battery() {
+        return Err(Error::NoBattery);
+    }
+
+    fs::write(path, mmu.export_save())?;
+
+    Ok(())
+}
+
+/// Writes the save only when RAM changed since the last import or export,
+/// returns whether it did.
+pub fn write_if_dirty<P: AsRef<Path>>(mmu: &mut Mmu, path: P) -> Result<bool> {
+    if !mmu.save_dirty() {
+        return Ok(false);
+    }
+
+    write_to_path(mmu, path)?;
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod test {
+    use std::env;
+    use std::path::PathBuf;
+    use std::process;
+    use std::rc::Rc;
+
+    use super::*;
+
+    use crate::cartridge::test::rom_image;
+    use crate::cartridge::Cartridge;
+    use crate::mbc::mbc3::{TimeSource, RTC_SAVE_SIZE};
+
+    fn mmu_with_cartridge(cartridge_type: u8, ram_size_code: u8) -> Mmu {
+        let rom = rom_image(cartridge_type, 0x01, ram_size_code);
+
+        Mmu::with_cartridge(Cartridge::from_bytes(&rom).unwrap()).unwrap()
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("gemuboi-{}-{}.sav", process::id(), name))
+    }
+
+    #[test]
+    fn export_raw_ram() {
+        let mut mmu = mmu_with_cartridge(0x03, 0x02);
+        assert!(mmu.has_battery());
+        assert!(!mmu.save_dirty());
+
+        mmu.write_byte(0x0000, 0x0A).unwrap();
+        mmu.write_byte(0xA001, 0x42).unwrap();
+        assert!(mmu.save_dirty());
+
+        let save = mmu.export_save();
+        assert_eq!(save.len(), 0x2000);
+        assert_eq!(save[1], 0x42);
+        assert!(!mmu.save_dirty());
+    }
+
+    #[test]
+    fn writes_to_disabled_ram_are_not_dirty() {
+        let mut mmu = mmu_with_cartridge(0x03, 0x02);
+
+        mmu.write_byte(0xA001, 0x42).unwrap();
+
+        assert!(!mmu.save_dirty());
+    }
+
+    #[test]
+    fn rewriting_the_same_value_is_not_dirty() {
+        let mut mmu = mmu_with_cartridge(0x03, 0x02);
+        mmu.write_byte(0x0000, 0x0A).unwrap();
+        mmu.write_byte(0xA001, 0x42).unwrap();
+        mmu.export_save();
+
+        mmu.write_byte(0xA001, 0x42).unwrap();
+        assert!(!mmu.save_dirty());
+
+        mmu.write_byte(0xA001, 0x43).unwrap();
+        assert!(mmu.save_dirty());
+    }
+
+    #[test]
+    fn rtc_writes_are_dirty() {
+        let mut mmu = mmu_with_cartridge(0x10, 0x02);
+        mmu.write_byte(0x0000, 0x0A).unwrap();
+        mmu.write_byte(0x4000, 0x08).unwrap();
+
+        mmu.write_byte(0xA000, 0x00).unwrap();
+
+        assert!(mmu.save_dirty());
+    }
+
+    #[test]
+    fn import_raw_ram() {
+        let mut mmu = mmu_with_cartridge(0x1B, 0x03);
+        let mut save = vec![0; 0x8000];
+        save[0x2000 + 5] = 0x99;
+
+        mmu.import_save(&save).unwrap();
+        mmu.write_byte(0x0000, 0x0A).unwrap();
+        mmu.write_byte(0x4000, 0x01).unwrap();
+
+        assert_eq!(mmu.read_byte(0xA005), 0x99);
+    }
+
+    #[test]
+    fn reject_mismatched_save() {
+        let mut mmu = mmu_with_cartridge(0x03, 0x02);
+
+        assert!(matches!(
+            mmu.import_save(&[0; 0x800]),
+            Err(Error::SizeMismatch {
+                expected: 0x2000,
+                actual: 0x800
+            })
+        ));
+    }
+
+    #[test]
+    fn reject_cartridge_without_battery() {
+        let mut mmu = mmu_with_cartridge(0x02, 0x02);
+
+        assert!(matches!(
+            mmu.import_save(&[0; 0x2000]),
+            Err(Error::NoBattery)
+        ));
+        assert!(matches!(
+            write_to_path(&mut mmu, temp_path("no-battery")),
+            Err(Error::NoBattery)
+        ));
+    }
+
+    #[test]
+    fn rtc_trailer_follows_ram() {
+        let mut mmu = mmu_with_cartridge(0x10, 0x02);
+        mmu.controller_mut()
+            .rtc_mut()
+            .unwrap()
+            .set_time_source(TimeSource::Custom(Rc::new(|| 1234)));
+
+        mmu.write_byte(0x0000, 0x0A).unwrap();
+        mmu.write_byte(0x4000, 0x09).unwrap();
+        mmu.write_byte(0xA000, 0x2A).unwrap();
+
+        let save = mmu.export_save();
+        assert_eq!(save.len(), 0x2000 + RTC_SAVE_SIZE);
+        assert_eq!(&save[0x2004..0x2008], &[0x2A, 0, 0, 0]);
+        assert_eq!(&save[0x2000 + 40..], &1234_u64.to_le_bytes());
+
+        let mut restored = mmu_with_cartridge(0x10, 0x02);
+        restored
+            .controller_mut()
+            .rtc_mut()
+            .unwrap()
+            .set_time_source(TimeSource::Custom(Rc::new(|| 1234)));
+        restored.import_save(&save).unwrap();
+        assert_eq!(restored.export_save(), save);
+
+        // Saves with the older 32-bit timestamp are accepted as well.
+        restored.import_save(&save[..save.len() - 4]).unwrap();
+        assert_eq!(restored.export_save(), save);
+
+        // As are saves from emulators which did not keep the clock.
+        restored.import_save(&save[..0x2000]).unwrap();
+    }
+
+    #[test]
+    fn mbc2_save_is_512_bytes() {
+        let mut mmu = mmu_with_cartridge(0x06, 0x00);
+
+        assert_eq!(mmu.export_save().len(), 0x200);
+    }
+
+    #[test]
+    fn round_trip_through_file() {
+        let path = temp_path("round-trip");
+        let _ = fs::remove_file(&path);
+
+        let mut mmu = mmu_with_cartridge(0x03, 0x02);
+        assert!(!load_from_path(&mut mmu, &path).unwrap());
+        assert!(!write_if_dirty(&mut mmu, &path).unwrap());
+        assert!(!path.exists());
+
+        mmu.write_byte(0x0000, 0x0A).unwrap();
+        mmu.write_byte(0xA010, 0x77).unwrap();
+        assert!(write_if_dirty(&mut mmu, &path).unwrap());
+        assert!(!write_if_dirty(&mut mmu, &path).unwrap());
+
+        let mut restored = mmu_with_cartridge(0x03, 0x02);
+        assert!(load_from_path(&mut restored, &path).unwrap());
+        restored.write_byte(0x0000, 0x0A).unwrap();
+        assert_eq!(restored.read_byte(0xA010), 0x77);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,325 @@
+//! The serial port, shifting SB out one bit at a time, most significant
+//! first, while the bits from the other end of the link cable shift in.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::interrupt::Interrupt;
+use crate::mmu::Addr;
+
+pub const SB_ADDR: Addr = 0xFF01;
+pub const SC_ADDR: Addr = 0xFF02;
+
+/// Set to start a transfer, cleared by the hardware once it is done.
+pub const SC_TRANSFER: u8 = 0b1000_0000;
+/// Set to drive the clock, cleared to follow the other end's.
+pub const SC_INTERNAL_CLOCK: u8 = 0b0000_0001;
+const SC_UNUSED_BITS: u8 = 0b0111_1110;
+
+/// T-cycles per bit on the internal clock, 8192 Hz.
+pub const INTERNAL_CLOCK_PERIOD: u32 = 512;
+
+/// The other end of the link cable.
+pub trait SerialEndpoint {
+    /// Takes the bit the Game Boy shifts out and returns the one it shifts
+    /// in.
+    fn exchange_bit(&mut self, bit: bool) -> bool;
+
+    /// T-cycles per bit when this end drives the clock. None leaves
+    /// transfers on the external clock waiting, as without a cable.
+    fn clock_period(&self) -> Option<u32> {
+        None
+    }
+}
+
+#[derive(Clone, Default)]
+pub enum Link {
+    /// No cable, every bit shifted in is 1.
+    #[default]
+    Disconnected,
+    Endpoint(Rc<RefCell<dyn SerialEndpoint>>),
+}
+
+impl Link {
+    fn exchange_bit(&self, bit: bool) -> bool {
+        match self {
+            Link::Disconnected => true,
+            Link::Endpoint(endpoint) => endpoint.borrow_mut().exchange_bit(bit),
+        }
+    }
+
+    fn clock_period(&self) -> Option<u32> {
+        match self {
+            Link::Disconnected => None,
+            Link::Endpoint(endpoint) => endpoint.borrow().clock_period(),
+        }
+    }
+}
+
+impl fmt::Debug for Link {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Link::Disconnected => write!(f, "Disconnected"),
+            Link::Endpoint(_) => write!(f, "Endpoint(..)"),
+        }
+    }
+}
+
+impl PartialEq for Link {
+    fn eq(&self, rhs: &Self) -> bool {
+        match (self, rhs) {
+            (Link::Disconnected, Link::Disconnected) => true,
+            (Link::Endpoint(lhs), Link::Endpoint(rhs)) => Rc::ptr_eq(lhs, rhs),
+            _ => false,
+        }
+    }
+}
+
+/// An endpoint collecting every byte sent to it as text and answering with
+/// 1 bits, the way test ROMs like Blargg's report their results.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct SerialCapture {
+    byte: u8,
+    bits: u8,
+    output: String,
+}
+
+impl SerialCapture {
+    pub fn output(&self) -> &str {
+        &self.output
+    }
+
+    pub fn take_output(&mut self) -> String {
+        std::mem::take(&mut self.output)
+    }
+}
+
+impl SerialEndpoint for SerialCapture {
+    fn exchange_bit(&mut self, bit: bool) -> bool {
+        self.byte = (self.byte << 1) | u8::from(bit);
+        self.bits += 1;
+
+        if self.bits == 8 {
+            self.output.push(char::from(self.byte));
+            self.byte = 0;
+            self.bits = 0;
+        }
+
+        true
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Serial {
+    sb: u8,
+    /// SC bits 7 and 0.
+    sc: u8,
+    /// Bits left to shift in the current transfer.
+    bits: u8,
+    /// T-cycles since the last bit was shifted.
+    elapsed: u32,
+    link: Link,
+}
+
+impl Serial {
+    pub fn is_register(addr: Addr) -> bool {
+        matches!(addr, SB_ADDR | SC_ADDR)
+    }
+
+    pub fn read_register(&self, addr: Addr) -> u8 {
+        match addr {
+            SB_ADDR => self.sb,
+            SC_ADDR => SC_UNUSED_BITS | self.sc,
+            _ => unreachable!("{:#06X} is not a serial register", addr),
+        }
+    }
+
+    pub fn write_register(&mut self, addr: Addr, value: u8) {
+        match addr {
+            SB_ADDR => self.sb = value,
+            SC_ADDR => {
+                self.sc = value & (SC_TRANSFER | SC_INTERNAL_CLOCK);
+                if self.transferring() {
+                    self.bits = 8;
+                    self.elapsed = 0;
+                }
+            }
+            _ => unreachable!("{:#06X} is not a serial register", addr),
+        }
+    }
+
+    pub fn link(&self) -> &Link {
+        &self.link
+    }
+
+    /// Plugs in the other end of the cable, or unplugs it.
+    pub fn set_link(&mut self, link: Link) {
+        self.link = link;
+    }
+
+    pub fn transferring(&self) -> bool {
+        self.sc & SC_TRANSFER != 0
+    }
+
+    /// T-cycles per bit for the current transfer, None while nothing
+    /// drives the clock.
+    fn clock_period(&self) -> Option<u32> {
+        if self.sc & SC_INTERNAL_CLOCK != 0 {
+            Some(INTERNAL_CLOCK_PERIOD)
+        } else {
+            self.link.clock_period()
+        }
+    }
+
+    /// Advances by `cycles` T-cycles, returns the interrupts raised in IF.
+    pub fn tick(&mut self, cycles: u32) -> u8 {
+        if !self.transferring() {
+            return 0;
+        }
+        let period = match self.clock_period() {
+            Some(period) => period,
+            None => return 0,
+        };
+
+        self.elapsed += cycles;
+        while self.elapsed >= period {
+            self.elapsed -= period;
+            self.shift_bit();
+
+            if self.bits == 0 {
+                self.sc &= !SC_TRANSFER;
+                self.elapsed = 0;
+                return Interrupt::Serial.bit();
+            }
+        }
+
+        0
+    }
+
+    fn shift_bit(&mut self) {
+        let received = self.link.exchange_bit(self.sb & 0x80 != 0);
+
+        self.sb = (self.sb << 1) | u8::from(received);
+        self.bits -= 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// An endpoint clocking the transfer and answering with a fixed byte.
+    struct Partner {
+        reply: u8,
+        received: u8,
+    }
+
+    impl SerialEndpoint for Partner {
+        fn exchange_bit(&mut self, bit: bool) -> bool {
+            self.received = (self.received << 1) | u8::from(bit);
+            let out = self.reply & 0x80 != 0;
+            self.reply <<= 1;
+
+            out
+        }
+
+        fn clock_period(&self) -> Option<u32> {
+            Some(16)
+        }
+    }
+
+    fn capturing() -> (Serial, Rc<RefCell<SerialCapture>>) {
+        let capture = Rc::new(RefCell::new(SerialCapture::default()));
+        let mut serial = Serial::default();
+        serial.set_link(Link::Endpoint(capture.clone()));
+
+        (serial, capture)
+    }
+
+    fn send(serial: &mut Serial, byte: u8) {
+        serial.write_register(SB_ADDR, byte);
+        serial.write_register(SC_ADDR, SC_TRANSFER | SC_INTERNAL_CLOCK);
+    }
+
+    #[test]
+    fn sc_unused_bits_read_as_one() {
+        let mut serial = Serial::default();
+        assert_eq!(serial.read_register(SC_ADDR), 0x7E);
+
+        serial.write_register(SC_ADDR, 0xFF);
+        assert_eq!(serial.read_register(SC_ADDR), 0xFF);
+    }
+
+    #[test]
+    fn internal_clock_shifts_a_bit_every_512_cycles() {
+        let (mut serial, capture) = capturing();
+        send(&mut serial, 0b1010_0000);
+
+        assert_eq!(serial.tick(INTERNAL_CLOCK_PERIOD - 1), 0);
+        assert_eq!(serial.read_register(SB_ADDR), 0b1010_0000);
+
+        serial.tick(1);
+        assert_eq!(serial.read_register(SB_ADDR), 0b0100_0001);
+
+        assert_eq!(serial.tick(6 * INTERNAL_CLOCK_PERIOD), 0);
+        assert!(serial.transferring());
+        assert_eq!(serial.tick(INTERNAL_CLOCK_PERIOD), Interrupt::Serial.bit());
+
+        assert!(!serial.transferring());
+        assert_eq!(serial.read_register(SC_ADDR), 0x7F);
+        assert_eq!(serial.read_register(SB_ADDR), 0xFF);
+        assert_eq!(capture.borrow().output(), "\u{A0}");
+    }
+
+    #[test]
+    fn capture_collects_text() {
+        let (mut serial, capture) = capturing();
+
+        for byte in b"Passed\n" {
+            send(&mut serial, *byte);
+            serial.tick(8 * INTERNAL_CLOCK_PERIOD);
+        }
+
+        assert_eq!(capture.borrow_mut().take_output(), "Passed\n");
+        assert_eq!(capture.borrow().output(), "");
+    }
+
+    #[test]
+    fn external_clock_waits_without_a_partner() {
+        let mut serial = Serial::default();
+        serial.write_register(SB_ADDR, 0x42);
+        serial.write_register(SC_ADDR, SC_TRANSFER);
+
+        assert_eq!(serial.tick(100 * INTERNAL_CLOCK_PERIOD), 0);
+        assert!(serial.transferring());
+        assert_eq!(serial.read_register(SB_ADDR), 0x42);
+    }
+
+    #[test]
+    fn external_clock_follows_the_partner() {
+        let partner = Rc::new(RefCell::new(Partner {
+            reply: 0x5A,
+            received: 0,
+        }));
+        let mut serial = Serial::default();
+        serial.set_link(Link::Endpoint(partner.clone()));
+        serial.write_register(SB_ADDR, 0xC3);
+        serial.write_register(SC_ADDR, SC_TRANSFER);
+
+        assert_eq!(serial.tick(8 * 16), Interrupt::Serial.bit());
+
+        assert_eq!(serial.read_register(SB_ADDR), 0x5A);
+        assert_eq!(partner.borrow().received, 0xC3);
+    }
+
+    #[test]
+    fn disconnected_shifts_in_ones() {
+        let mut serial = Serial::default();
+        send(&mut serial, 0x00);
+
+        serial.tick(8 * INTERNAL_CLOCK_PERIOD);
+
+        assert_eq!(serial.read_register(SB_ADDR), 0xFF);
+    }
+}
@@ -0,0 +1,292 @@
+//! DIV and the TIMA timer, both driven by one 16-bit counter. TIMA counts
+//! falling edges of a counter bit picked by TAC, which is why resetting DIV
+//! or changing TAC can tick it.
+
+use crate::interrupt::Interrupt;
+use crate::mmu::Addr;
+
+pub const DIV_ADDR: Addr = 0xFF04;
+pub const TIMA_ADDR: Addr = 0xFF05;
+pub const TMA_ADDR: Addr = 0xFF06;
+pub const TAC_ADDR: Addr = 0xFF07;
+
+pub const TAC_ENABLE: u8 = 0b0000_0100;
+const TAC_CLOCK_SELECT: u8 = 0b0000_0011;
+const TAC_UNUSED_BITS: u8 = 0b1111_1000;
+
+/// T-cycles TIMA reads 0x00 after overflowing, before TMA is loaded.
+const RELOAD_DELAY: u8 = 4;
+/// T-cycles during which the reload keeps overriding TIMA writes.
+const RELOAD_WINDOW: u8 = 4;
+
+/// Counter bit whose falling edge ticks TIMA, per TAC clock select: 4096,
+/// 262144, 65536 and 16384 Hz.
+fn clock_bit(tac: u8) -> u16 {
+    match tac & TAC_CLOCK_SELECT {
+        0b00 => 1 << 9,
+        0b01 => 1 << 3,
+        0b10 => 1 << 5,
+        0b11 => 1 << 7,
+        _ => unreachable!(),
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+enum Reload {
+    #[default]
+    Idle,
+    /// TIMA overflowed and reads 0x00, TMA gets loaded once this many
+    /// T-cycles have passed. Writing TIMA now cancels the reload.
+    Pending(u8),
+    /// TMA was just loaded. Writes to TIMA are lost and writes to TMA go
+    /// through to TIMA for this many T-cycles.
+    Reloading(u8),
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Timer {
+    /// DIV is its upper byte.
+    counter: u16,
+    tima: u8,
+    tma: u8,
+    tac: u8,
+    reload: Reload,
+}
+
+impl Timer {
+    pub fn is_register(addr: Addr) -> bool {
+        matches!(addr, DIV_ADDR..=TAC_ADDR)
+    }
+
+    pub fn read_register(&self, addr: Addr) -> u8 {
+        match addr {
+            DIV_ADDR => self.div(),
+            TIMA_ADDR => self.tima,
+            TMA_ADDR => self.tma,
+            TAC_ADDR => TAC_UNUSED_BITS | self.tac,
+            _ => unreachable!("{:#06X} is not a timer register", addr),
+        }
+    }
+
+    pub fn write_register(&mut self, addr: Addr, value: u8) {
+        match addr {
+            DIV_ADDR => self.reset_div(),
+            TIMA_ADDR => match self.reload {
+                Reload::Pending(_) => {
+                    self.reload = Reload::Idle;
+                    self.tima = value;
+                }
+                Reload::Reloading(_) => {}
+                Reload::Idle => self.tima = value,
+            },
+            TMA_ADDR => {
+                self.tma = value;
+                if let Reload::Reloading(_) = self.reload {
+                    self.tima = value;
+                }
+            }
+            TAC_ADDR => {
+                let input = self.input();
+                self.tac = value & !TAC_UNUSED_BITS;
+                self.detect_falling_edge(input);
+            }
+            _ => unreachable!("{:#06X} is not a timer register", addr),
+        }
+    }
+
+    pub fn div(&self) -> u8 {
+        (self.counter >> 8) as u8
+    }
+
+    /// Clears the whole counter, as writing DIV does.
+    pub fn reset_div(&mut self) {
+        let input = self.input();
+        self.counter = 0;
+        self.detect_falling_edge(input);
+    }
+
+    /// Advances by `cycles` T-cycles. Returns the interrupts to raise in IF.
+    pub fn tick(&mut self, cycles: u32) -> u8 {
+        let mut interrupts = 0;
+        for _ in 0..cycles {
+            interrupts |= self.tick_cycle();
+        }
+
+        interrupts
+    }
+
+    fn tick_cycle(&mut self) -> u8 {
+        let mut interrupts = 0;
+
+        self.reload = match self.reload {
+            Reload::Pending(1) => {
+                self.tima = self.tma;
+                interrupts |= Interrupt::Timer.bit();
+                Reload::Reloading(RELOAD_WINDOW)
+            }
+            Reload::Pending(cycles) => Reload::Pending(cycles - 1),
+            Reload::Reloading(1) => Reload::Idle,
+            Reload::Reloading(cycles) => Reload::Reloading(cycles - 1),
+            Reload::Idle => Reload::Idle,
+        };
+
+        let input = self.input();
+        self.counter = self.counter.wrapping_add(1);
+        self.detect_falling_edge(input);
+
+        interrupts
+    }
+
+    /// The signal TIMA counts the falling edges of.
+    fn input(&self) -> bool {
+        self.tac & TAC_ENABLE != 0 && self.counter & clock_bit(self.tac) != 0
+    }
+
+    fn detect_falling_edge(&mut self, before: bool) {
+        if before && !self.input() {
+            self.increment_tima();
+        }
+    }
+
+    fn increment_tima(&mut self) {
+        let (tima, overflow) = self.tima.overflowing_add(1);
+        self.tima = tima;
+
+        if overflow {
+            self.reload = Reload::Pending(RELOAD_DELAY);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn enabled_timer(clock_select: u8) -> Timer {
+        let mut timer = Timer::default();
+        timer.write_register(TAC_ADDR, TAC_ENABLE | clock_select);
+
+        timer
+    }
+
+    #[test]
+    fn div_counts_every_256_cycles() {
+        let mut timer = Timer::default();
+
+        timer.tick(255);
+        assert_eq!(timer.read_register(DIV_ADDR), 0);
+
+        timer.tick(1);
+        assert_eq!(timer.read_register(DIV_ADDR), 1);
+
+        timer.write_register(DIV_ADDR, 0x42);
+        assert_eq!(timer.read_register(DIV_ADDR), 0);
+    }
+
+    #[test]
+    fn clock_selections() {
+        for &(clock_select, period) in &[(0b00, 1024), (0b01, 16), (0b10, 64), (0b11, 256)] {
+            let mut timer = enabled_timer(clock_select);
+
+            timer.tick(period - 1);
+            assert_eq!(timer.read_register(TIMA_ADDR), 0);
+
+            timer.tick(1);
+            assert_eq!(timer.read_register(TIMA_ADDR), 1);
+
+            timer.tick(period * 3);
+            assert_eq!(timer.read_register(TIMA_ADDR), 4);
+        }
+    }
+
+    #[test]
+    fn disabled_timer_does_not_count() {
+        let mut timer = Timer::default();
+        timer.write_register(TAC_ADDR, 0b01);
+
+        timer.tick(1024);
+
+        assert_eq!(timer.read_register(TIMA_ADDR), 0);
+        assert_eq!(timer.read_register(TAC_ADDR), 0b1111_1001);
+    }
+
+    #[test]
+    fn overflow_reloads_after_delay() {
+        let mut timer = enabled_timer(0b01);
+        timer.write_register(TMA_ADDR, 0xAB);
+        timer.write_register(TIMA_ADDR, 0xFF);
+
+        assert_eq!(timer.tick(16), 0);
+        assert_eq!(timer.read_register(TIMA_ADDR), 0x00);
+
+        assert_eq!(timer.tick(3), 0);
+        assert_eq!(timer.read_register(TIMA_ADDR), 0x00);
+
+        assert_eq!(timer.tick(1), Interrupt::Timer.bit());
+        assert_eq!(timer.read_register(TIMA_ADDR), 0xAB);
+    }
+
+    #[test]
+    fn tima_write_during_delay_cancels_reload() {
+        let mut timer = enabled_timer(0b01);
+        timer.write_register(TMA_ADDR, 0xAB);
+        timer.write_register(TIMA_ADDR, 0xFF);
+        timer.tick(16);
+
+        timer.write_register(TIMA_ADDR, 0x12);
+
+        assert_eq!(timer.tick(4), 0);
+        assert_eq!(timer.read_register(TIMA_ADDR), 0x12);
+    }
+
+    #[test]
+    fn writes_during_reload() {
+        let mut timer = enabled_timer(0b01);
+        timer.write_register(TMA_ADDR, 0xAB);
+        timer.write_register(TIMA_ADDR, 0xFF);
+        timer.tick(16 + 4);
+
+        // Lost to the reload.
+        timer.write_register(TIMA_ADDR, 0x12);
+        assert_eq!(timer.read_register(TIMA_ADDR), 0xAB);
+
+        // Goes through to TIMA.
+        timer.write_register(TMA_ADDR, 0xCD);
+        assert_eq!(timer.read_register(TIMA_ADDR), 0xCD);
+
+        timer.tick(4);
+        timer.write_register(TIMA_ADDR, 0x12);
+        assert_eq!(timer.read_register(TIMA_ADDR), 0x12);
+    }
+
+    #[test]
+    fn div_write_can_tick_tima() {
+        let mut timer = enabled_timer(0b01);
+
+        // Bit 3 set, resetting the counter makes it fall.
+        timer.tick(8);
+        timer.write_register(DIV_ADDR, 0);
+        assert_eq!(timer.read_register(TIMA_ADDR), 1);
+
+        // Bit 3 clear, no edge.
+        timer.tick(4);
+        timer.write_register(DIV_ADDR, 0);
+        assert_eq!(timer.read_register(TIMA_ADDR), 1);
+    }
+
+    #[test]
+    fn tac_write_can_tick_tima() {
+        let mut timer = enabled_timer(0b01);
+        timer.tick(8);
+
+        // Disabling drops the input.
+        timer.write_register(TAC_ADDR, 0b01);
+        assert_eq!(timer.read_register(TIMA_ADDR), 1);
+
+        // Switching to a clock bit which is clear drops it too.
+        timer.write_register(TAC_ADDR, TAC_ENABLE | 0b01);
+        // Clock select 0b00, bit 9.
+        timer.write_register(TAC_ADDR, TAC_ENABLE);
+        assert_eq!(timer.read_register(TIMA_ADDR), 2);
+    }
+}